@@ -0,0 +1,53 @@
+use crate::bvh::Bvh;
+use crate::grid::Grid;
+use crate::Element;
+use crate::Number;
+use crate::Ray;
+
+// How the elements of a scene or group are sorted, so a ray only has to test the ones near it.
+#[derive(Debug)]
+pub enum Accelerator {
+    // A tree of boxes. Copes best with elements of very different sizes.
+    Bvh(Bvh),
+    // Equal cells walked in order. Best with lots of similar sized elements, and with no
+    // recursion or pointers it's the one to port to the Hack target.
+    Grid(Grid),
+}
+
+impl Accelerator {
+    pub fn bvh(elements: Vec<Box<dyn Element>>) -> Accelerator {
+        Accelerator::Bvh(Bvh::new(elements))
+    }
+
+    pub fn grid(elements: Vec<Box<dyn Element>>) -> Accelerator {
+        Accelerator::Grid(Grid::new(elements))
+    }
+
+    // The nearest visible hit and what it's on, ignoring `skip` and anything at or beyond
+    // `max_distance`.
+    pub fn nearest(
+        &self,
+        ray: &Ray,
+        max_distance: Option<&Number>,
+        skip: Option<&dyn Element>,
+    ) -> Option<(Number, &dyn Element)> {
+        match self {
+            Accelerator::Bvh(bvh) => bvh.nearest(ray, max_distance, skip),
+            Accelerator::Grid(grid) => grid.nearest(ray, max_distance, skip),
+        }
+    }
+
+    // Whether anything that casts shadows, other than `skip`, is hit before `max_distance`.
+    // Stops at the first one, so it's cheaper than `nearest` for shadow rays.
+    pub fn occluded(
+        &self,
+        ray: &Ray,
+        max_distance: Option<&Number>,
+        skip: Option<&dyn Element>,
+    ) -> bool {
+        match self {
+            Accelerator::Bvh(bvh) => bvh.occluded(ray, max_distance, skip),
+            Accelerator::Grid(grid) => grid.occluded(ray, max_distance, skip),
+        }
+    }
+}
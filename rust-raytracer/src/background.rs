@@ -0,0 +1,141 @@
+use crate::environment::EnvironmentMap;
+use crate::Number;
+use crate::Vec3;
+
+// What a ray sees when it doesn't hit anything.
+#[derive(Debug, Clone)]
+pub enum Background {
+    Constant(Number),
+    // Straight down is `bottom`, straight up is `top`, blending linearly with the ray's y.
+    Gradient {
+        bottom: Number,
+        top: Number,
+    },
+    // Brightens from `horizon` to `zenith` going up, is flat `ground` below the horizon, and has
+    // a sun glow around `sun_direction` (which points towards the sun and should be normalized).
+    // `sun_size` is the cosine of the glow's angular radius.
+    Sky {
+        horizon: Number,
+        zenith: Number,
+        ground: Number,
+        sun_direction: Vec3,
+        sun_size: Number,
+        sun_color: Number,
+    },
+    Environment(EnvironmentMap),
+}
+
+impl Background {
+    pub fn sample(&self, direction: &Vec3) -> Number {
+        match self {
+            Background::Constant(c) => *c,
+            Background::Gradient { bottom, top } => {
+                let mut t = direction.y;
+                t.do_add(&Number::from(1));
+                t.do_div(&Number::from(2));
+
+                lerp(bottom, top, &t)
+            }
+            Background::Sky {
+                horizon,
+                zenith,
+                ground,
+                sun_direction,
+                sun_size,
+                sun_color,
+            } => {
+                if direction.y.is_negative() {
+                    return *ground;
+                }
+
+                let mut color = lerp(horizon, zenith, &direction.y);
+
+                let cos = direction.dot(sun_direction);
+                if cos.cmp(sun_size) > 0 {
+                    // Fades from nothing at the edge of the glow to full strength at its center.
+                    let mut t = cos;
+                    t.do_sub(sun_size);
+                    let mut range = Number::from(1);
+                    range.do_sub(sun_size);
+                    t.do_div(&range);
+                    let t_copy = t;
+                    t.do_mul(&t_copy);
+
+                    let mut sun = *sun_color;
+                    sun.do_mul(&t);
+                    color.do_add(&sun);
+                }
+
+                color
+            }
+            Background::Environment(map) => map.sample(direction),
+        }
+    }
+}
+
+pub fn lerp(from: &Number, to: &Number, t: &Number) -> Number {
+    let mut r = *to;
+    r.do_sub(from);
+    r.do_mul(t);
+    r.do_add(from);
+    r
+}
+
+#[cfg(test)]
+mod test {
+    use super::Background;
+    use crate::vector::Vec3;
+    use crate::Number;
+
+    fn up() -> Vec3 {
+        Vec3 {
+            x: Number::from(0),
+            y: Number::from(1),
+            z: Number::from(0),
+        }
+    }
+
+    fn forward() -> Vec3 {
+        Vec3 {
+            x: Number::from(0),
+            y: Number::from(0),
+            z: Number::from(-1),
+        }
+    }
+
+    #[test]
+    fn test_gradient() {
+        let background = Background::Gradient {
+            bottom: Number::from(0),
+            top: Number::from(1),
+        };
+
+        assert_eq!(background.sample(&up()).to_f64(), 1.0);
+        assert_eq!(background.sample(&forward()).to_f64(), 0.5);
+    }
+
+    #[test]
+    fn test_sky() {
+        let mut half = Number::from(1);
+        half.do_div(&Number::from(2));
+
+        let mut sun_size = Number::from(9);
+        sun_size.do_div(&Number::from(10));
+
+        let background = Background::Sky {
+            horizon: half,
+            zenith: Number::from(0),
+            ground: Number::from(0),
+            sun_direction: up(),
+            sun_size,
+            sun_color: Number::from(2),
+        };
+
+        assert_eq!(background.sample(&forward()).to_f64(), 0.5);
+        assert_eq!(background.sample(&up()).to_f64(), 2.0);
+
+        let mut down = up();
+        down.do_neg();
+        assert!(background.sample(&down).is_zero());
+    }
+}
@@ -0,0 +1,499 @@
+use crate::elements::boxes::{component, slab_span};
+use crate::elements::csg::Span;
+use crate::sidedness::intersect_visible;
+use crate::Element;
+use crate::Number;
+use crate::Ray;
+use crate::Vec3;
+
+// Nodes with this many elements or fewer aren't split any further.
+const LEAF_SIZE: usize = 2;
+
+lazy_static! {
+    // Each element's box is grown by this much, so rounding in the slab test can't make a ray
+    // miss the box but hit what's inside.
+    pub static ref PADDING: Number = Number::from_i16_frac(128);
+}
+
+// An axis-aligned box around an element.
+#[derive(Debug, Clone, Copy)]
+pub struct Bounds {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Bounds {
+    pub fn around(points: &[Vec3]) -> Bounds {
+        let mut bounds = Bounds {
+            min: points[0],
+            max: points[0],
+        };
+        for p in points[1..].iter() {
+            bounds = bounds.union(&Bounds { min: *p, max: *p });
+        }
+        bounds
+    }
+
+    pub fn around_sphere(center: &Vec3, radius: &Number) -> Bounds {
+        Bounds::around(&[*center]).grown(radius)
+    }
+
+    pub fn grown(&self, by: &Number) -> Bounds {
+        let mut by = Vec3 {
+            x: *by,
+            y: *by,
+            z: *by,
+        };
+        let mut max = self.max;
+        max.do_add(&by);
+        by.do_neg();
+        let mut min = self.min;
+        min.do_add(&by);
+        Bounds { min, max }
+    }
+
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        let pick = |a: &Vec3, b: &Vec3, larger: bool| {
+            let mut v = *a;
+            for (c, o) in [(&mut v.x, b.x), (&mut v.y, b.y), (&mut v.z, b.z)].iter_mut() {
+                if o.is_less_than(c) != larger {
+                    **c = *o;
+                }
+            }
+            v
+        };
+        Bounds {
+            min: pick(&self.min, &other.min, false),
+            max: pick(&self.max, &other.max, true),
+        }
+    }
+
+    // Where both overlap. Can come out inside out if they don't, which no ray will hit.
+    pub fn overlap(&self, other: &Bounds) -> Bounds {
+        let flipped = Bounds {
+            min: self.max,
+            max: self.min,
+        }
+        .union(&Bounds {
+            min: other.max,
+            max: other.min,
+        });
+        Bounds {
+            min: flipped.max,
+            max: flipped.min,
+        }
+    }
+
+    pub fn corners(&self) -> [Vec3; 8] {
+        let mut corners = [self.min; 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            if i & 1 != 0 {
+                corner.x = self.max.x;
+            }
+            if i & 2 != 0 {
+                corner.y = self.max.y;
+            }
+            if i & 4 != 0 {
+                corner.z = self.max.z;
+            }
+        }
+        corners
+    }
+
+    // Halfway along each axis, without adding the ends together first.
+    fn center(&self) -> Vec3 {
+        let mut half = Number::from(1);
+        half.do_div(&Number::from(2));
+        let mut center = self.min;
+        center.do_scale(&half);
+        let mut high = self.max;
+        high.do_scale(&half);
+        center.do_add(&high);
+        center
+    }
+
+    pub fn span(&self, ray: &Ray) -> Option<Span> {
+        slab_span(&self.min, &self.max, ray).filter(|span| !span.exit.is_negative())
+    }
+}
+
+// Replaces `nearest` with `elem` if the ray hits it closer than both that and `limit`.
+pub fn test_element<'a>(
+    elem: &'a dyn Element,
+    ray: &Ray,
+    limit: Option<&Number>,
+    skip: Option<&dyn Element>,
+    nearest: &mut Option<(Number, &'a dyn Element)>,
+) {
+    if is_skipped(elem, skip) {
+        return;
+    }
+    let cutoff = nearest.map(|(d, _)| d).or_else(|| limit.copied());
+    if let Some(d) = intersect_visible(elem, ray) {
+        if cutoff.is_none_or(|c| d.is_less_than(&c)) {
+            *nearest = Some((d, elem));
+        }
+    }
+}
+
+// Whether `elem` stops a shadow ray before `limit`.
+pub fn blocks(
+    elem: &dyn Element,
+    ray: &Ray,
+    limit: Option<&Number>,
+    skip: Option<&dyn Element>,
+) -> bool {
+    !is_skipped(elem, skip)
+        && elem.casts_shadows()
+        && intersect_visible(elem, ray).is_some_and(|d| limit.is_none_or(|l| d.is_less_than(l)))
+}
+
+// Whether `elem` is the very element `skip` refers to, like the one a ray is leaving.
+fn is_skipped(elem: &dyn Element, skip: Option<&dyn Element>) -> bool {
+    skip.is_some_and(|s| std::ptr::addr_eq(s, elem))
+}
+
+#[derive(Debug)]
+enum Node {
+    Leaf {
+        bounds: Bounds,
+        elements: Vec<Box<dyn Element>>,
+    },
+    Branch {
+        bounds: Bounds,
+        children: Box<[Node; 2]>,
+    },
+}
+
+impl Node {
+    // Splits at the middle of the longest axis the elements' centers spread along.
+    fn build(mut items: Vec<(Bounds, Box<dyn Element>)>) -> Node {
+        let bounds = items[1..]
+            .iter()
+            .fold(items[0].0, |all, (b, _)| all.union(b));
+        if items.len() <= LEAF_SIZE {
+            return Node::Leaf {
+                bounds,
+                elements: items.into_iter().map(|(_, e)| e).collect(),
+            };
+        }
+
+        let centers: Vec<Vec3> = items.iter().map(|(b, _)| b.center()).collect();
+        let spread = Bounds::around(&centers);
+        let mut extent = spread.max;
+        extent.do_sub(&spread.min);
+        let axis = if extent.x.cmp(&extent.y) >= 0 && extent.x.cmp(&extent.z) >= 0 {
+            0
+        } else if extent.y.cmp(&extent.z) >= 0 {
+            1
+        } else {
+            2
+        };
+        let middle = component(&spread.center(), axis);
+
+        let (mut left, mut right): (Vec<_>, Vec<_>) = items
+            .drain(..)
+            .partition(|(b, _)| component(&b.center(), axis).is_less_than(&middle));
+
+        // All the centers were too close together to split that way, so just halve them.
+        if left.is_empty() || right.is_empty() {
+            left.append(&mut right);
+            left.sort_by(|(a, _), (b, _)| {
+                component(&a.center(), axis)
+                    .cmp(&component(&b.center(), axis))
+                    .cmp(&0)
+            });
+            right = left.split_off(left.len() / 2);
+        }
+
+        Node::Branch {
+            bounds,
+            children: Box::new([Node::build(left), Node::build(right)]),
+        }
+    }
+
+    fn bounds(&self) -> &Bounds {
+        match self {
+            Node::Leaf { bounds, .. } => bounds,
+            Node::Branch { bounds, .. } => bounds,
+        }
+    }
+
+    // Looks inside for anything nearer than `nearest` or `limit`. `span` is where the ray crosses
+    // this node's bounds.
+    fn visit<'a>(
+        &'a self,
+        ray: &Ray,
+        span: &Span,
+        limit: Option<&Number>,
+        skip: Option<&dyn Element>,
+        nearest: &mut Option<(Number, &'a dyn Element)>,
+    ) {
+        let cutoff = nearest.map(|(d, _)| d).or_else(|| limit.copied());
+        if cutoff.is_some_and(|c| !span.enter.is_less_than(&c)) {
+            return;
+        }
+
+        match self {
+            Node::Leaf { elements, .. } => {
+                for elem in elements.iter() {
+                    test_element(elem.as_ref(), ray, limit, skip, nearest);
+                }
+            }
+            Node::Branch { children, .. } => {
+                // Nearer child first, so the further one can often be skipped.
+                let mut crossed: Vec<(&Node, Span)> = children
+                    .iter()
+                    .filter_map(|child| child.bounds().span(ray).map(|s| (child, s)))
+                    .collect();
+                crossed.sort_by(|(_, a), (_, b)| a.enter.cmp(&b.enter).cmp(&0));
+                for (child, span) in crossed.iter() {
+                    child.visit(ray, span, limit, skip, nearest);
+                }
+            }
+        }
+    }
+
+    // Like `visit`, but stops at the first thing in the way rather than looking for the nearest.
+    fn occluded(
+        &self,
+        ray: &Ray,
+        span: &Span,
+        limit: Option<&Number>,
+        skip: Option<&dyn Element>,
+    ) -> bool {
+        if limit.is_some_and(|l| !span.enter.is_less_than(l)) {
+            return false;
+        }
+
+        match self {
+            Node::Leaf { elements, .. } => elements
+                .iter()
+                .any(|elem| blocks(elem.as_ref(), ray, limit, skip)),
+            Node::Branch { children, .. } => children.iter().any(|child| {
+                child
+                    .bounds()
+                    .span(ray)
+                    .is_some_and(|span| child.occluded(ray, &span, limit, skip))
+            }),
+        }
+    }
+}
+
+// The elements of a scene or group, sorted into a tree of boxes so that a ray only has to be
+// tested against the ones whose boxes it passes through.
+#[derive(Debug)]
+pub struct Bvh {
+    root: Option<Node>,
+    // Anything without bounds, like an infinite plane, is tested against every ray.
+    unbounded: Vec<Box<dyn Element>>,
+}
+
+impl Bvh {
+    pub fn new(elements: Vec<Box<dyn Element>>) -> Bvh {
+        let mut bounded = vec![];
+        let mut unbounded = vec![];
+        for elem in elements {
+            match elem.bounds() {
+                Some(b) => bounded.push((b.grown(&PADDING), elem)),
+                None => unbounded.push(elem),
+            }
+        }
+
+        Bvh {
+            root: if bounded.is_empty() {
+                None
+            } else {
+                Some(Node::build(bounded))
+            },
+            unbounded,
+        }
+    }
+
+    // The nearest visible hit and what it's on, ignoring `skip` and anything at or beyond
+    // `max_distance`.
+    pub fn nearest(
+        &self,
+        ray: &Ray,
+        max_distance: Option<&Number>,
+        skip: Option<&dyn Element>,
+    ) -> Option<(Number, &dyn Element)> {
+        let mut nearest: Option<(Number, &dyn Element)> = None;
+
+        for elem in self.unbounded.iter() {
+            test_element(elem.as_ref(), ray, max_distance, skip, &mut nearest);
+        }
+
+        if let Some(root) = &self.root {
+            if let Some(span) = root.bounds().span(ray) {
+                root.visit(ray, &span, max_distance, skip, &mut nearest);
+            }
+        }
+
+        nearest
+    }
+
+    // Whether anything that casts shadows, other than `skip`, is hit before `max_distance`.
+    pub fn occluded(
+        &self,
+        ray: &Ray,
+        max_distance: Option<&Number>,
+        skip: Option<&dyn Element>,
+    ) -> bool {
+        self.unbounded
+            .iter()
+            .any(|elem| blocks(elem.as_ref(), ray, max_distance, skip))
+            || self.root.as_ref().is_some_and(|root| {
+                root.bounds()
+                    .span(ray)
+                    .is_some_and(|span| root.occluded(ray, &span, max_distance, skip))
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Bounds, Bvh};
+    use crate::elements::plane::Plane;
+    use crate::elements::sphere::Sphere;
+    use crate::material::Surface;
+    use crate::sidedness::{intersect_visible, Sidedness};
+    use crate::vector::{vec3, Vec3};
+    use crate::Element;
+    use crate::Number;
+    use crate::Ray;
+    use rstest::rstest;
+
+    fn sphere(x: i16, y: i16, z: i16) -> Box<dyn Element> {
+        Box::new(Sphere {
+            center: vec3(x, y, z),
+            radius: Number::from(1),
+            color: Number::from(1),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        })
+    }
+
+    // A grid of spheres over a floor.
+    fn elements() -> Vec<Box<dyn Element>> {
+        let mut elements: Vec<Box<dyn Element>> = vec![Box::new(Plane::new(
+            vec3(0, -2, 0),
+            vec3(0, 1, 0),
+            Number::from(1),
+            Surface::Diffuse,
+            Sidedness::Both,
+        ))];
+        for x in -2..3 {
+            for z in 1..4 {
+                elements.push(sphere(x * 3, x, -3 * z));
+            }
+        }
+        elements
+    }
+
+    #[rstest]
+    #[case(vec3(0, 0, -1))]
+    #[case(vec3(3, 1, -3))]
+    #[case(vec3(-6, -2, -9))]
+    #[case(vec3(1, -1, -6))]
+    #[case(vec3(5, 0, -1))]
+    #[case(vec3(0, 1, 0))]
+    fn test_bvh_matches_every_element(#[case] direction: Vec3) {
+        let mut direction = direction;
+        direction.do_normalize();
+        let ray = Ray {
+            origin: vec3(0, 0, 0),
+            direction,
+        };
+
+        let expected = elements()
+            .iter()
+            .filter_map(|e| intersect_visible(e.as_ref(), &ray))
+            .min_by(|a, b| a.cmp(b).cmp(&0));
+        let actual = Bvh::new(elements())
+            .nearest(&ray, None, None)
+            .map(|(d, _)| d);
+
+        match expected {
+            None => assert!(actual.is_none()),
+            Some(d) => assert!(f64::abs(actual.unwrap().to_f64() - d.to_f64()) < 0.001),
+        }
+    }
+
+    #[rstest]
+    #[case(vec3(0, 0, -1), None, true)]
+    #[case(vec3(0, 0, -1), Some(1), false)]
+    #[case(vec3(0, 0, -1), Some(3), true)]
+    #[case(vec3(0, 1, 0), None, false)]
+    #[case(vec3(3, 1, -3), None, true)]
+    fn test_bvh_occluded(
+        #[case] direction: Vec3,
+        #[case] limit: Option<i16>,
+        #[case] expected: bool,
+    ) {
+        let mut direction = direction;
+        direction.do_normalize();
+        let ray = Ray {
+            origin: vec3(0, 0, 0),
+            direction,
+        };
+        let limit = limit.map(Number::from);
+        assert_eq!(
+            Bvh::new(elements()).occluded(&ray, limit.as_ref(), None),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_bvh_skips_shadowless() {
+        let mut shadowless = Sphere {
+            center: vec3(0, 0, -3),
+            radius: Number::from(1),
+            color: Number::from(1),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: false,
+        };
+        let ray = Ray {
+            origin: vec3(0, 0, 0),
+            direction: vec3(0, 0, -1),
+        };
+        let bvh = Bvh::new(vec![Box::new(shadowless)]);
+        assert!(bvh.nearest(&ray, None, None).is_some());
+        assert!(!bvh.occluded(&ray, None, None));
+
+        shadowless.casts_shadows = true;
+        assert!(Bvh::new(vec![Box::new(shadowless)]).occluded(&ray, None, None));
+    }
+
+    #[test]
+    fn test_bvh_max_distance() {
+        let bvh = Bvh::new(elements());
+        let ray = Ray {
+            origin: vec3(0, 0, 0),
+            direction: vec3(0, 0, -1),
+        };
+        // The first sphere ahead is 2 away.
+        assert!(bvh.nearest(&ray, Some(&Number::from(1)), None).is_none());
+        assert!(bvh.nearest(&ray, Some(&Number::from(3)), None).is_some());
+    }
+
+    #[test]
+    fn test_bounds() {
+        let a = Bounds::around(&[vec3(0, 0, 0), vec3(2, -1, 1)]);
+        let b = Bounds::around_sphere(&vec3(2, 0, 0), &Number::from(1));
+
+        let both = a.union(&b);
+        assert_eq!(both.min.y.to_f64(), -1.0);
+        assert_eq!(both.max.x.to_f64(), 3.0);
+
+        let overlap = a.overlap(&b);
+        assert_eq!(overlap.min.x.to_f64(), 1.0);
+        assert_eq!(overlap.max.x.to_f64(), 2.0);
+        assert_eq!(overlap.max.y.to_f64(), 0.0);
+
+        assert_eq!(a.corners()[5].x.to_f64(), 2.0);
+        assert_eq!(a.corners()[5].z.to_f64(), 1.0);
+    }
+}
@@ -0,0 +1,544 @@
+use crate::bvh::Bounds;
+use crate::elements::csg::{Solid, Span};
+use crate::hit::Hit;
+use crate::material::Surface;
+use crate::sidedness::{facing_normal, Sidedness};
+use crate::solver::{checked_div, FAR};
+use crate::Element;
+use crate::Number;
+use crate::Ray;
+use crate::Vec3;
+
+// Faces are indexed -x, +x, -y, +y, -z, +z, for `face_colors`.
+
+#[derive(Debug, Clone, Copy)]
+pub struct AlignedBox {
+    pub min: Vec3,
+    pub max: Vec3,
+    pub face_colors: [Number; 6],
+    pub surface: Surface,
+    pub sidedness: Sidedness,
+    pub casts_shadows: bool,
+}
+
+// A box rotated into an arbitrary orthonormal basis.
+#[derive(Debug, Clone, Copy)]
+pub struct OrientedBox {
+    center: Vec3,
+    // Local x, y and z in world space.
+    axes: [Vec3; 3],
+    // Half of the box's width, height and depth along `axes`.
+    half_size: Vec3,
+    face_colors: [Number; 6],
+    surface: Surface,
+    sidedness: Sidedness,
+    // On from `new`; switch it off afterwards if need be.
+    pub casts_shadows: bool,
+}
+
+pub fn component(v: &Vec3, axis: usize) -> Number {
+    match axis {
+        0 => v.x,
+        1 => v.y,
+        _ => v.z,
+    }
+}
+
+// num / den, clamped to +-FAR instead of overflowing when den is close to zero. Zero counts as
+// positive, so a ray parallel to a slab gets the right infinite interval.
+fn saturating_div(num: &Number, den: &Number) -> Number {
+    checked_div(num, den).unwrap_or_else(|| {
+        let mut far = *FAR;
+        if num.is_negative() != den.is_negative() {
+            far.do_neg();
+        }
+        far
+    })
+}
+
+// The slab method. Where the ray's line goes in and comes out, either of which can be behind the
+// ray. FAR stands in for an infinite slab distance.
+pub fn slab_span(min: &Vec3, max: &Vec3, ray: &Ray) -> Option<Span> {
+    let mut near = *FAR;
+    near.do_neg();
+    let mut far = *FAR;
+
+    for axis in 0..3 {
+        let origin = component(&ray.origin, axis);
+        let direction = component(&ray.direction, axis);
+
+        let mut to_min = component(min, axis);
+        to_min.do_sub(&origin);
+        let mut to_max = component(max, axis);
+        to_max.do_sub(&origin);
+
+        let mut t0 = saturating_div(&to_min, &direction);
+        let mut t1 = saturating_div(&to_max, &direction);
+        if t1.is_less_than(&t0) {
+            std::mem::swap(&mut t0, &mut t1);
+        }
+
+        if near.is_less_than(&t0) {
+            near = t0;
+        }
+        if t1.is_less_than(&far) {
+            far = t1;
+        }
+        if far.is_less_than(&near) {
+            return None;
+        }
+    }
+
+    Some(Span {
+        enter: near,
+        exit: far,
+    })
+}
+
+// The entry distance, or the exit distance when the ray starts inside. Anything FAR away counts
+// as a miss.
+fn intersect_slabs(min: &Vec3, max: &Vec3, ray: &Ray) -> Option<Number> {
+    let Span {
+        enter: near,
+        exit: far,
+    } = slab_span(min, max, ray)?;
+
+    if far.is_negative() || !far.is_less_than(&FAR) {
+        return None;
+    }
+
+    if near.is_negative() {
+        Some(far)
+    } else {
+        Some(near)
+    }
+}
+
+// The face whose plane `point` is closest to.
+fn face_at(min: &Vec3, max: &Vec3, point: &Vec3) -> usize {
+    let mut best = 0;
+    let mut best_distance = *FAR;
+
+    for face in 0..6 {
+        let axis = face / 2;
+        let mut distance = component(point, axis);
+        distance.do_sub(&component(
+            if face.is_multiple_of(2) { min } else { max },
+            axis,
+        ));
+        distance.do_abs();
+
+        if distance.is_less_than(&best_distance) {
+            best = face;
+            best_distance = distance;
+        }
+    }
+
+    best
+}
+
+// Where `point` is on `face`, measured from its corner nearest `min` along the next two axes
+// round.
+fn face_uv(min: &Vec3, point: &Vec3, face: usize) -> (Number, Number) {
+    let axis = face / 2;
+    let along = |a: usize| {
+        let mut d = component(point, a);
+        d.do_sub(&component(min, a));
+        d
+    };
+    (along((axis + 1) % 3), along((axis + 2) % 3))
+}
+
+// How far `point` is from the surface of the box, inside or out.
+fn distance_to_box(min: &Vec3, max: &Vec3, point: &Vec3) -> Number {
+    // How far outside each pair of faces the point is, negative when it's between them.
+    let mut gaps = [Number::from(0); 3];
+    for (axis, gap) in gaps.iter_mut().enumerate() {
+        let p = component(point, axis);
+        let mut below = component(min, axis);
+        below.do_sub(&p);
+        let mut above = p;
+        above.do_sub(&component(max, axis));
+        *gap = if below.is_less_than(&above) {
+            above
+        } else {
+            below
+        };
+    }
+
+    let mut widest = gaps[0];
+    for gap in gaps.iter() {
+        if widest.is_less_than(gap) {
+            widest = *gap;
+        }
+    }
+
+    // Inside, the nearest face is the one with the smallest gap.
+    if !widest.is_positive() {
+        widest.do_neg();
+        return widest;
+    }
+
+    for gap in gaps.iter_mut() {
+        if gap.is_negative() {
+            *gap = Number::from(0);
+        }
+    }
+    Vec3 {
+        x: gaps[0],
+        y: gaps[1],
+        z: gaps[2],
+    }
+    .length()
+}
+
+fn face_normal(face: usize) -> Vec3 {
+    let sign = if face.is_multiple_of(2) {
+        Number::from(-1)
+    } else {
+        Number::from(1)
+    };
+    let zero = Number::from(0);
+
+    match face / 2 {
+        0 => Vec3 {
+            x: sign,
+            y: zero,
+            z: zero,
+        },
+        1 => Vec3 {
+            x: zero,
+            y: sign,
+            z: zero,
+        },
+        _ => Vec3 {
+            x: zero,
+            y: zero,
+            z: sign,
+        },
+    }
+}
+
+impl Element for AlignedBox {
+    fn intersect(&self, ray: &Ray) -> Option<Number> {
+        intersect_slabs(&self.min, &self.max, ray)
+    }
+
+    fn hit(&self, ray: &Ray, distance: Number) -> Hit<'_> {
+        let point = ray.at(&distance);
+        let face = face_at(&self.min, &self.max, &point);
+        let (normal, front_face) = facing_normal(&ray.direction, &face_normal(face));
+        let (u, v) = face_uv(&self.min, &point, face);
+
+        Hit {
+            distance,
+            point,
+            normal,
+            geometric_normal: normal,
+            u,
+            v,
+            front_face,
+            color: self.face_colors[face],
+            surface: self.surface,
+            element: self,
+        }
+    }
+
+    fn sidedness(&self) -> Sidedness {
+        self.sidedness
+    }
+
+    fn casts_shadows(&self) -> bool {
+        self.casts_shadows
+    }
+
+    fn is_flat(&self) -> bool {
+        false
+    }
+
+    fn bounds(&self) -> Option<Bounds> {
+        Some(Bounds {
+            min: self.min,
+            max: self.max,
+        })
+    }
+}
+
+impl Solid for AlignedBox {
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        slab_span(&self.min, &self.max, ray).into_iter().collect()
+    }
+
+    fn distance_to_surface(&self, point: &Vec3) -> Number {
+        distance_to_box(&self.min, &self.max, point)
+    }
+}
+
+impl OrientedBox {
+    // The box's local -z faces along `forward`, and its local +y is as close to `up` as it can be
+    // while staying perpendicular to that. Neither needs to be normalized.
+    pub fn new(
+        center: Vec3,
+        half_size: Vec3,
+        forward: &Vec3,
+        up: &Vec3,
+        face_colors: [Number; 6],
+        surface: Surface,
+        sidedness: Sidedness,
+    ) -> OrientedBox {
+        let mut z = *forward;
+        z.do_neg();
+        z.do_normalize();
+
+        let mut x = *up;
+        x.do_cross(&z);
+        x.do_normalize();
+
+        let mut y = z;
+        y.do_cross(&x);
+
+        OrientedBox {
+            center,
+            axes: [x, y, z],
+            half_size,
+            face_colors,
+            surface,
+            sidedness,
+            casts_shadows: true,
+        }
+    }
+
+    fn local_direction(&self, v: &Vec3) -> Vec3 {
+        Vec3 {
+            x: v.dot(&self.axes[0]),
+            y: v.dot(&self.axes[1]),
+            z: v.dot(&self.axes[2]),
+        }
+    }
+
+    fn local_point(&self, point: &Vec3) -> Vec3 {
+        let mut offset = *point;
+        offset.do_sub(&self.center);
+        self.local_direction(&offset)
+    }
+
+    // The basis is orthonormal, so distances along the local ray match the world ones.
+    fn local_ray(&self, ray: &Ray) -> Ray {
+        Ray {
+            origin: self.local_point(&ray.origin),
+            direction: self.local_direction(&ray.direction),
+        }
+    }
+
+    fn local_bounds(&self) -> (Vec3, Vec3) {
+        let mut min = self.half_size;
+        min.do_neg();
+        (min, self.half_size)
+    }
+}
+
+impl Element for OrientedBox {
+    fn intersect(&self, ray: &Ray) -> Option<Number> {
+        let (min, max) = self.local_bounds();
+        intersect_slabs(&min, &max, &self.local_ray(ray))
+    }
+
+    fn hit(&self, ray: &Ray, distance: Number) -> Hit<'_> {
+        let point = ray.at(&distance);
+        let local_point = self.local_point(&point);
+        let (min, max) = self.local_bounds();
+        let face = face_at(&min, &max, &local_point);
+
+        let local = face_normal(face);
+        let mut outward = self.axes[0];
+        outward.do_scale(&local.x);
+        let mut y = self.axes[1];
+        y.do_scale(&local.y);
+        let mut z = self.axes[2];
+        z.do_scale(&local.z);
+        outward.do_add(&y);
+        outward.do_add(&z);
+
+        let (normal, front_face) = facing_normal(&ray.direction, &outward);
+        let (u, v) = face_uv(&min, &local_point, face);
+
+        Hit {
+            distance,
+            point,
+            normal,
+            geometric_normal: normal,
+            u,
+            v,
+            front_face,
+            color: self.face_colors[face],
+            surface: self.surface,
+            element: self,
+        }
+    }
+
+    fn sidedness(&self) -> Sidedness {
+        self.sidedness
+    }
+
+    fn casts_shadows(&self) -> bool {
+        self.casts_shadows
+    }
+
+    fn is_flat(&self) -> bool {
+        false
+    }
+
+    fn bounds(&self) -> Option<Bounds> {
+        let local = Bounds {
+            min: {
+                let mut min = self.half_size;
+                min.do_neg();
+                min
+            },
+            max: self.half_size,
+        };
+        let corners: Vec<Vec3> = local
+            .corners()
+            .iter()
+            .map(|c| {
+                let mut p = self.center;
+                for (axis, along) in self.axes.iter().zip([c.x, c.y, c.z].iter()) {
+                    let mut offset = *axis;
+                    offset.do_scale(along);
+                    p.do_add(&offset);
+                }
+                p
+            })
+            .collect();
+        Some(Bounds::around(&corners))
+    }
+}
+
+impl Solid for OrientedBox {
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        let (min, max) = self.local_bounds();
+        slab_span(&min, &max, &self.local_ray(ray))
+            .into_iter()
+            .collect()
+    }
+
+    fn distance_to_surface(&self, point: &Vec3) -> Number {
+        let (min, max) = self.local_bounds();
+        distance_to_box(&min, &max, &self.local_point(point))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AlignedBox, OrientedBox};
+    use crate::material::Surface;
+    use crate::sidedness::Sidedness;
+    use crate::vector::{vec3, Vec3};
+    use crate::Element;
+    use crate::Number;
+    use crate::Ray;
+    use rstest::rstest;
+
+    fn test_box() -> AlignedBox {
+        AlignedBox {
+            min: vec3(-1, -1, -6),
+            max: vec3(1, 1, -4),
+            face_colors: [
+                Number::from(0),
+                Number::from(1),
+                Number::from(2),
+                Number::from(3),
+                Number::from(4),
+                Number::from(5),
+            ],
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }
+    }
+
+    fn ray(origin: Vec3, direction: Vec3) -> Ray {
+        let mut direction = direction;
+        direction.do_normalize();
+        Ray { origin, direction }
+    }
+
+    #[rstest]
+    #[case(vec3(0, 0, 0), vec3(0, 0, -1), Some(4.0))]
+    #[case(vec3(0, 0, -5), vec3(0, 0, -1), Some(1.0))]
+    #[case(vec3(0, 0, 0), vec3(0, 0, 1), None)]
+    #[case(vec3(0, 5, -5), vec3(0, -1, 0), Some(4.0))]
+    #[case(vec3(2, 0, 0), vec3(0, 0, -1), None)]
+    #[case(vec3(0, 0, 0), vec3(1, 1, -8), Some(66.0_f64.sqrt() / 2.0))]
+    fn test_aligned_intersect(
+        #[case] origin: Vec3,
+        #[case] direction: Vec3,
+        #[case] expected: Option<f64>,
+    ) {
+        let hit = test_box().intersect(&ray(origin, direction));
+        match expected {
+            None => assert!(hit.is_none()),
+            Some(d) => assert!(f64::abs(hit.unwrap().to_f64() - d) < 0.01),
+        }
+    }
+
+    #[rstest]
+    #[case(vec3(-1, 0, -5), vec3(-1, 0, 0), 0)]
+    #[case(vec3(1, 0, -5), vec3(1, 0, 0), 1)]
+    #[case(vec3(0, 1, -5), vec3(0, 1, 0), 3)]
+    #[case(vec3(0, 0, -4), vec3(0, 0, 1), 5)]
+    #[case(vec3(0, 0, -6), vec3(0, 0, -1), 4)]
+    fn test_aligned_faces(#[case] point: Vec3, #[case] normal: Vec3, #[case] face: i16) {
+        // Coming straight in from 2 units out.
+        let mut origin = normal;
+        origin.do_scale(&Number::from(2));
+        origin.do_add(&point);
+        let mut direction = normal;
+        direction.do_neg();
+
+        let b = test_box();
+        let hit = b.hit(&ray(origin, direction), Number::from(2));
+        let n = hit.normal;
+        assert_eq!(n.x.to_f64(), normal.x.to_f64());
+        assert_eq!(n.y.to_f64(), normal.y.to_f64());
+        assert_eq!(n.z.to_f64(), normal.z.to_f64());
+        assert!(hit.front_face);
+        assert_eq!(hit.color.to_f64(), f64::from(face));
+    }
+
+    #[test]
+    fn test_aligned_face_uv() {
+        // On the +z face, x and y measured from the corner at (-1, -1).
+        let b = test_box();
+        let mut origin = vec3(0, 0, 0);
+        origin.y = Number::from_i16_frac(16384);
+        let hit = b.hit(&ray(origin, vec3(0, 0, -1)), Number::from(4));
+        assert_eq!(hit.u.to_f64(), 1.0);
+        assert_eq!(hit.v.to_f64(), 1.5);
+    }
+
+    #[test]
+    fn test_oriented_box() {
+        // A 2x2x2 cube turned 45 degrees about y, so an edge points at the origin.
+        let b = OrientedBox::new(
+            vec3(0, 0, -5),
+            vec3(1, 1, 1),
+            &vec3(1, 0, -1),
+            &vec3(0, 1, 0),
+            [Number::from(1); 6],
+            Surface::Diffuse,
+            Sidedness::Both,
+        );
+
+        let hit = b.intersect(&ray(vec3(0, 0, 0), vec3(0, 0, -1))).unwrap();
+        assert!(f64::abs(hit.to_f64() - (5.0 - 2.0_f64.sqrt())) < 0.01);
+
+        // Just left of the edge is a face pointing back and to the left.
+        let mut point = vec3(0, 0, 0);
+        point.x.do_sub(&Number::from_i16_frac(8192));
+        let forward = ray(point, vec3(0, 0, -1));
+        let n = b.hit(&forward, b.intersect(&forward).unwrap()).normal;
+        assert!(f64::abs(n.x.to_f64() + std::f64::consts::FRAC_1_SQRT_2) < 0.01);
+        assert!(f64::abs(n.z.to_f64() - std::f64::consts::FRAC_1_SQRT_2) < 0.01);
+    }
+}
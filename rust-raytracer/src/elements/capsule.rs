@@ -0,0 +1,329 @@
+use crate::bvh::Bounds;
+use crate::elements::cone::{first_of, Cone};
+use crate::elements::csg::{combine, Solid, Span};
+use crate::elements::sphere::Sphere;
+use crate::hit::{around, Hit};
+use crate::material::Surface;
+use crate::sidedness::{facing_normal, Sidedness};
+use crate::solver::solve_quadratic;
+use crate::Element;
+use crate::Number;
+use crate::Ray;
+use crate::Vec3;
+
+// Every point within `radius` of the line segment from `start` to `end`: a cylinder with
+// hemispherical ends.
+#[derive(Debug, Clone, Copy)]
+pub struct Capsule {
+    pub start: Vec3,
+    pub end: Vec3,
+    pub radius: Number,
+    pub color: Number,
+    pub surface: Surface,
+    pub sidedness: Sidedness,
+    pub casts_shadows: bool,
+}
+
+impl Capsule {
+    fn length(&self) -> Number {
+        let mut spine = self.end;
+        spine.do_sub(&self.start);
+        let mut length = spine.dist_sq();
+        length.do_sqrt();
+        length
+    }
+
+    // The capsule's middle, which intersect_side takes care of.
+    fn side(&self) -> Cone {
+        let mut axis = self.end;
+        axis.do_sub(&self.start);
+        axis.do_normalize();
+
+        Cone {
+            base: self.start,
+            axis,
+            height: self.length(),
+            base_radius: self.radius,
+            top_radius: self.radius,
+            color: self.color,
+            surface: self.surface,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }
+    }
+
+    fn bounding_sphere(&self) -> Sphere {
+        let mut center = self.start;
+        center.do_add(&self.end);
+        center.do_scale(&{
+            let mut half = Number::from(1);
+            half.do_div(&Number::from(2));
+            half
+        });
+
+        let mut radius = self.length();
+        radius.do_div(&Number::from(2));
+        radius.do_add(&self.radius);
+        // Room for sqrt's rounding.
+        let mut slack = radius;
+        slack.do_div(&Number::from(50));
+        radius.do_add(&slack);
+
+        Sphere {
+            center,
+            radius,
+            color: self.color,
+            surface: self.surface,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }
+    }
+
+    // How far along the spine the closest point to `point` is, from 0 at `start` to the length
+    // at `end`. Not clamped.
+    fn along(&self, side: &Cone, point: &Vec3) -> Number {
+        let mut from_start = *point;
+        from_start.do_sub(&self.start);
+        from_start.dot(&side.axis)
+    }
+
+    // `along`, but kept between the ends.
+    fn along_clamped(&self, side: &Cone, point: &Vec3) -> Number {
+        let mut along = self.along(side, point);
+        if along.is_negative() {
+            along = Number::from(0);
+        }
+        if side.height.is_less_than(&along) {
+            along = side.height;
+        }
+        along
+    }
+
+    // Straight out from the closest point on the spine.
+    fn surface_normal(&self, hit_point: &Vec3) -> Vec3 {
+        let side = self.side();
+        let mut closest = side.axis;
+        closest.do_scale(&self.along_clamped(&side, hit_point));
+        closest.do_add(&self.start);
+
+        let mut n = *hit_point;
+        n.do_sub(&closest);
+        n.do_normalize();
+        n
+    }
+
+    // Hits on the end sphere around `center` only count on the outside half of it. `outside`
+    // says whether a distance along the spine is past that end.
+    fn intersect_end<F: Fn(&Number) -> bool>(
+        &self,
+        center: &Vec3,
+        ray: &Ray,
+        outside: F,
+    ) -> Option<Number> {
+        let mut from_center = ray.origin;
+        from_center.do_sub(center);
+
+        let half_b = from_center.dot(&ray.direction);
+        let mut c = from_center.dist_sq();
+        let mut radius_sq = self.radius;
+        radius_sq.do_mul(&self.radius);
+        c.do_sub(&radius_sq);
+
+        let (t0, t1) = solve_quadratic(&Number::from(1), &half_b, &c)?;
+
+        let side = self.side();
+        first_of(&[t0, t1], |t| {
+            let mut p = ray.direction;
+            p.do_scale(t);
+            p.do_add(&ray.origin);
+            outside(&self.along(&side, &p))
+        })
+    }
+}
+
+impl Element for Capsule {
+    fn intersect(&self, ray: &Ray) -> Option<Number> {
+        let (near, mut moved) = self.bounding_sphere().approach(ray)?;
+
+        let length = self.length();
+        let candidates = [
+            self.side().intersect_side(&near),
+            self.intersect_end(&self.start, &near, |along| along.is_negative()),
+            self.intersect_end(&self.end, &near, |along| length.is_less_than(along)),
+        ];
+
+        let mut nearest: Option<Number> = None;
+        for t in candidates.iter().flatten() {
+            if !nearest.is_some_and(|n| n.is_less_than(t)) {
+                nearest = Some(*t);
+            }
+        }
+
+        moved.do_add(&nearest?);
+        Some(moved)
+    }
+
+    // Round the spine, then along it from `start` (v = 0) to `end` (v = 1). The rounded ends
+    // share the v of the rim they're on.
+    fn hit(&self, ray: &Ray, distance: Number) -> Hit<'_> {
+        let point = ray.at(&distance);
+        let outward = self.surface_normal(&point);
+        let (normal, front_face) = facing_normal(&ray.direction, &outward);
+
+        let side = self.side();
+        let mut v = self.along_clamped(&side, &point);
+        v.do_div(&side.height);
+
+        Hit {
+            distance,
+            point,
+            normal,
+            geometric_normal: normal,
+            u: around(&side.axis, &outward),
+            v,
+            front_face,
+            color: self.color,
+            surface: self.surface,
+            element: self,
+        }
+    }
+
+    fn sidedness(&self) -> Sidedness {
+        self.sidedness
+    }
+
+    fn casts_shadows(&self) -> bool {
+        self.casts_shadows
+    }
+
+    fn is_flat(&self) -> bool {
+        false
+    }
+
+    fn bounds(&self) -> Option<Bounds> {
+        Some(Bounds::around(&[self.start, self.end]).grown(&self.radius))
+    }
+}
+
+impl Solid for Capsule {
+    // Inside the side or either of the balls on the ends.
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        let ball = |center: &Vec3| Sphere {
+            center: *center,
+            radius: self.radius,
+            color: self.color,
+            surface: self.surface,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        };
+        let ends = combine(
+            &ball(&self.start).spans(ray),
+            &ball(&self.end).spans(ray),
+            |l, r| l || r,
+        );
+        combine(&self.side().spans(ray), &ends, |l, r| l || r)
+    }
+
+    fn distance_to_surface(&self, point: &Vec3) -> Number {
+        let side = self.side();
+        let mut closest = side.axis;
+        closest.do_scale(&self.along_clamped(&side, point));
+        closest.do_add(&self.start);
+
+        let mut from_spine = *point;
+        from_spine.do_sub(&closest);
+        let mut d = from_spine.length();
+        d.do_sub(&self.radius);
+        d.do_abs();
+        d
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Capsule;
+    use crate::elements::csg::Solid;
+    use crate::material::Surface;
+    use crate::sidedness::Sidedness;
+    use crate::vector::{vec3, Vec3};
+    use crate::Element;
+    use crate::Number;
+    use crate::Ray;
+    use rstest::rstest;
+
+    // Upright, with its spine from y = -1 to y = 1.
+    fn capsule() -> Capsule {
+        Capsule {
+            start: vec3(0, -1, -5),
+            end: vec3(0, 1, -5),
+            radius: Number::from(1),
+            color: Number::from(1),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }
+    }
+
+    #[rstest]
+    #[case(vec3(0, 0, 0), vec3(0, 0, -1), Some(4.0))]
+    #[case(vec3(0, 10, -5), vec3(0, -1, 0), Some(8.0))]
+    #[case(vec3(0, -10, -5), vec3(0, 1, 0), Some(8.0))]
+    // Level with the end of the spine.
+    #[case(vec3(0, 1, 0), vec3(0, 0, -1), Some(4.0))]
+    #[case(vec3(2, 0, 0), vec3(0, 0, -1), None)]
+    // From inside.
+    #[case(vec3(0, 0, -5), vec3(0, 1, 0), Some(2.0))]
+    fn test_capsule_intersect(
+        #[case] origin: Vec3,
+        #[case] direction: Vec3,
+        #[case] expected: Option<f64>,
+    ) {
+        let hit = capsule().intersect(&Ray { origin, direction });
+        match expected {
+            None => assert!(hit.is_none()),
+            Some(d) => assert!(f64::abs(hit.unwrap().to_f64() - d) < 0.02),
+        }
+    }
+
+    #[rstest]
+    #[case(vec3(0, 0, -4), vec3(0, 0, 1))]
+    #[case(vec3(0, 2, -5), vec3(0, 1, 0))]
+    #[case(vec3(-1, -1, -5), vec3(-1, 0, 0))]
+    fn test_capsule_normals(#[case] point: Vec3, #[case] expected: Vec3) {
+        let n = capsule().surface_normal(&point);
+        assert!(f64::abs(n.x.to_f64() - expected.x.to_f64()) < 0.01);
+        assert!(f64::abs(n.y.to_f64() - expected.y.to_f64()) < 0.01);
+        assert!(f64::abs(n.z.to_f64() - expected.z.to_f64()) < 0.01);
+    }
+
+    #[rstest]
+    // In one side and out the other.
+    #[case(vec3(0, 0, 0), vec3(0, 0, -1), vec![(4.0, 6.0)])]
+    // End to end, through both balls and the side between them.
+    #[case(vec3(0, 10, -5), vec3(0, -1, 0), vec![(8.0, 12.0)])]
+    // Starting inside, so it goes in behind the origin.
+    #[case(vec3(0, 0, -5), vec3(0, 1, 0), vec![(-2.0, 2.0)])]
+    #[case(vec3(2, 0, 0), vec3(0, 0, -1), vec![])]
+    fn test_capsule_spans(
+        #[case] origin: Vec3,
+        #[case] direction: Vec3,
+        #[case] expected: Vec<(f64, f64)>,
+    ) {
+        let spans = capsule().spans(&Ray { origin, direction });
+        assert_eq!(spans.len(), expected.len());
+        for (span, (enter, exit)) in spans.iter().zip(expected.iter()) {
+            assert!(f64::abs(span.enter.to_f64() - enter) < 0.02);
+            assert!(f64::abs(span.exit.to_f64() - exit) < 0.02);
+        }
+    }
+
+    #[rstest]
+    #[case(vec3(0, 0, -4), 0.0)]
+    #[case(vec3(0, 4, -5), 2.0)]
+    #[case(vec3(0, 0, -5), 1.0)]
+    #[case(vec3(3, -1, -5), 2.0)]
+    fn test_capsule_distance_to_surface(#[case] point: Vec3, #[case] expected: f64) {
+        let d = capsule().distance_to_surface(&point);
+        assert!(f64::abs(d.to_f64() - expected) < 0.01);
+    }
+}
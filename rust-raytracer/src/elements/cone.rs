@@ -0,0 +1,452 @@
+use crate::bvh::Bounds;
+use crate::elements::csg::{combine, everywhere, quadratic_spans, Solid, Span};
+use crate::elements::sphere::Sphere;
+use crate::hit::{around, Hit};
+use crate::material::Surface;
+use crate::sidedness::{facing_normal, Sidedness};
+use crate::solver::{checked_div, solve_quadratic};
+use crate::Element;
+use crate::Number;
+use crate::Ray;
+use crate::Vec3;
+
+// A capped cone, running `height` along `axis` (which should be normalized) from `base`. The
+// radius goes linearly from `base_radius` to `top_radius`, so a top radius of zero gives a
+// pointed cone and anything else a frustum.
+#[derive(Debug, Clone, Copy)]
+pub struct Cone {
+    pub base: Vec3,
+    pub axis: Vec3,
+    pub height: Number,
+    pub base_radius: Number,
+    pub top_radius: Number,
+    pub color: Number,
+    pub surface: Surface,
+    pub sidedness: Sidedness,
+    pub casts_shadows: bool,
+}
+
+// The parts of a point or direction along and across the cone's axis.
+struct Split {
+    along: Number,
+    across: Vec3,
+}
+
+impl Cone {
+    fn split(&self, v: &Vec3) -> Split {
+        let along = v.dot(&self.axis);
+        let mut across = self.axis;
+        across.do_scale(&along);
+        across.do_neg();
+        across.do_add(v);
+        Split { along, across }
+    }
+
+    // How much the radius grows per unit of height.
+    fn slope(&self) -> Number {
+        let mut k = self.top_radius;
+        k.do_sub(&self.base_radius);
+        k.do_div(&self.height);
+        k
+    }
+
+    fn radius_at(&self, along: &Number) -> Number {
+        let mut r = self.slope();
+        r.do_mul(along);
+        r.do_add(&self.base_radius);
+        r
+    }
+
+    pub fn bounding_sphere(&self) -> Sphere {
+        let mut half_height = self.height;
+        half_height.do_div(&Number::from(2));
+
+        let mut center = self.axis;
+        center.do_scale(&half_height);
+        center.do_add(&self.base);
+
+        let mut widest = self.base_radius;
+        if widest.is_less_than(&self.top_radius) {
+            widest = self.top_radius;
+        }
+
+        let mut radius = half_height;
+        radius.do_mul(&half_height);
+        let mut widest_sq = widest;
+        widest_sq.do_mul(&widest);
+        radius.do_add(&widest_sq);
+        radius.do_sqrt();
+        // sqrt is only good to about 1%.
+        let mut slack = radius;
+        slack.do_div(&Number::from(50));
+        radius.do_add(&slack);
+
+        Sphere {
+            center,
+            radius,
+            color: self.color,
+            surface: self.surface,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }
+    }
+
+    // |across(t)|^2 - radius(t)^2 as a t^2 + 2 half_b t + c, which is negative inside the
+    // (endless, double) cone. The ray should already be close by (see `Sphere::approach`) to
+    // keep the coefficients in range.
+    fn side_quadratic(&self, o: &Split, d: &Split) -> (Number, Number, Number) {
+        let k = self.slope();
+        let radius = self.radius_at(&o.along);
+
+        let mut a = d.across.dot(&d.across);
+        let mut k_along = k;
+        k_along.do_mul(&d.along);
+        let mut k_along_sq = k_along;
+        k_along_sq.do_mul(&k_along);
+        a.do_sub(&k_along_sq);
+
+        let mut half_b = o.across.dot(&d.across);
+        let mut grow = k_along;
+        grow.do_mul(&radius);
+        half_b.do_sub(&grow);
+
+        let mut c = o.across.dot(&o.across);
+        let mut radius_sq = radius;
+        radius_sq.do_mul(&radius);
+        c.do_sub(&radius_sq);
+
+        (a, half_b, c)
+    }
+
+    // Nearest hit on the slanted side, ignoring the caps. The ray should already be close by.
+    pub fn intersect_side(&self, ray: &Ray) -> Option<Number> {
+        let mut from_base = ray.origin;
+        from_base.do_sub(&self.base);
+        let o = self.split(&from_base);
+        let d = self.split(&ray.direction);
+
+        let (a, half_b, c) = self.side_quadratic(&o, &d);
+        let (t0, t1) = solve_quadratic(&a, &half_b, &c)?;
+
+        first_of(&[t0, t1], |t| {
+            let mut along = d.along;
+            along.do_mul(t);
+            along.do_add(&o.along);
+            !along.is_negative() && !self.height.is_less_than(&along)
+        })
+    }
+
+    fn intersect_caps(&self, ray: &Ray) -> Option<Number> {
+        let mut from_base = ray.origin;
+        from_base.do_sub(&self.base);
+        let o = self.split(&from_base);
+        let d = self.split(&ray.direction);
+
+        let mut nearest: Option<Number> = None;
+
+        let mut caps = vec![(Number::from(0), self.base_radius)];
+        if !self.top_radius.is_zero() {
+            caps.push((self.height, self.top_radius));
+        }
+
+        for (along, radius) in caps {
+            let mut to_cap = along;
+            to_cap.do_sub(&o.along);
+
+            let t = match checked_div(&to_cap, &d.along) {
+                Some(t) if !t.is_negative() => t,
+                _ => continue,
+            };
+
+            let mut across = d.across;
+            across.do_scale(&t);
+            across.do_add(&o.across);
+
+            if across.is_within(&radius) && !nearest.is_some_and(|n| n.is_less_than(&t)) {
+                nearest = Some(t);
+            }
+        }
+
+        nearest
+    }
+
+    pub fn surface_normal(&self, hit_point: &Vec3) -> Vec3 {
+        let mut from_base = *hit_point;
+        from_base.do_sub(&self.base);
+        let p = self.split(&from_base);
+
+        let radius = self.radius_at(&p.along);
+        let mut across_length = p.across.dist_sq();
+        across_length.do_sqrt();
+
+        // Whichever surface the point is closest to.
+        let mut to_side = across_length;
+        to_side.do_sub(&radius);
+        to_side.do_abs();
+        let mut to_base = p.along;
+        to_base.do_abs();
+        let mut to_top = p.along;
+        to_top.do_sub(&self.height);
+        to_top.do_abs();
+
+        if to_base.is_less_than(&to_side) && to_base.cmp(&to_top) <= 0 {
+            let mut n = self.axis;
+            n.do_neg();
+            return n;
+        }
+        if to_top.is_less_than(&to_side) && !self.top_radius.is_zero() {
+            return self.axis;
+        }
+
+        // Gradient of |across|^2 - radius^2.
+        let mut n = self.axis;
+        let mut tilt = radius;
+        tilt.do_mul(&self.slope());
+        n.do_scale(&tilt);
+        n.do_neg();
+        n.do_add(&p.across);
+        // Right on the point of a cone.
+        if n.dist_sq().is_zero() {
+            return self.axis;
+        }
+        n.do_normalize();
+        n
+    }
+
+    // Round the axis, then up it from the base (v = 0) to the top (v = 1).
+    pub fn uv(&self, hit_point: &Vec3) -> (Number, Number) {
+        let mut from_base = *hit_point;
+        from_base.do_sub(&self.base);
+        let p = self.split(&from_base);
+
+        let mut v = p.along;
+        v.do_div(&self.height);
+        (around(&self.axis, &p.across), v)
+    }
+}
+
+// The first non-negative distance that passes `valid`.
+pub fn first_of<F: Fn(&Number) -> bool>(candidates: &[Number], valid: F) -> Option<Number> {
+    candidates
+        .iter()
+        .find(|t| !t.is_negative() && valid(t))
+        .copied()
+}
+
+impl Element for Cone {
+    fn intersect(&self, ray: &Ray) -> Option<Number> {
+        let (near, mut moved) = self.bounding_sphere().approach(ray)?;
+
+        let side = self.intersect_side(&near);
+        let caps = self.intersect_caps(&near);
+        let t = match (side, caps) {
+            (Some(s), Some(c)) if c.is_less_than(&s) => c,
+            (Some(s), _) => s,
+            (None, c) => c?,
+        };
+
+        moved.do_add(&t);
+        Some(moved)
+    }
+
+    fn hit(&self, ray: &Ray, distance: Number) -> Hit<'_> {
+        let point = ray.at(&distance);
+        let (normal, front_face) = facing_normal(&ray.direction, &self.surface_normal(&point));
+        let (u, v) = self.uv(&point);
+
+        Hit {
+            distance,
+            point,
+            normal,
+            geometric_normal: normal,
+            u,
+            v,
+            front_face,
+            color: self.color,
+            surface: self.surface,
+            element: self,
+        }
+    }
+
+    fn sidedness(&self) -> Sidedness {
+        self.sidedness
+    }
+
+    fn casts_shadows(&self) -> bool {
+        self.casts_shadows
+    }
+
+    fn is_flat(&self) -> bool {
+        false
+    }
+
+    // Around balls at either end as big as the wider one.
+    fn bounds(&self) -> Option<Bounds> {
+        let mut top = self.axis;
+        top.do_scale(&self.height);
+        top.do_add(&self.base);
+        let radius = if self.base_radius.is_less_than(&self.top_radius) {
+            self.top_radius
+        } else {
+            self.base_radius
+        };
+        Some(Bounds::around(&[self.base, top]).grown(&radius))
+    }
+}
+
+impl Solid for Cone {
+    // Inside the cone around the axis, and between the caps.
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        let (near, moved) = match self.bounding_sphere().approach(ray) {
+            Some(approached) => approached,
+            None => return vec![],
+        };
+
+        let mut from_base = near.origin;
+        from_base.do_sub(&self.base);
+        let o = self.split(&from_base);
+        let d = self.split(&near.direction);
+
+        let mut to_base = o.along;
+        to_base.do_neg();
+        let mut to_top = self.height;
+        to_top.do_sub(&o.along);
+
+        let between_caps = match (
+            checked_div(&to_base, &d.along),
+            checked_div(&to_top, &d.along),
+        ) {
+            (Some(t0), Some(t1)) if t1.is_less_than(&t0) => Span {
+                enter: t1,
+                exit: t0,
+            },
+            (Some(t0), Some(t1)) => Span {
+                enter: t0,
+                exit: t1,
+            },
+            // Running parallel to the caps, so it's between them all the way or not at all.
+            _ if to_base.is_positive() || to_top.is_negative() => return vec![],
+            _ => everywhere(),
+        };
+
+        let (a, half_b, c) = self.side_quadratic(&o, &d);
+        shifted(
+            combine(
+                &quadratic_spans(&a, &half_b, &c),
+                &[between_caps],
+                |l, r| l && r,
+            ),
+            &moved,
+        )
+    }
+
+    fn distance_to_surface(&self, point: &Vec3) -> Number {
+        let mut from_base = *point;
+        from_base.do_sub(&self.base);
+        let p = self.split(&from_base);
+
+        // Both positive outside and negative inside. Close enough for picking a surface, though
+        // not a true distance off a slanted side.
+        let mut side = p.across.length();
+        side.do_sub(&self.radius_at(&p.along));
+        let mut cap = p.along;
+        cap.do_neg();
+        let mut above = p.along;
+        above.do_sub(&self.height);
+        if cap.is_less_than(&above) {
+            cap = above;
+        }
+
+        if side.is_negative() && cap.is_negative() {
+            let mut d = if side.is_less_than(&cap) { cap } else { side };
+            d.do_neg();
+            return d;
+        }
+
+        let zero = Number::from(0);
+        Vec3 {
+            x: if side.is_negative() { zero } else { side },
+            y: if cap.is_negative() { zero } else { cap },
+            z: zero,
+        }
+        .length()
+    }
+}
+
+// Moves spans found from an approached ray back onto the original.
+fn shifted(spans: Vec<Span>, moved: &Number) -> Vec<Span> {
+    spans
+        .into_iter()
+        .map(|mut span| {
+            span.enter.do_add(moved);
+            span.exit.do_add(moved);
+            span
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::Cone;
+    use crate::material::Surface;
+    use crate::sidedness::Sidedness;
+    use crate::vector::{vec3, Vec3};
+    use crate::Element;
+    use crate::Number;
+    use crate::Ray;
+    use rstest::rstest;
+
+    // Pointing up from y = -1 to y = 1, with its base 2 wide.
+    fn cone() -> Cone {
+        Cone {
+            base: vec3(0, -1, -5),
+            axis: vec3(0, 1, 0),
+            height: Number::from(2),
+            base_radius: Number::from(1),
+            top_radius: Number::from(0),
+            color: Number::from(1),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }
+    }
+
+    #[rstest]
+    // Halfway up, the radius is 1/2.
+    #[case(vec3(0, 0, 0), vec3(0, 0, -1), Some(4.5))]
+    // Straight up into the base.
+    #[case(vec3(0, -5, -5), vec3(0, 1, 0), Some(4.0))]
+    // Straight down onto the tip.
+    #[case(vec3(0, 5, -5), vec3(0, -1, 0), Some(4.0))]
+    #[case(vec3(0, 0, 0), vec3(0, 1, 0), None)]
+    // Passes over the tip.
+    #[case(vec3(2, 2, -5), vec3(-1, 0, 0), None)]
+    // From far away, where the quadratic would overflow without approaching first.
+    #[case(vec3(0, 0, 2000), vec3(0, 0, -1), Some(2004.5))]
+    fn test_cone_intersect(
+        #[case] origin: Vec3,
+        #[case] direction: Vec3,
+        #[case] expected: Option<f64>,
+    ) {
+        let hit = cone().intersect(&Ray { origin, direction });
+        match expected {
+            None => assert!(hit.is_none()),
+            Some(d) => assert!(f64::abs(hit.unwrap().to_f64() - d) < 0.02),
+        }
+    }
+
+    #[test]
+    fn test_cone_normals() {
+        let c = cone();
+
+        let base = c.surface_normal(&vec3(0, -1, -5));
+        assert_eq!(base.y.to_f64(), -1.0);
+
+        // The side rises 2 for every 1 it narrows.
+        let mut side = vec3(0, 0, -5);
+        side.z.do_add(&Number::from_i16_frac(16384));
+        let n = c.surface_normal(&side);
+        assert!(f64::abs(n.z.to_f64() - 2.0 / 5.0_f64.sqrt()) < 0.01);
+        assert!(f64::abs(n.y.to_f64() - 1.0 / 5.0_f64.sqrt()) < 0.01);
+    }
+}
@@ -0,0 +1,585 @@
+use crate::bvh::Bounds;
+use crate::hit::Hit;
+use crate::material::Surface;
+use crate::sidedness::Sidedness;
+use crate::solver::{solve_quadratic, FAR};
+use crate::Element;
+use crate::Number;
+use crate::Ray;
+use crate::Vec3;
+
+// A stretch of a ray inside a solid, from where it goes in to where it comes out. Either end can
+// be behind the ray's origin.
+#[derive(Debug, Clone, Copy)]
+pub struct Span {
+    pub enter: Number,
+    pub exit: Number,
+}
+
+// An element with an inside, so that it can be combined with others.
+pub trait Solid: Element {
+    // Every stretch of the ray inside the solid, in order and not overlapping. Stretches entirely
+    // behind the ray's origin can be left out.
+    fn spans(&self, ray: &Ray) -> Vec<Span>;
+
+    // Roughly how far `point` is from the surface. It only has to be good close to the surface,
+    // where it tells which part of a combined solid a hit landed on.
+    fn distance_to_surface(&self, point: &Vec3) -> Number;
+}
+
+pub fn everywhere() -> Span {
+    let mut enter = *FAR;
+    enter.do_neg();
+    Span { enter, exit: *FAR }
+}
+
+// Where a t^2 + 2 half_b t + c <= 0.
+pub fn quadratic_spans(a: &Number, half_b: &Number, c: &Number) -> Vec<Span> {
+    let (t0, t1) = match solve_quadratic(a, half_b, c) {
+        Some(roots) => roots,
+        // It never changes sign, so it's the same everywhere as at t = 0.
+        None if c.is_positive() => return vec![],
+        None => return vec![everywhere()],
+    };
+
+    if t0.cmp(&t1) != 0 {
+        return if a.is_positive() {
+            vec![Span {
+                enter: t0,
+                exit: t1,
+            }]
+        } else {
+            vec![
+                Span {
+                    enter: everywhere().enter,
+                    exit: t0,
+                },
+                Span {
+                    enter: t1,
+                    exit: *FAR,
+                },
+            ]
+        };
+    }
+
+    // One root, either because it's (close enough to) linear, or because the ray just grazes
+    // the surface. The slope there says which.
+    let mut slope = *a;
+    slope.do_mul(&t0);
+    slope.do_add(half_b);
+
+    if slope.is_positive() {
+        vec![Span {
+            enter: everywhere().enter,
+            exit: t0,
+        }]
+    } else if slope.is_negative() {
+        vec![Span {
+            enter: t0,
+            exit: *FAR,
+        }]
+    } else if a.is_positive() {
+        vec![Span {
+            enter: t0,
+            exit: t0,
+        }]
+    } else {
+        vec![everywhere()]
+    }
+}
+
+// Walks along both lists of spans at once, keeping the stretches that `keep` says are inside
+// the combination, given whether they're inside each side.
+pub fn combine(left: &[Span], right: &[Span], keep: fn(bool, bool) -> bool) -> Vec<Span> {
+    let mut edges = vec![];
+    for (spans, is_left) in [(left, true), (right, false)].iter() {
+        for span in spans.iter() {
+            edges.push((span.enter, *is_left));
+            edges.push((span.exit, *is_left));
+        }
+    }
+    // Stable, so a span's enter stays ahead of its exit even when they're equal.
+    edges.sort_by(|a, b| a.0.cmp(&b.0).cmp(&0));
+
+    let mut in_left = false;
+    let mut in_right = false;
+    let mut start: Option<Number> = None;
+    let mut combined = vec![];
+
+    for (t, is_left) in edges {
+        if is_left {
+            in_left = !in_left;
+        } else {
+            in_right = !in_right;
+        }
+
+        let inside = keep(in_left, in_right);
+        match start {
+            None if inside => start = Some(t),
+            Some(enter) if !inside => {
+                combined.push(Span { enter, exit: t });
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    combined
+}
+
+// The nearest end of a span that isn't behind the ray.
+fn first_crossing(spans: &[Span]) -> Option<Number> {
+    spans
+        .iter()
+        .flat_map(|s| vec![s.enter, s.exit])
+        .find(|t| !t.is_negative() && t.is_less_than(&FAR))
+}
+
+// Which side's surface `point` is on, and whether it was the right one.
+fn surface_at<'a>(
+    left: &'a dyn Solid,
+    right: &'a dyn Solid,
+    point: &Vec3,
+) -> (&'a dyn Solid, bool) {
+    if right
+        .distance_to_surface(point)
+        .is_less_than(&left.distance_to_surface(point))
+    {
+        (right, true)
+    } else {
+        (left, false)
+    }
+}
+
+fn nearer_surface(left: &dyn Solid, right: &dyn Solid, point: &Vec3) -> Number {
+    let (side, _) = surface_at(left, right, point);
+    side.distance_to_surface(point)
+}
+
+// Everything inside either side.
+#[derive(Debug)]
+pub struct CsgUnion {
+    pub left: Box<dyn Solid>,
+    pub right: Box<dyn Solid>,
+    pub surface: Surface,
+    pub sidedness: Sidedness,
+    pub casts_shadows: bool,
+}
+
+// Only what's inside both sides.
+#[derive(Debug)]
+pub struct CsgIntersection {
+    pub left: Box<dyn Solid>,
+    pub right: Box<dyn Solid>,
+    pub surface: Surface,
+    pub sidedness: Sidedness,
+    pub casts_shadows: bool,
+}
+
+// The left side with the right side cut out of it. Where the cut shows, it takes the right
+// side's color, so holes can be picked out.
+#[derive(Debug)]
+pub struct CsgDifference {
+    pub left: Box<dyn Solid>,
+    pub right: Box<dyn Solid>,
+    pub surface: Surface,
+    pub sidedness: Sidedness,
+    pub casts_shadows: bool,
+}
+
+impl Solid for CsgUnion {
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        combine(&self.left.spans(ray), &self.right.spans(ray), |l, r| l || r)
+    }
+
+    fn distance_to_surface(&self, point: &Vec3) -> Number {
+        nearer_surface(self.left.as_ref(), self.right.as_ref(), point)
+    }
+}
+
+impl Solid for CsgIntersection {
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        combine(&self.left.spans(ray), &self.right.spans(ray), |l, r| l && r)
+    }
+
+    fn distance_to_surface(&self, point: &Vec3) -> Number {
+        nearer_surface(self.left.as_ref(), self.right.as_ref(), point)
+    }
+}
+
+impl Solid for CsgDifference {
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        combine(&self.left.spans(ray), &self.right.spans(ray), |l, r| {
+            l && !r
+        })
+    }
+
+    fn distance_to_surface(&self, point: &Vec3) -> Number {
+        nearer_surface(self.left.as_ref(), self.right.as_ref(), point)
+    }
+}
+
+impl Element for CsgUnion {
+    fn intersect(&self, ray: &Ray) -> Option<Number> {
+        first_crossing(&self.spans(ray))
+    }
+
+    fn hit(&self, ray: &Ray, distance: Number) -> Hit<'_> {
+        let (side, _) = surface_at(self.left.as_ref(), self.right.as_ref(), &ray.at(&distance));
+        Hit {
+            surface: self.surface,
+            element: self,
+            ..side.hit(ray, distance)
+        }
+    }
+
+    fn sidedness(&self) -> Sidedness {
+        self.sidedness
+    }
+
+    fn casts_shadows(&self) -> bool {
+        self.casts_shadows
+    }
+
+    fn is_flat(&self) -> bool {
+        false
+    }
+
+    fn bounds(&self) -> Option<Bounds> {
+        Some(self.left.bounds()?.union(&self.right.bounds()?))
+    }
+}
+
+impl Element for CsgIntersection {
+    fn intersect(&self, ray: &Ray) -> Option<Number> {
+        first_crossing(&self.spans(ray))
+    }
+
+    fn hit(&self, ray: &Ray, distance: Number) -> Hit<'_> {
+        let (side, _) = surface_at(self.left.as_ref(), self.right.as_ref(), &ray.at(&distance));
+        Hit {
+            surface: self.surface,
+            element: self,
+            ..side.hit(ray, distance)
+        }
+    }
+
+    fn sidedness(&self) -> Sidedness {
+        self.sidedness
+    }
+
+    fn casts_shadows(&self) -> bool {
+        self.casts_shadows
+    }
+
+    fn is_flat(&self) -> bool {
+        false
+    }
+
+    // Either side on its own, if the other goes on forever.
+    fn bounds(&self) -> Option<Bounds> {
+        match (self.left.bounds(), self.right.bounds()) {
+            (Some(left), Some(right)) => Some(left.overlap(&right)),
+            (left, right) => left.or(right),
+        }
+    }
+}
+
+impl Element for CsgDifference {
+    fn intersect(&self, ray: &Ray) -> Option<Number> {
+        first_crossing(&self.spans(ray))
+    }
+
+    // The inside of the cut faces out of the solid that's left, so its sides swap round.
+    fn hit(&self, ray: &Ray, distance: Number) -> Hit<'_> {
+        let (side, is_right) =
+            surface_at(self.left.as_ref(), self.right.as_ref(), &ray.at(&distance));
+        let hit = side.hit(ray, distance);
+        Hit {
+            front_face: hit.front_face != is_right,
+            surface: self.surface,
+            element: self,
+            ..hit
+        }
+    }
+
+    fn sidedness(&self) -> Sidedness {
+        self.sidedness
+    }
+
+    fn casts_shadows(&self) -> bool {
+        self.casts_shadows
+    }
+
+    fn is_flat(&self) -> bool {
+        false
+    }
+
+    fn bounds(&self) -> Option<Bounds> {
+        self.left.bounds()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{combine, CsgDifference, CsgIntersection, CsgUnion, Solid, Span};
+    use crate::elements::boxes::AlignedBox;
+    use crate::elements::capsule::Capsule;
+    use crate::elements::cylinder::Cylinder;
+    use crate::elements::plane::Plane;
+    use crate::elements::sphere::Sphere;
+    use crate::elements::torus::Torus;
+    use crate::material::Surface;
+    use crate::sidedness::Sidedness;
+    use crate::vector::vec3;
+    use crate::Element;
+    use crate::Number;
+    use crate::Ray;
+    use rstest::rstest;
+
+    fn forward() -> Ray {
+        Ray {
+            origin: vec3(0, 0, 0),
+            direction: vec3(0, 0, -1),
+        }
+    }
+
+    fn sphere(z: i16, radius: i16) -> Box<Sphere> {
+        Box::new(Sphere {
+            center: vec3(0, 0, z),
+            radius: Number::from(radius),
+            color: Number::from(1),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        })
+    }
+
+    fn spans(pairs: &[(i16, i16)]) -> Vec<Span> {
+        pairs
+            .iter()
+            .map(|(enter, exit)| Span {
+                enter: Number::from(*enter),
+                exit: Number::from(*exit),
+            })
+            .collect()
+    }
+
+    fn assert_spans(actual: &[Span], expected: &[(f64, f64)]) {
+        assert_eq!(actual.len(), expected.len());
+        for (span, (enter, exit)) in actual.iter().zip(expected.iter()) {
+            assert!(f64::abs(span.enter.to_f64() - enter) < 0.02);
+            assert!(f64::abs(span.exit.to_f64() - exit) < 0.02);
+        }
+    }
+
+    #[test]
+    fn test_combine() {
+        let left = spans(&[(1, 4), (6, 8)]);
+        let right = spans(&[(3, 7)]);
+
+        assert_spans(&combine(&left, &right, |l, r| l || r), &[(1.0, 8.0)]);
+        assert_spans(
+            &combine(&left, &right, |l, r| l && r),
+            &[(3.0, 4.0), (6.0, 7.0)],
+        );
+        assert_spans(
+            &combine(&left, &right, |l, r| l && !r),
+            &[(1.0, 3.0), (7.0, 8.0)],
+        );
+    }
+
+    #[rstest]
+    // Through the middle of both.
+    #[case(0, vec![(4.0, 8.0)])]
+    // Too high for the box.
+    #[case(3, vec![])]
+    fn test_primitive_spans(#[case] y: i16, #[case] expected: Vec<(f64, f64)>) {
+        let ray = Ray {
+            origin: vec3(0, y, 0),
+            direction: vec3(0, 0, -1),
+        };
+
+        let aligned = AlignedBox {
+            min: vec3(-2, -2, -8),
+            max: vec3(2, 2, -4),
+            face_colors: [Number::from(1); 6],
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        };
+        assert_spans(&aligned.spans(&ray), &expected);
+
+        let cylinder = Cylinder {
+            base: vec3(0, 0, -4),
+            axis: vec3(0, 0, -1),
+            height: Number::from(4),
+            radius: Number::from(2),
+            color: Number::from(1),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        };
+        assert_spans(&cylinder.spans(&ray), &expected);
+    }
+
+    #[test]
+    fn test_sphere_and_plane_spans() {
+        assert_spans(&sphere(-5, 2).spans(&forward()), &[(3.0, 7.0)]);
+
+        // Solid beyond z = -5, so the ray goes in and never comes out.
+        let plane = Plane::new(
+            vec3(0, 0, -5),
+            vec3(0, 0, -1),
+            Number::from(1),
+            Surface::Diffuse,
+            Sidedness::Both,
+        );
+        assert_spans(&plane.spans(&forward()), &[(5.0, 16000.0)]);
+    }
+
+    #[rstest]
+    // Into the hole, then out of the far side of the big sphere.
+    #[case(0, 0, Some(8.0))]
+    // Beside the hole.
+    #[case(3, 0, Some(10.0 - 7.0_f64.sqrt()))]
+    fn test_difference(#[case] x: i16, #[case] y: i16, #[case] expected: Option<f64>) {
+        // A sphere with its middle scooped out by a smaller one poking out the front.
+        let scooped = CsgDifference {
+            left: sphere(-10, 4),
+            right: sphere(-6, 2),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        };
+
+        let ray = Ray {
+            origin: vec3(x, y, 0),
+            direction: vec3(0, 0, -1),
+        };
+        let hit = scooped.intersect(&ray);
+        match expected {
+            None => assert!(hit.is_none()),
+            Some(d) => assert!(f64::abs(hit.unwrap().to_f64() - d) < 0.1),
+        }
+    }
+
+    #[test]
+    fn test_difference_normal_faces_out_of_the_hole() {
+        let scooped = CsgDifference {
+            left: sphere(-10, 4),
+            right: sphere(-6, 2),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        };
+
+        // The back of the small sphere is the floor of the hole, seen from the front.
+        let hit = scooped.hit(&forward(), Number::from(8));
+        assert!(hit.front_face);
+        assert!(f64::abs(hit.normal.z.to_f64() - 1.0) < 0.01);
+    }
+
+    #[test]
+    fn test_union_and_intersection() {
+        let union = CsgUnion {
+            left: sphere(-5, 2),
+            right: sphere(-8, 2),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        };
+        assert_spans(&union.spans(&forward()), &[(3.0, 10.0)]);
+        assert!(f64::abs(union.intersect(&forward()).unwrap().to_f64() - 3.0) < 0.01);
+
+        let lens = CsgIntersection {
+            left: sphere(-5, 2),
+            right: sphere(-8, 2),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        };
+        assert!(f64::abs(lens.intersect(&forward()).unwrap().to_f64() - 6.0) < 0.01);
+        // The front of the lens is the back sphere's surface.
+        let hit = lens.hit(&forward(), Number::from(6));
+        assert!(hit.front_face);
+        assert!(f64::abs(hit.normal.z.to_f64() - 1.0) < 0.01);
+
+        let apart = CsgIntersection {
+            left: sphere(-5, 1),
+            right: sphere(-8, 1),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        };
+        assert!(apart.intersect(&forward()).is_none());
+    }
+
+    // A sphere drilled through by a capsule, and a ring with its top half cut off.
+    #[rstest]
+    // Down the drill, and down the ring's hole.
+    #[case(0, None, None)]
+    #[case(2, Some(10.0 - 12.0_f64.sqrt()), None)]
+    #[case(3, Some(10.0 - 7.0_f64.sqrt()), Some(10.0))]
+    fn test_capsule_and_torus(
+        #[case] x: i16,
+        #[case] drilled_hit: Option<f64>,
+        #[case] ring_hit: Option<f64>,
+    ) {
+        let drilled = CsgDifference {
+            left: sphere(-10, 4),
+            right: Box::new(Capsule {
+                start: vec3(0, 0, -4),
+                end: vec3(0, 0, -16),
+                radius: Number::from(1),
+                color: Number::from(1),
+                surface: Surface::Diffuse,
+                sidedness: Sidedness::Both,
+                casts_shadows: true,
+            }),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        };
+        let hit = drilled.intersect(&Ray {
+            origin: vec3(x, 0, 0),
+            direction: vec3(0, 0, -1),
+        });
+        match drilled_hit {
+            None => assert!(hit.is_none()),
+            Some(d) => assert!(f64::abs(hit.unwrap().to_f64() - d) < 0.05),
+        }
+
+        let half_ring = CsgIntersection {
+            left: Box::new(Torus {
+                center: vec3(0, 0, -10),
+                axis: vec3(0, 1, 0),
+                major_radius: Number::from(3),
+                minor_radius: Number::from(1),
+                color: Number::from(1),
+                surface: Surface::Diffuse,
+                sidedness: Sidedness::Both,
+                casts_shadows: true,
+            }),
+            // Solid below y = 0.
+            right: Box::new(Plane::new(
+                vec3(0, 0, -10),
+                vec3(0, -1, 0),
+                Number::from(1),
+                Surface::Diffuse,
+                Sidedness::Both,
+            )),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        };
+        let hit = half_ring.intersect(&Ray {
+            origin: vec3(x, 10, -10),
+            direction: vec3(0, -1, 0),
+        });
+        match ring_hit {
+            None => assert!(hit.is_none()),
+            Some(d) => assert!(f64::abs(hit.unwrap().to_f64() - d) < 0.05),
+        }
+    }
+}
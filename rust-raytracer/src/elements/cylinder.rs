@@ -0,0 +1,149 @@
+use crate::bvh::Bounds;
+use crate::elements::cone::Cone;
+use crate::elements::csg::{Solid, Span};
+use crate::hit::Hit;
+use crate::material::Surface;
+use crate::sidedness::{facing_normal, Sidedness};
+use crate::Element;
+use crate::Number;
+use crate::Ray;
+use crate::Vec3;
+
+// A capped cylinder, running `height` along `axis` (which should be normalized) from `base`.
+#[derive(Debug, Clone, Copy)]
+pub struct Cylinder {
+    pub base: Vec3,
+    pub axis: Vec3,
+    pub height: Number,
+    pub radius: Number,
+    pub color: Number,
+    pub surface: Surface,
+    pub sidedness: Sidedness,
+    pub casts_shadows: bool,
+}
+
+impl Cylinder {
+    // A cylinder is just a cone that doesn't taper.
+    fn as_cone(&self) -> Cone {
+        Cone {
+            base: self.base,
+            axis: self.axis,
+            height: self.height,
+            base_radius: self.radius,
+            top_radius: self.radius,
+            color: self.color,
+            surface: self.surface,
+            sidedness: self.sidedness,
+            casts_shadows: self.casts_shadows,
+        }
+    }
+}
+
+impl Element for Cylinder {
+    fn intersect(&self, ray: &Ray) -> Option<Number> {
+        self.as_cone().intersect(ray)
+    }
+
+    fn hit(&self, ray: &Ray, distance: Number) -> Hit<'_> {
+        let cone = self.as_cone();
+        let point = ray.at(&distance);
+        let (normal, front_face) = facing_normal(&ray.direction, &cone.surface_normal(&point));
+        let (u, v) = cone.uv(&point);
+
+        Hit {
+            distance,
+            point,
+            normal,
+            geometric_normal: normal,
+            u,
+            v,
+            front_face,
+            color: self.color,
+            surface: self.surface,
+            element: self,
+        }
+    }
+
+    fn sidedness(&self) -> Sidedness {
+        self.sidedness
+    }
+
+    fn casts_shadows(&self) -> bool {
+        self.casts_shadows
+    }
+
+    fn is_flat(&self) -> bool {
+        false
+    }
+
+    fn bounds(&self) -> Option<Bounds> {
+        self.as_cone().bounds()
+    }
+}
+
+impl Solid for Cylinder {
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        self.as_cone().spans(ray)
+    }
+
+    fn distance_to_surface(&self, point: &Vec3) -> Number {
+        self.as_cone().distance_to_surface(point)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Cylinder;
+    use crate::material::Surface;
+    use crate::sidedness::Sidedness;
+    use crate::vector::{vec3, Vec3};
+    use crate::Element;
+    use crate::Number;
+    use crate::Ray;
+    use rstest::rstest;
+
+    // Lying along x, from x = -2 to x = 2.
+    fn cylinder() -> Cylinder {
+        Cylinder {
+            base: vec3(-2, 0, -5),
+            axis: vec3(1, 0, 0),
+            height: Number::from(4),
+            radius: Number::from(1),
+            color: Number::from(1),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }
+    }
+
+    #[rstest]
+    #[case(vec3(0, 0, 0), vec3(0, 0, -1), Some(4.0))]
+    #[case(vec3(1, 5, -5), vec3(0, -1, 0), Some(4.0))]
+    #[case(vec3(10, 0, -5), vec3(-1, 0, 0), Some(8.0))]
+    // From inside, out through the far cap.
+    #[case(vec3(0, 0, -5), vec3(1, 0, 0), Some(2.0))]
+    #[case(vec3(3, 0, 0), vec3(0, 0, -1), None)]
+    fn test_cylinder_intersect(
+        #[case] origin: Vec3,
+        #[case] direction: Vec3,
+        #[case] expected: Option<f64>,
+    ) {
+        let hit = cylinder().intersect(&Ray { origin, direction });
+        match expected {
+            None => assert!(hit.is_none()),
+            Some(d) => assert!(f64::abs(hit.unwrap().to_f64() - d) < 0.02),
+        }
+    }
+
+    #[rstest]
+    #[case(vec3(0, 0, -4), vec3(0, 0, 1))]
+    #[case(vec3(1, -1, -5), vec3(0, -1, 0))]
+    #[case(vec3(2, 0, -5), vec3(1, 0, 0))]
+    #[case(vec3(-2, 0, -5), vec3(-1, 0, 0))]
+    fn test_cylinder_normals(#[case] point: Vec3, #[case] expected: Vec3) {
+        let n = cylinder().as_cone().surface_normal(&point);
+        assert!(f64::abs(n.x.to_f64() - expected.x.to_f64()) < 0.01);
+        assert!(f64::abs(n.y.to_f64() - expected.y.to_f64()) < 0.01);
+        assert!(f64::abs(n.z.to_f64() - expected.z.to_f64()) < 0.01);
+    }
+}
@@ -0,0 +1,113 @@
+use crate::bvh::Bounds;
+use crate::elements::plane::intersect_plane;
+use crate::hit::{across, Hit};
+use crate::material::Surface;
+use crate::sidedness::{facing_normal, Sidedness};
+use crate::Element;
+use crate::Number;
+use crate::Ray;
+use crate::Vec3;
+
+#[derive(Debug, Clone, Copy)]
+// Like Plane, `normal` points away from the front.
+pub struct Disc {
+    pub center: Vec3,
+    pub normal: Vec3,
+    pub radius: Number,
+    pub color: Number,
+    pub surface: Surface,
+    pub sidedness: Sidedness,
+    pub casts_shadows: bool,
+}
+
+impl Element for Disc {
+    fn intersect(&self, ray: &Ray) -> Option<Number> {
+        let distance = intersect_plane(&self.center, &self.normal, ray)?;
+
+        let mut from_center = ray.direction;
+        from_center.do_scale(&distance);
+        from_center.do_add(&ray.origin);
+        from_center.do_sub(&self.center);
+
+        if from_center.is_within(&self.radius) {
+            Some(distance)
+        } else {
+            None
+        }
+    }
+
+    // Across the disc in scene units, from its center.
+    fn hit(&self, ray: &Ray, distance: Number) -> Hit<'_> {
+        let point = ray.at(&distance);
+        let mut outward = self.normal;
+        outward.do_neg();
+        let (normal, front_face) = facing_normal(&ray.direction, &outward);
+
+        let mut from_center = point;
+        from_center.do_sub(&self.center);
+        let (u, v) = across(&self.normal, &from_center);
+
+        Hit {
+            distance,
+            point,
+            normal,
+            geometric_normal: normal,
+            u,
+            v,
+            front_face,
+            color: self.color,
+            surface: self.surface,
+            element: self,
+        }
+    }
+
+    fn sidedness(&self) -> Sidedness {
+        self.sidedness
+    }
+
+    fn casts_shadows(&self) -> bool {
+        self.casts_shadows
+    }
+
+    fn is_flat(&self) -> bool {
+        true
+    }
+
+    fn bounds(&self) -> Option<Bounds> {
+        Some(Bounds::around_sphere(&self.center, &self.radius))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Disc;
+    use crate::material::Surface;
+    use crate::sidedness::Sidedness;
+    use crate::vector::{vec3, Vec3};
+    use crate::Element;
+    use crate::Number;
+    use crate::Ray;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(vec3(0, 0, 0), Some(5.0))]
+    #[case(vec3(1, 1, 0), Some(5.0))]
+    #[case(vec3(2, 1, 0), None)]
+    fn test_disc_intersect(#[case] origin: Vec3, #[case] expected: Option<f64>) {
+        let disc = Disc {
+            center: vec3(0, 0, -5),
+            normal: vec3(0, 0, -1),
+            radius: Number::from(2),
+            color: Number::from(1),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        };
+
+        let hit = disc.intersect(&Ray {
+            origin,
+            direction: vec3(0, 0, -1),
+        });
+        assert_eq!(hit.map(|d| d.to_f64()), expected);
+    }
+}
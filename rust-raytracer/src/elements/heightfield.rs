@@ -0,0 +1,616 @@
+use crate::bvh::Bounds;
+use crate::elements::boxes::slab_span;
+use crate::grid::Stepper;
+use crate::hit::Hit;
+use crate::material::Surface;
+use crate::sidedness::{facing_normal, Sidedness};
+use crate::Element;
+use crate::Number;
+use crate::Ray;
+use crate::Vec3;
+use image::{DynamicImage, ImageError, ImageResult};
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+pub struct HeightfieldOptions {
+    // The corner with the smallest x and z, at the height of black.
+    pub origin: Vec3,
+    // How far the image stretches along x (its columns) and z (its rows), and how far above the
+    // origin white is. The top row of the image is furthest back, at the smallest z.
+    pub size: Vec3,
+    pub color: Number,
+    pub surface: Surface,
+    pub sidedness: Sidedness,
+    pub casts_shadows: bool,
+}
+
+// Terrain from a grayscale image, with a height at every pixel and two triangles between each
+// square of four.
+#[derive(Clone)]
+pub struct Heightfield {
+    origin: Vec3,
+    size: Vec3,
+    columns: i16,
+    rows: i16,
+    // Heights and normals at each pixel, row by row.
+    heights: Vec<Number>,
+    normals: Vec<Vec3>,
+    color: Number,
+    surface: Surface,
+    sidedness: Sidedness,
+    casts_shadows: bool,
+}
+
+// Which cell of a grid `count` points wide a position (in cells) is in, kept inside the grid.
+fn cell_index(position: &Number, count: i16) -> i16 {
+    if position.is_negative() {
+        return 0;
+    }
+    let i = i16::try_from(position.to_int32().to_i32()).unwrap_or(i16::MAX);
+    i.min(count - 2)
+}
+
+impl Heightfield {
+    pub fn load<P: AsRef<Path>>(path: P, options: &HeightfieldOptions) -> ImageResult<Heightfield> {
+        Heightfield::from_image(&image::open(path)?, options)
+    }
+
+    // Cells are found by their i16 column and row, so the image can be at most i16::MAX pixels
+    // either way, and it takes at least 2 to make a cell.
+    pub fn from_image(
+        image: &DynamicImage,
+        options: &HeightfieldOptions,
+    ) -> ImageResult<Heightfield> {
+        let gray = image.to_luma8();
+        let (width, height) = gray.dimensions();
+        let count = |pixels: u32| match i16::try_from(pixels) {
+            Ok(n) if n >= 2 => Ok(n),
+            _ => Err(ImageError::IoError(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("heightfield can't be {}x{} pixels", width, height),
+            ))),
+        };
+        let (columns, rows) = (count(width)?, count(height)?);
+
+        // Heights are data rather than a picture, so there's no gamma to undo.
+        let heights = gray
+            .pixels()
+            .map(|pixel| {
+                let mut h = Number::from(i16::from(pixel.0[0]));
+                h.do_div(&Number::from(255));
+                h.do_mul(&options.size.y);
+                h.do_add(&options.origin.y);
+                h
+            })
+            .collect();
+
+        let mut field = Heightfield {
+            origin: options.origin,
+            size: options.size,
+            columns,
+            rows,
+            heights,
+            normals: vec![],
+            color: options.color,
+            surface: options.surface,
+            sidedness: options.sidedness,
+            casts_shadows: options.casts_shadows,
+        };
+
+        for row in 0..field.rows {
+            for column in 0..field.columns {
+                let normal = field.normal_at(column, row);
+                field.normals.push(normal);
+            }
+        }
+
+        Ok(field)
+    }
+
+    fn cell_size(&self) -> (Number, Number) {
+        let mut x = self.size.x;
+        x.do_div(&Number::from(self.columns - 1));
+        let mut z = self.size.z;
+        z.do_div(&Number::from(self.rows - 1));
+        (x, z)
+    }
+
+    // In usize, since anything much over 181 pixels square has more than i16::MAX of them.
+    fn index(&self, column: i16, row: i16) -> usize {
+        let to_usize = |i: i16| usize::try_from(i).unwrap();
+        to_usize(row) * to_usize(self.columns) + to_usize(column)
+    }
+
+    fn vertex(&self, column: i16, row: i16) -> Vec3 {
+        let (cell_x, cell_z) = self.cell_size();
+
+        let mut x = cell_x;
+        x.do_mul(&Number::from(column));
+        x.do_add(&self.origin.x);
+        let mut z = cell_z;
+        z.do_mul(&Number::from(row));
+        z.do_add(&self.origin.z);
+
+        Vec3 {
+            x,
+            y: self.heights[self.index(column, row)],
+            z,
+        }
+    }
+
+    // From the slope between the neighbours on either side, or just the one at the edges.
+    fn normal_at(&self, column: i16, row: i16) -> Vec3 {
+        let slope = |before: Vec3, after: Vec3, along_x: bool| {
+            let mut rise = after.y;
+            rise.do_sub(&before.y);
+            let mut run = if along_x { after.x } else { after.z };
+            run.do_sub(if along_x { &before.x } else { &before.z });
+            rise.do_div(&run);
+            rise
+        };
+
+        let left = self.vertex((column - 1).max(0), row);
+        let right = self.vertex((column + 1).min(self.columns - 1), row);
+        let back = self.vertex(column, (row - 1).max(0));
+        let front = self.vertex(column, (row + 1).min(self.rows - 1));
+
+        let mut dx = slope(left, right, true);
+        dx.do_neg();
+        let mut dz = slope(back, front, false);
+        dz.do_neg();
+
+        let mut n = Vec3 {
+            x: dx,
+            y: Number::from(1),
+            z: dz,
+        };
+        n.do_normalize();
+        n
+    }
+
+    // Blended between the normals at the corners of the cell, for smooth shading.
+    fn surface_normal(&self, hit_point: &Vec3) -> Vec3 {
+        let (x, z) = self.grid_position(hit_point);
+        let column = cell_index(&x, self.columns);
+        let row = cell_index(&z, self.rows);
+
+        let mut fx = x;
+        fx.do_sub(&Number::from(column));
+        let mut fz = z;
+        fz.do_sub(&Number::from(row));
+
+        let weight = |corner_x: bool, corner_z: bool| {
+            let mut wx = fx;
+            if !corner_x {
+                wx.do_neg();
+                wx.do_add(&Number::from(1));
+            }
+            let mut wz = fz;
+            if !corner_z {
+                wz.do_neg();
+                wz.do_add(&Number::from(1));
+            }
+            wx.do_mul(&wz);
+            wx
+        };
+
+        let mut n = Vec3 {
+            x: Number::from(0),
+            y: Number::from(0),
+            z: Number::from(0),
+        };
+        for (dx, dz) in [(0, 0), (1, 0), (0, 1), (1, 1)].iter() {
+            let mut corner = self.normals[self.index(column + dx, row + dz)];
+            corner.do_scale(&weight(*dx == 1, *dz == 1));
+            n.do_add(&corner);
+        }
+        n.do_normalize();
+        n
+    }
+
+    // The flat normal of whichever of its cell's two triangles `point` is on, unlike the smoothed
+    // one it's shaded with.
+    fn face_normal(&self, point: &Vec3) -> Vec3 {
+        let (x, z) = self.grid_position(point);
+        let column = cell_index(&x, self.columns);
+        let row = cell_index(&z, self.rows);
+
+        let mut diagonal = x;
+        diagonal.do_sub(&Number::from(column));
+        diagonal.do_add(&z);
+        diagonal.do_sub(&Number::from(row));
+        // Both edges from the triangle's right-angled corner, in the order that makes the normal
+        // point up.
+        let (corner, first, second) = if diagonal.cmp(&Number::from(1)) <= 0 {
+            (
+                self.vertex(column, row),
+                self.vertex(column, row + 1),
+                self.vertex(column + 1, row),
+            )
+        } else {
+            (
+                self.vertex(column + 1, row + 1),
+                self.vertex(column + 1, row),
+                self.vertex(column, row + 1),
+            )
+        };
+
+        let mut n = first;
+        n.do_sub(&corner);
+        let mut edge = second;
+        edge.do_sub(&corner);
+        n.do_cross(&edge);
+        n.do_normalize();
+        n
+    }
+
+    // Where `point` is on the grid, in cells.
+    fn grid_position(&self, point: &Vec3) -> (Number, Number) {
+        let (cell_x, cell_z) = self.cell_size();
+        let mut x = point.x;
+        x.do_sub(&self.origin.x);
+        x.do_div(&cell_x);
+        let mut z = point.z;
+        z.do_sub(&self.origin.z);
+        z.do_div(&cell_z);
+        (x, z)
+    }
+
+    // The surface's height at a point on the grid, in the cell at `column` and `row`. The cell
+    // is split into two triangles along its diagonal from (1, 0) to (0, 1).
+    fn height_in_cell(&self, column: i16, row: i16, x: &Number, z: &Number) -> Number {
+        let zero = Number::from(0);
+        let one = Number::from(1);
+        let within_cell = |position: &Number, index: i16| {
+            let mut f = *position;
+            f.do_sub(&Number::from(index));
+            if f.is_negative() {
+                zero
+            } else if f.cmp(&one) > 0 {
+                one
+            } else {
+                f
+            }
+        };
+        let fx = within_cell(x, column);
+        let fz = within_cell(z, row);
+        let height = |dx: i16, dz: i16| self.heights[self.index(column + dx, row + dz)];
+
+        let mut diagonal = fx;
+        diagonal.do_add(&fz);
+        // From the corner of whichever triangle it's in, up the slopes along either edge.
+        let (corner, along_x, along_z, ux, uz) = if diagonal.cmp(&one) <= 0 {
+            (height(0, 0), height(1, 0), height(0, 1), fx, fz)
+        } else {
+            let mut ux = one;
+            ux.do_sub(&fx);
+            let mut uz = one;
+            uz.do_sub(&fz);
+            (height(1, 1), height(0, 1), height(1, 0), ux, uz)
+        };
+
+        let mut h = corner;
+        let mut rise = along_x;
+        rise.do_sub(&corner);
+        rise.do_mul(&ux);
+        h.do_add(&rise);
+        let mut rise = along_z;
+        rise.do_sub(&corner);
+        rise.do_mul(&uz);
+        h.do_add(&rise);
+        h
+    }
+
+    // How far `t` along the ray is above the surface in the given cell, or below if negative.
+    fn clearance(&self, column: i16, row: i16, ray: &Ray, t: &Number) -> Number {
+        let mut point = ray.direction;
+        point.do_scale(t);
+        point.do_add(&ray.origin);
+        let (x, z) = self.grid_position(&point);
+
+        let mut clearance = point.y;
+        clearance.do_sub(&self.height_in_cell(column, row, &x, &z));
+        clearance
+    }
+
+    // Where the ray first meets the surface between `enter` and `exit`, its stretch over the
+    // cell. Rather than testing the cell's two triangles separately, which can let a ray slip
+    // between them along the diagonal, this follows how far the ray is above the surface. That's
+    // straight between the cell's edges and the diagonal, so only needs checking at those.
+    fn intersect_cell(
+        &self,
+        column: i16,
+        row: i16,
+        ray: &Ray,
+        enter: &Number,
+        exit: &Number,
+    ) -> Option<Number> {
+        let one = Number::from(1);
+        let past_diagonal = |t: &Number| {
+            let mut point = ray.direction;
+            point.do_scale(t);
+            point.do_add(&ray.origin);
+            let (mut x, mut z) = self.grid_position(&point);
+            x.do_sub(&Number::from(column));
+            z.do_sub(&Number::from(row));
+            x.do_add(&z);
+            x.do_sub(&one);
+            x
+        };
+
+        let mut stops = vec![*enter];
+        let (before, after) = (past_diagonal(enter), past_diagonal(exit));
+        if before.is_negative() != after.is_negative() {
+            let mut crossing = before;
+            let mut change = before;
+            change.do_sub(&after);
+            crossing.do_div(&change);
+            let mut t = *exit;
+            t.do_sub(enter);
+            t.do_mul(&crossing);
+            t.do_add(enter);
+            stops.push(t);
+        }
+        stops.push(*exit);
+
+        for stretch in stops.windows(2) {
+            let (a, b) = (stretch[0], stretch[1]);
+            let above_a = self.clearance(column, row, ray, &a);
+            let above_b = self.clearance(column, row, ray, &b);
+            if above_b.is_zero() || above_a.is_negative() != above_b.is_negative() {
+                let mut fraction = above_a;
+                let mut change = above_a;
+                change.do_sub(&above_b);
+                fraction.do_div(&change);
+                let mut t = b;
+                t.do_sub(&a);
+                t.do_mul(&fraction);
+                t.do_add(&a);
+                return Some(t);
+            }
+        }
+
+        None
+    }
+
+    // A little above white and below black, so a ray meeting the surface at either isn't cut off
+    // right where it hits.
+    fn padded_bounds(&self) -> Bounds {
+        let margin = Number::from_i16_frac(1024);
+        let mut bottom = self.origin;
+        bottom.y.do_sub(&margin);
+        let mut top = self.origin;
+        top.do_add(&self.size);
+        top.y.do_add(&margin);
+        Bounds {
+            min: bottom,
+            max: top,
+        }
+    }
+}
+
+impl fmt::Debug for Heightfield {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Heightfield({}x{})", self.columns, self.rows)
+    }
+}
+
+impl Element for Heightfield {
+    // Walks the cells under the ray in order (a 2D DDA), following the ray's clearance over each
+    // one until it drops below the surface.
+    fn intersect(&self, ray: &Ray) -> Option<Number> {
+        let bounds = self.padded_bounds();
+        let span = slab_span(&bounds.min, &bounds.max, ray)?;
+        if span.exit.is_negative() {
+            return None;
+        }
+
+        // Start from where the ray enters, which keeps the clearance tests' numbers small.
+        let start = if span.enter.is_negative() {
+            Number::from(0)
+        } else {
+            span.enter
+        };
+        let mut length = span.exit;
+        length.do_sub(&start);
+
+        let mut origin = ray.direction;
+        origin.do_scale(&start);
+        origin.do_add(&ray.origin);
+        let local = Ray {
+            origin,
+            direction: ray.direction,
+        };
+
+        let (x, z) = self.grid_position(&origin);
+        let mut column = cell_index(&x, self.columns);
+        let mut row = cell_index(&z, self.rows);
+
+        let (cell_x, cell_z) = self.cell_size();
+        let mut speed_x = ray.direction.x;
+        speed_x.do_div(&cell_x);
+        let mut speed_z = ray.direction.z;
+        speed_z.do_div(&cell_z);
+        let mut across = Stepper::new(&x, column, &speed_x);
+        let mut down = Stepper::new(&z, row, &speed_z);
+
+        // How far along `local` the current cell starts.
+        let mut entered = Number::from(0);
+
+        loop {
+            let (stepper, across_columns) = if across.next.is_less_than(&down.next) {
+                (&mut across, true)
+            } else {
+                (&mut down, false)
+            };
+            let leaving = length.cmp(&stepper.next) <= 0;
+            let exit = if leaving { length } else { stepper.next };
+
+            if let Some(t) = self.intersect_cell(column, row, &local, &entered, &exit) {
+                let mut t = t;
+                t.do_add(&start);
+                return Some(t);
+            }
+
+            if leaving {
+                return None;
+            }
+            entered = stepper.next;
+            stepper.next.do_add(&stepper.delta);
+            if across_columns {
+                column += stepper.step;
+            } else {
+                row += stepper.step;
+            }
+            if column < 0 || column > self.columns - 2 || row < 0 || row > self.rows - 2 {
+                return None;
+            }
+        }
+    }
+
+    // From 0 to 1 across the field in x and z.
+    fn hit(&self, ray: &Ray, distance: Number) -> Hit<'_> {
+        let point = ray.at(&distance);
+        let (geometric_normal, front_face) =
+            facing_normal(&ray.direction, &self.face_normal(&point));
+        let (normal, _) = facing_normal(&ray.direction, &self.surface_normal(&point));
+
+        let (mut u, mut v) = self.grid_position(&point);
+        u.do_div(&Number::from(self.columns - 1));
+        v.do_div(&Number::from(self.rows - 1));
+
+        Hit {
+            distance,
+            point,
+            normal,
+            geometric_normal,
+            u,
+            v,
+            front_face,
+            color: self.color,
+            surface: self.surface,
+            element: self,
+        }
+    }
+
+    fn sidedness(&self) -> Sidedness {
+        self.sidedness
+    }
+
+    fn casts_shadows(&self) -> bool {
+        self.casts_shadows
+    }
+
+    fn is_flat(&self) -> bool {
+        false
+    }
+
+    fn bounds(&self) -> Option<Bounds> {
+        Some(self.padded_bounds())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Heightfield, HeightfieldOptions};
+    use crate::material::Surface;
+    use crate::sidedness::Sidedness;
+    use crate::vector::{vec3, Vec3};
+    use crate::Element;
+    use crate::Number;
+    use crate::Ray;
+    use image::{DynamicImage, GrayImage, Luma};
+    use rstest::rstest;
+
+    fn options() -> HeightfieldOptions {
+        HeightfieldOptions {
+            origin: vec3(-2, 0, -2),
+            size: vec3(4, 4, 4),
+            color: Number::from(1),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }
+    }
+
+    // 5x5 pixels over x and z from -2 to 2. Flat at height 0, apart from a ramp up to 4 along
+    // the right-hand column.
+    fn heightfield() -> Heightfield {
+        let image = GrayImage::from_fn(5, 5, |x, _| Luma([if x == 4 { 255 } else { 0 }]));
+        Heightfield::from_image(&DynamicImage::ImageLuma8(image), &options()).unwrap()
+    }
+
+    // More pixels than fit in an i16, which the index into them has to cope with.
+    #[test]
+    fn test_large_heightfield() {
+        let image = GrayImage::from_fn(256, 256, |_, _| Luma([0]));
+        let field = Heightfield::from_image(&DynamicImage::ImageLuma8(image), &options()).unwrap();
+
+        let hit = field.intersect(&ray(vec3(1, 4, 1), vec3(0, -1, 0)));
+        assert!(f64::abs(hit.unwrap().to_f64() - 4.0) < 0.02);
+    }
+
+    #[rstest]
+    #[case(1, 5)]
+    #[case(5, 0)]
+    #[case(40000, 2)]
+    fn test_bad_heightfield_size(#[case] width: u32, #[case] height: u32) {
+        let image = GrayImage::from_fn(width, height, |_, _| Luma([0]));
+        assert!(Heightfield::from_image(&DynamicImage::ImageLuma8(image), &options()).is_err());
+    }
+
+    fn ray(origin: Vec3, direction: Vec3) -> Ray {
+        let mut direction = direction;
+        direction.do_normalize();
+        Ray { origin, direction }
+    }
+
+    // Half a cell in from `x` and `z`, so rays don't run along the grid lines.
+    fn off_grid(x: i16, y: i16, z: i16) -> Vec3 {
+        let mut v = vec3(x, y, z);
+        v.x.do_add(&Number::from_i16_frac(16384));
+        v.z.do_add(&Number::from_i16_frac(16384));
+        v
+    }
+
+    #[rstest]
+    // Straight down onto the flat part.
+    #[case(off_grid(0, 10, 0), vec3(0, -1, 0), Some(10.0))]
+    // Down onto the ramp, which rises 4 over the last cell.
+    #[case(off_grid(1, 10, 0), vec3(0, -1, 0), Some(8.0))]
+    // Across the grid from the left at height 1, until it runs into the ramp.
+    #[case(off_grid(-11, 1, 0), vec3(1, 0, 0), Some(11.75))]
+    // Over the top.
+    #[case(off_grid(-11, 5, 0), vec3(1, 0, 0), None)]
+    // Down at an angle, crossing cells diagonally.
+    #[case(off_grid(-3, 4, 1), vec3(1, -2, -1), Some(24.0_f64.sqrt()))]
+    fn test_heightfield_intersect(
+        #[case] origin: Vec3,
+        #[case] direction: Vec3,
+        #[case] expected: Option<f64>,
+    ) {
+        let hit = heightfield().intersect(&ray(origin, direction));
+        match expected {
+            None => assert!(hit.is_none()),
+            Some(d) => assert!(f64::abs(hit.unwrap().to_f64() - d) < 0.02),
+        }
+    }
+
+    #[test]
+    fn test_heightfield_normals() {
+        let field = heightfield();
+
+        let flat = field.surface_normal(&vec3(-1, 0, 0));
+        assert!(f64::abs(flat.y.to_f64() - 1.0) < 0.01);
+
+        // Halfway up the ramp, the slope is 4 across 1.
+        let mut point = vec3(1, 2, 0);
+        point.x.do_add(&Number::from_i16_frac(16384));
+        let ramp = field.surface_normal(&point);
+        assert!(ramp.x.is_negative());
+        assert!(ramp.y.is_positive());
+        assert!(f64::abs(ramp.z.to_f64()) < 0.01);
+    }
+}
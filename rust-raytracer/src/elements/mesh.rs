@@ -0,0 +1,310 @@
+use crate::bvh::{Bounds, PADDING};
+use crate::elements::triangle::{intersect_triangle, Triangle};
+use crate::hit::Hit;
+use crate::material::Surface;
+use crate::sidedness::Sidedness;
+use crate::Element;
+use crate::Number;
+use crate::Ray;
+use crate::Vec3;
+
+#[derive(Debug, Clone)]
+pub struct Mesh {
+    triangles: Vec<Triangle>,
+    color: Number,
+    surface: Surface,
+    sidedness: Sidedness,
+    // On from `new`. load_obj sets it from its options.
+    pub casts_shadows: bool,
+    // Rays that miss this skip every triangle test. It's a box rather than a sphere, which would
+    // have to square its radius, and padded so rounding can't lose a triangle on its edge.
+    bounds: Bounds,
+}
+
+impl Mesh {
+    // The triangles' own color, surface and sidedness are ignored in favour of the mesh's.
+    pub fn new(
+        triangles: Vec<Triangle>,
+        color: Number,
+        surface: Surface,
+        sidedness: Sidedness,
+    ) -> Mesh {
+        assert!(!triangles.is_empty());
+
+        let corners: Vec<Vec3> = triangles
+            .iter()
+            .flat_map(|t| t.vertices.iter().copied())
+            .collect();
+
+        Mesh {
+            triangles,
+            color,
+            surface,
+            sidedness,
+            casts_shadows: true,
+            bounds: Bounds::around(&corners).grown(&PADDING),
+        }
+    }
+
+    // The triangle the ray hits closest to `distance`, with the barycentric u and v where it
+    // lands. Going by the distance rather than taking the nearest means a hit that sidedness
+    // stepped past the nearer triangles for still lands on the right one. On an edge two
+    // triangles share, it's whichever came last, the same way every time.
+    fn triangle_at(&self, ray: &Ray, distance: &Number) -> Option<(&Triangle, Number, Number)> {
+        let mut closest: Option<(&Triangle, Number, Number, Number)> = None;
+
+        for triangle in &self.triangles {
+            if let Some((d, u, v)) = intersect_triangle(&triangle.vertices, ray) {
+                let mut gap = d;
+                gap.do_sub(distance);
+                gap.do_abs();
+                if !closest.is_some_and(|(_, g, _, _)| g.is_less_than(&gap)) {
+                    closest = Some((triangle, gap, u, v));
+                }
+            }
+        }
+
+        closest.map(|(triangle, _, u, v)| (triangle, u, v))
+    }
+}
+
+impl Element for Mesh {
+    fn intersect(&self, ray: &Ray) -> Option<Number> {
+        self.bounds.span(ray)?;
+        self.triangles
+            .iter()
+            .filter_map(|triangle| intersect_triangle(&triangle.vertices, ray))
+            .map(|(d, _, _)| d)
+            .reduce(|a, b| if b.is_less_than(&a) { b } else { a })
+    }
+
+    fn hit(&self, ray: &Ray, distance: Number) -> Hit<'_> {
+        let (triangle, u, v) = self
+            .triangle_at(ray, &distance)
+            .expect("hit with a ray that misses the mesh");
+        Hit {
+            color: self.color,
+            surface: self.surface,
+            element: self,
+            ..triangle.hit_at(ray, distance, u, v)
+        }
+    }
+
+    fn sidedness(&self) -> Sidedness {
+        self.sidedness
+    }
+
+    fn casts_shadows(&self) -> bool {
+        self.casts_shadows
+    }
+
+    fn is_flat(&self) -> bool {
+        false
+    }
+
+    fn bounds(&self) -> Option<Bounds> {
+        Some(self.bounds)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Mesh;
+    use crate::elements::triangle::Triangle;
+    use crate::material::Surface;
+    use crate::obj::{parse_obj, Fit, ObjOptions};
+    use crate::sidedness::{intersect_visible, Sidedness};
+    use crate::vector::{vec3, Vec3};
+    use crate::Element;
+    use crate::Number;
+    use crate::Ray;
+    use rstest::rstest;
+
+    fn triangle(vertices: [Vec3; 3]) -> Triangle {
+        Triangle {
+            vertices,
+            normals: None,
+            color: Number::from(0),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }
+    }
+
+    // Two facing walls, one at z = -5 facing +z and one at z = -8 facing -z.
+    fn walls(sidedness: Sidedness) -> Mesh {
+        Mesh::new(
+            vec![
+                triangle([vec3(-1, -1, -5), vec3(1, -1, -5), vec3(0, 1, -5)]),
+                triangle([vec3(-1, -1, -8), vec3(0, 1, -8), vec3(1, -1, -8)]),
+            ],
+            Number::from(1),
+            Surface::Diffuse,
+            sidedness,
+        )
+    }
+
+    #[test]
+    fn test_mesh_nearest_triangle() {
+        let ray = Ray {
+            origin: vec3(0, 0, 0),
+            direction: vec3(0, 0, -1),
+        };
+
+        let d = walls(Sidedness::Both).intersect(&ray).unwrap();
+        assert_eq!(d.to_f64(), 5.0);
+    }
+
+    #[test]
+    fn test_mesh_miss() {
+        let ray = Ray {
+            origin: vec3(0, 0, 0),
+            direction: vec3(0, 1, 0),
+        };
+
+        assert!(walls(Sidedness::Both).intersect(&ray).is_none());
+    }
+
+    #[test]
+    fn test_mesh_hit_picks_hit_triangle() {
+        let mesh = walls(Sidedness::Both);
+
+        let ray = Ray {
+            origin: vec3(0, 0, 0),
+            direction: vec3(0, 0, -1),
+        };
+        let front = mesh.hit(&ray, Number::from(5));
+        assert!(front.front_face);
+        assert!(front.normal.z.is_positive());
+        assert!(std::ptr::addr_eq(front.element, &mesh));
+
+        let ray = Ray {
+            origin: vec3(0, 0, -10),
+            direction: vec3(0, 0, 1),
+        };
+        let back = mesh.hit(&ray, Number::from(2));
+        assert!(back.front_face);
+        assert!(back.normal.z.is_negative());
+    }
+
+    // Looking down z from the origin, the near wall faces the ray and the far one faces away. A
+    // back-only mesh is seen through the near wall, and its hit should be on the far one.
+    #[rstest]
+    #[case(Sidedness::Front, 5.0, true)]
+    #[case(Sidedness::Back, 8.0, false)]
+    fn test_mesh_hit_with_sidedness(
+        #[case] sidedness: Sidedness,
+        #[case] expected: f64,
+        #[case] front_face: bool,
+    ) {
+        let mesh = walls(sidedness);
+        let ray = Ray {
+            origin: vec3(0, 0, 0),
+            direction: vec3(0, 0, -1),
+        };
+
+        let d = intersect_visible(&mesh, &ray).unwrap();
+        assert!(f64::abs(d.to_f64() - expected) < 0.01);
+
+        let hit = mesh.hit(&ray, d);
+        assert_eq!(hit.front_face, front_face);
+        assert!(f64::abs(hit.point.z.to_f64() + expected) < 0.01);
+        assert!(hit.normal.z.is_positive());
+    }
+
+    // Side by side on one plane, the second with its normals leaning right. A ray just inside
+    // the first is just outside the second, and should get the first's flat normal however
+    // close it is to their shared edge.
+    #[test]
+    fn test_mesh_hit_at_shared_edge() {
+        let mut leaning = triangle([vec3(0, -1, -5), vec3(1, -1, -5), vec3(0, 1, -5)]);
+        leaning.normals = Some([vec3(1, 0, 1); 3]);
+        let mesh = Mesh::new(
+            vec![
+                leaning,
+                triangle([vec3(-1, -1, -5), vec3(0, -1, -5), vec3(0, 1, -5)]),
+            ],
+            Number::from(1),
+            Surface::Diffuse,
+            Sidedness::Both,
+        );
+
+        let mut origin = vec3(0, 0, 0);
+        origin.x = Number::from_f64(-1.0 / 256.0);
+        let ray = Ray {
+            origin,
+            direction: vec3(0, 0, -1),
+        };
+        let hit = mesh.hit(&ray, mesh.intersect(&ray).unwrap());
+        assert_eq!(hit.normal.x.to_f64(), 0.0);
+        assert!(f64::abs(hit.u.to_f64() - 0.5) < 0.01);
+    }
+
+    // Two small triangles 400 apart, far too spread out for a bounding sphere's radius to be
+    // squared. Hit straight on, each gives its own distance.
+    #[rstest]
+    #[case(-200, 5.0)]
+    #[case(200, 8.0)]
+    fn test_large_mesh_intersect(#[case] x: i16, #[case] expected: f64) {
+        let mesh = Mesh::new(
+            vec![
+                triangle([vec3(-201, -1, -5), vec3(-199, -1, -5), vec3(-200, 1, -5)]),
+                triangle([vec3(199, -1, -8), vec3(201, -1, -8), vec3(200, 1, -8)]),
+            ],
+            Number::from(1),
+            Surface::Diffuse,
+            Sidedness::Both,
+        );
+
+        let ray = Ray {
+            origin: vec3(x, 0, 0),
+            direction: vec3(0, 0, -1),
+        };
+        let d = mesh.intersect(&ray).unwrap();
+        assert!(f64::abs(d.to_f64() - expected) < 0.001);
+        assert_eq!(mesh.hit(&ray, d).point.x.to_f64(), f64::from(x));
+    }
+
+    // The same from an OBJ fitted to 100 across, so each end is about 50 from the middle.
+    #[test]
+    fn test_large_fitted_mesh_intersect() {
+        let source = "
+            v -1 -1 0
+            v 1 -1 0
+            v 0 1 0
+            v 999 -1 0
+            v 1001 -1 0
+            v 1000 1 0
+            f 1 2 3
+            f 4 5 6
+        ";
+        let options = ObjOptions {
+            fit: Some(Fit {
+                center: vec3(0, 0, -5),
+                size: Number::from(100),
+            }),
+            color: Number::from(1),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        };
+        let mesh = Mesh::new(
+            parse_obj(source, &options).unwrap(),
+            Number::from(1),
+            Surface::Diffuse,
+            Sidedness::Both,
+        );
+
+        // Fitting scales by 100/1002, so each triangle's middle is 49.9 out.
+        for x in [-49.9, 49.9] {
+            let mut origin = vec3(0, 0, 0);
+            origin.x = Number::from_f64(x);
+            let ray = Ray {
+                origin,
+                direction: vec3(0, 0, -1),
+            };
+            let d = mesh.intersect(&ray).unwrap();
+            assert!(f64::abs(d.to_f64() - 5.0) < 0.001);
+        }
+    }
+}
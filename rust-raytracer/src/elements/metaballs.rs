@@ -0,0 +1,364 @@
+use crate::bvh::Bounds;
+use crate::elements::csg::Solid;
+use crate::elements::sphere::Sphere;
+use crate::hit::Hit;
+use crate::material::Surface;
+use crate::sidedness::{facing_normal, Sidedness};
+use crate::solver::{largest, multiply, roots_between};
+use crate::Element;
+use crate::Number;
+use crate::Ray;
+use crate::Vec3;
+
+// One source of the field. Its contribution is weight (1 - r^2 / radius^2)^3 at a distance r,
+// falling smoothly to nothing at `radius`. A negative weight carves into its neighbours.
+#[derive(Debug, Clone, Copy)]
+pub struct Ball {
+    pub center: Vec3,
+    pub radius: Number,
+    pub weight: Number,
+}
+
+// A blobby surface where the balls' summed field comes to `threshold`. On its own, a ball of
+// weight 1 with a threshold of 1/8 has its surface 1/sqrt(2) of the way out to its radius.
+#[derive(Debug, Clone)]
+pub struct Metaballs {
+    pub balls: Vec<Ball>,
+    pub threshold: Number,
+    pub color: Number,
+    pub surface: Surface,
+    pub sidedness: Sidedness,
+    pub casts_shadows: bool,
+}
+
+impl Ball {
+    fn reach(&self) -> Sphere {
+        Sphere {
+            center: self.center,
+            radius: self.radius,
+            color: Number::from(0),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }
+    }
+
+    // 1 - r^2 / radius^2 along the ray, as a quadratic in t. Only meaningful while the ray is
+    // within reach.
+    fn falloff(&self, ray: &Ray) -> [Number; 3] {
+        let mut from_center = ray.origin;
+        from_center.do_sub(&self.center);
+
+        let mut radius_sq = self.radius;
+        radius_sq.do_mul(&self.radius);
+
+        let mut a = Number::from(-1);
+        a.do_div(&radius_sq);
+
+        let mut b = from_center.dot(&ray.direction);
+        b.do_mul(&Number::from(2));
+        b.do_mul(&a);
+
+        let mut c = from_center.dist_sq();
+        c.do_mul(&a);
+        c.do_add(&Number::from(1));
+
+        [c, b, a]
+    }
+
+    // The field's contribution at t, lowest power first, with t stretched so that 1 is `length`
+    // along the ray. Over a length no longer than the ball is across, the falloff's coefficients
+    // stay within 4 however small the ball is.
+    fn field(&self, ray: &Ray, length: &Number) -> Vec<Number> {
+        let mut falloff = self.falloff(ray);
+        falloff[1].do_mul(length);
+        falloff[2].do_mul(length);
+        falloff[2].do_mul(length);
+        let mut field = multiply(&multiply(&falloff, &falloff), &falloff);
+        for c in field.iter_mut() {
+            c.do_mul(&self.weight);
+        }
+        field
+    }
+}
+
+impl Metaballs {
+    // Down the field's gradient, which is the sum of
+    //   6 weight (1 - r^2 / radius^2)^2 (p - center) / radius^2
+    // over the balls in reach, pointing away from the centers.
+    fn surface_normal(&self, hit_point: &Vec3) -> Vec3 {
+        let mut n = Vec3 {
+            x: Number::from(0),
+            y: Number::from(0),
+            z: Number::from(0),
+        };
+
+        for ball in self.balls.iter() {
+            let mut from_center = *hit_point;
+            from_center.do_sub(&ball.center);
+            if !from_center.is_within(&ball.radius) {
+                continue;
+            }
+
+            let mut radius_sq = ball.radius;
+            radius_sq.do_mul(&ball.radius);
+
+            let mut falloff = from_center.dist_sq();
+            falloff.do_div(&radius_sq);
+            falloff.do_neg();
+            falloff.do_add(&Number::from(1));
+
+            let mut scale = falloff;
+            scale.do_mul(&falloff);
+            scale.do_mul(&ball.weight);
+            scale.do_mul(&Number::from(6));
+            scale.do_div(&radius_sq);
+
+            from_center.do_scale(&scale);
+            n.do_add(&from_center);
+        }
+
+        n.do_normalize();
+        n
+    }
+}
+
+impl Element for Metaballs {
+    // Each ball's field is a polynomial in t while the ray is within its reach, so between the
+    // points where the ray passes in or out of reach of any ball, the total is one polynomial.
+    // Each of those pieces is solved from its own start and over its own length, which keeps the
+    // coefficients small, and a heavy piece is divided through until its derivatives fit.
+    fn intersect(&self, ray: &Ray) -> Option<Number> {
+        // Each ball with where the ray is in its reach, from no further back than the origin.
+        let reaches: Vec<(&Ball, Number, Number)> = self
+            .balls
+            .iter()
+            .flat_map(|ball| {
+                ball.reach()
+                    .spans(ray)
+                    .into_iter()
+                    .map(move |span| (ball, span))
+            })
+            .filter(|(_, span)| !span.exit.is_negative())
+            .map(|(ball, span)| {
+                let enter = if span.enter.is_negative() {
+                    Number::from(0)
+                } else {
+                    span.enter
+                };
+                (ball, enter, span.exit)
+            })
+            .collect();
+
+        let mut edges: Vec<Number> = reaches
+            .iter()
+            .flat_map(|(_, enter, exit)| vec![*enter, *exit])
+            .collect();
+        edges.sort_by(|a, b| a.cmp(b).cmp(&0));
+
+        for piece in edges.windows(2) {
+            let (start, end) = (piece[0], piece[1]);
+            let mut length = end;
+            length.do_sub(&start);
+            if length.is_zero() {
+                continue;
+            }
+
+            let mut origin = ray.direction;
+            origin.do_scale(&start);
+            origin.do_add(&ray.origin);
+            let local = Ray {
+                origin,
+                direction: ray.direction,
+            };
+
+            let mut total = vec![Number::from(0); 7];
+            total[0].do_sub(&self.threshold);
+            let mut any = false;
+            for (ball, enter, exit) in reaches.iter() {
+                if start.is_less_than(enter) || exit.is_less_than(&end) {
+                    continue;
+                }
+                for (t, c) in total.iter_mut().zip(ball.field(&local, &length).iter()) {
+                    t.do_add(c);
+                }
+                any = true;
+            }
+
+            if !any {
+                continue;
+            }
+
+            // The fifth derivative is 720 times the top coefficient, so up to 32 is safe. Any
+            // more is divided off, but no more than that, since every division costs precision
+            // right where a ray only just grazes the surface.
+            let most = Number::from(32);
+            let mut biggest = largest(&total);
+            if most.is_less_than(&biggest) {
+                biggest.do_div(&most);
+                for c in total.iter_mut() {
+                    c.do_div(&biggest);
+                }
+            }
+
+            if let Some(root) = roots_between(&total, &Number::from(0), &Number::from(1)).first() {
+                let mut t = *root;
+                t.do_mul(&length);
+                t.do_add(&start);
+                return Some(t);
+            }
+        }
+
+        None
+    }
+
+    // Nothing to unwrap a blob onto, so u and v are left at 0. Anything painted on it has to go by
+    // the point instead.
+    fn hit(&self, ray: &Ray, distance: Number) -> Hit<'_> {
+        let point = ray.at(&distance);
+        let (normal, front_face) = facing_normal(&ray.direction, &self.surface_normal(&point));
+
+        Hit {
+            distance,
+            point,
+            normal,
+            geometric_normal: normal,
+            u: Number::from(0),
+            v: Number::from(0),
+            front_face,
+            color: self.color,
+            surface: self.surface,
+            element: self,
+        }
+    }
+
+    fn sidedness(&self) -> Sidedness {
+        self.sidedness
+    }
+
+    fn casts_shadows(&self) -> bool {
+        self.casts_shadows
+    }
+
+    fn is_flat(&self) -> bool {
+        false
+    }
+
+    fn bounds(&self) -> Option<Bounds> {
+        self.balls
+            .iter()
+            .map(|ball| Bounds::around_sphere(&ball.center, &ball.radius))
+            .reduce(|all, b| all.union(&b))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Ball, Metaballs};
+    use crate::material::Surface;
+    use crate::sidedness::Sidedness;
+    use crate::vector::vec3;
+    use crate::Element;
+    use crate::Number;
+    use crate::Ray;
+    use rstest::rstest;
+
+    fn ball(x: i16, weight: i16) -> Ball {
+        Ball {
+            center: vec3(x, 0, -5),
+            radius: Number::from(2),
+            weight: Number::from(weight),
+        }
+    }
+
+    fn metaballs(balls: Vec<Ball>) -> Metaballs {
+        let mut threshold = Number::from(1);
+        threshold.do_div(&Number::from(8));
+
+        Metaballs {
+            balls,
+            threshold,
+            color: Number::from(1),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }
+    }
+
+    fn forward(x: i16) -> Ray {
+        Ray {
+            origin: vec3(x, 0, 0),
+            direction: vec3(0, 0, -1),
+        }
+    }
+
+    #[rstest]
+    // A lone ball's surface is sqrt(2) out from its center.
+    #[case(vec![ball(0, 1)], 0, Some(5.0 - 2.0_f64.sqrt()))]
+    #[case(vec![ball(0, 1)], 2, None)]
+    // Halfway between two balls 4 apart, where neither reaches.
+    #[case(vec![ball(-2, 1), ball(2, 1)], 0, None)]
+    // 2 apart, they give 27/64 each, so they've merged.
+    #[case(vec![ball(-1, 1), ball(1, 1)], 0, Some(5.0 - (4.0 * (1.0 - (1.0_f64 / 16.0).cbrt()) - 1.0).sqrt()))]
+    // A negative ball in the middle leaves a hole.
+    #[case(vec![ball(0, 1), ball(0, -1)], 0, None)]
+    fn test_metaballs_intersect(
+        #[case] balls: Vec<Ball>,
+        #[case] x: i16,
+        #[case] expected: Option<f64>,
+    ) {
+        let hit = metaballs(balls).intersect(&forward(x));
+        match expected {
+            None => assert!(hit.is_none()),
+            Some(d) => assert!(f64::abs(hit.unwrap().to_f64() - d) < 0.01),
+        }
+    }
+
+    // Small or heavy balls, whose fields' derivatives would overflow if solved as they come.
+    // Radius in quarters.
+    #[rstest]
+    #[case(4, 1)]
+    #[case(2, 1)]
+    #[case(1, 1)]
+    #[case(4, 4)]
+    #[case(1, 8)]
+    #[case(4, 16)]
+    #[case(2, 16)]
+    #[case(4, 100)]
+    fn test_small_metaballs_intersect(#[case] quarters: i16, #[case] weight: i16) {
+        let mut radius = Number::from(quarters);
+        radius.do_div(&Number::from(4));
+        let m = metaballs(vec![Ball {
+            center: vec3(0, 0, -5),
+            radius,
+            weight: Number::from(weight),
+        }]);
+
+        // Where weight (1 - r^2 / radius^2)^3 comes down to 1/8.
+        let falloff = (1.0 / (8.0 * f64::from(weight))).cbrt();
+        let out = radius.to_f64() * (1.0 - falloff).sqrt();
+        let hit = m.intersect(&forward(0)).unwrap();
+        assert!(f64::abs(hit.to_f64() - (5.0 - out)) < 0.01);
+    }
+
+    #[test]
+    fn test_metaballs_from_inside() {
+        let hit = metaballs(vec![ball(0, 1)]).intersect(&Ray {
+            origin: vec3(0, 0, -5),
+            direction: vec3(0, 0, -1),
+        });
+        assert!(f64::abs(hit.unwrap().to_f64() - 2.0_f64.sqrt()) < 0.01);
+    }
+
+    #[test]
+    fn test_metaballs_normal() {
+        let m = metaballs(vec![ball(-1, 1), ball(1, 1)]);
+
+        let n = m.surface_normal(&vec3(0, 1, -5));
+        assert!(f64::abs(n.y.to_f64() - 1.0) < 0.01);
+
+        // Only the nearer ball reaches the far end.
+        let n = m.surface_normal(&vec3(2, 0, -5));
+        assert!(f64::abs(n.x.to_f64() - 1.0) < 0.01);
+    }
+}
@@ -1,2 +1,18 @@
+pub mod boxes;
+pub mod capsule;
+pub mod cone;
+pub mod csg;
+pub mod cylinder;
+pub mod disc;
+pub mod heightfield;
+pub mod mesh;
+pub mod metaballs;
 pub mod plane;
+pub mod polygon;
+pub mod rectangle;
+pub mod sdf;
 pub mod sphere;
+pub mod textured;
+pub mod torus;
+pub mod transformed;
+pub mod triangle;
@@ -1,104 +1,179 @@
-use crate::int32::Int32;
+use crate::bvh::Bounds;
+use crate::elements::csg::{Solid, Span};
+use crate::hit::Hit;
+use crate::material::Surface;
+use crate::sidedness::{facing_normal, Sidedness};
+use crate::solver::{checked_div, FAR};
 use crate::Element;
 use crate::Number;
 use crate::Ray;
 use crate::Vec3;
 
 #[derive(Debug, Clone, Copy)]
-// One-sided plane.
+// `normal` points into the plane, away from its front.
 pub struct Plane {
-    pub origin: Vec3,
-    pub normal: Vec3,
-    pub color: Number,
-    pub checkerboarded: bool,
+    origin: Vec3,
+    normal: Vec3,
+    color: Number,
+    surface: Surface,
+    sidedness: Sidedness,
+    // `new` leaves this on.
+    pub casts_shadows: bool,
+    // The directions u and v run in across the plane.
+    x_axis: Vec3,
+    y_axis: Vec3,
+}
+
+// Shared by the bounded shapes on a plane. Hits either side; which sides count is up to the
+// element's sidedness.
+pub fn intersect_plane(origin: &Vec3, normal: &Vec3, ray: &Ray) -> Option<Number> {
+    let denom = normal.dot(&ray.direction);
+
+    let mut ray_to_origin = *origin;
+    ray_to_origin.do_sub(&ray.origin);
+
+    // None when the ray runs (nearly) parallel to the plane.
+    let distance = checked_div(&ray_to_origin.dot(normal), &denom)?;
+
+    if !distance.is_negative() {
+        Some(distance)
+    } else {
+        None
+    }
+}
+
+impl Plane {
+    pub fn new(
+        origin: Vec3,
+        normal: Vec3,
+        color: Number,
+        surface: Surface,
+        sidedness: Sidedness,
+    ) -> Plane {
+        let (x_axis, y_axis) = axes(&normal);
+        Plane {
+            origin,
+            normal,
+            color,
+            surface,
+            sidedness,
+            casts_shadows: true,
+            x_axis,
+            y_axis,
+        }
+    }
+}
+
+fn axes(normal: &Vec3) -> (Vec3, Vec3) {
+    let mut x_axis = *normal;
+    x_axis.do_cross(&Vec3 {
+        x: Number::from(0),
+        y: Number::from(0),
+        z: Number::from(1),
+    });
+    if x_axis.dist_sq().is_zero() {
+        x_axis.do_cross(&Vec3 {
+            x: Number::from(0),
+            y: Number::from(1),
+            z: Number::from(0),
+        });
+    }
+
+    let mut y_axis = *normal;
+    y_axis.do_cross(&x_axis);
+
+    (x_axis, y_axis)
 }
 
 impl Element for Plane {
     fn intersect(&self, ray: &Ray) -> Option<Number> {
-        let denom = self.normal.dot(&ray.direction);
+        intersect_plane(&self.origin, &self.normal, ray)
+    }
 
-        if !denom.is_positive() {
-            return None;
+    // Along two directions across the plane, in scene units.
+    fn hit(&self, ray: &Ray, distance: Number) -> Hit<'_> {
+        let point = ray.at(&distance);
+        let mut outward = self.normal;
+        outward.do_neg();
+        let (normal, front_face) = facing_normal(&ray.direction, &outward);
+
+        let u = point.dot(&self.x_axis);
+        let v = point.dot(&self.y_axis);
+
+        Hit {
+            distance,
+            point,
+            normal,
+            geometric_normal: normal,
+            u,
+            v,
+            front_face,
+            color: self.color,
+            surface: self.surface,
+            element: self,
         }
+    }
 
-        let mut ray_to_origin = self.origin;
-        ray_to_origin.do_sub(&ray.origin);
+    fn sidedness(&self) -> Sidedness {
+        self.sidedness
+    }
 
-        let mut distance = ray_to_origin.dot(&self.normal);
-        distance.do_div(&denom);
+    fn casts_shadows(&self) -> bool {
+        self.casts_shadows
+    }
 
-        if !distance.is_negative() {
-            Some(distance)
-        } else {
-            None
-        }
+    fn is_flat(&self) -> bool {
+        true
     }
 
-    fn color(&self, hit_point: &Vec3) -> Number {
-        if self.checkerboarded {
-            let mut x_axis = self.normal;
-            x_axis.do_cross(&Vec3 {
-                x: Number::from(0),
-                y: Number::from(0),
-                z: Number::from(1),
-            });
-            if x_axis.dist_sq().is_zero() {
-                x_axis.do_cross(&Vec3 {
-                    x: Number::from(0),
-                    y: Number::from(1),
-                    z: Number::from(0),
-                });
-            }
-
-            let mut y_axis = self.normal;
-            y_axis.do_cross(&x_axis);
-
-            let scale = Number::from(1);
-
-            let x = {
-                let mut v = hit_point.dot(&x_axis);
-                v.do_mul(&scale);
-                v.do_add(&Number::from(1000));
-                v.to_int32()
-            };
-            let y = {
-                let mut v = hit_point.dot(&y_axis);
-                v.do_mul(&scale);
-                v.do_add(&Number::from(1000));
-                v.to_int32()
-            };
-
-            let mut sum = x;
-            sum.do_add(&y);
-
-            let mut halftwice = sum;
-            halftwice.do_div(&Int32::from(2));
-            halftwice.do_mul(&Int32::from(2));
-
-            if sum.cmp(&halftwice) == 0 {
-                let mut n = Number::from(90);
-                n.do_div(&Number::from(100));
-                n
-            } else {
-                let mut n = Number::from(3);
-                n.do_div(&Number::from(100));
-                n
-            }
-        } else {
-            self.color
+    fn bounds(&self) -> Option<Bounds> {
+        None
+    }
+}
+
+// As a solid, a plane is the half-space behind its front.
+impl Solid for Plane {
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        let mut behind = *FAR;
+        behind.do_neg();
+
+        let mut ray_to_origin = self.origin;
+        ray_to_origin.do_sub(&ray.origin);
+        let depth = ray_to_origin.dot(&self.normal);
+        let denom = self.normal.dot(&ray.direction);
+
+        match checked_div(&depth, &denom) {
+            Some(t) if denom.is_positive() => vec![Span {
+                enter: t,
+                exit: *FAR,
+            }],
+            Some(t) => vec![Span {
+                enter: behind,
+                exit: t,
+            }],
+            // Parallel, so either always inside or never.
+            None if depth.is_positive() => vec![],
+            None => vec![Span {
+                enter: behind,
+                exit: *FAR,
+            }],
         }
     }
 
-    fn surface_normal(&self, _: &Vec3) -> Vec3 {
-        let mut c = self.normal;
-        c.do_scale(&Number::from(-1));
-        c
+    fn distance_to_surface(&self, point: &Vec3) -> Number {
+        let mut offset = *point;
+        offset.do_sub(&self.origin);
+        let mut d = offset.dot(&self.normal);
+        d.do_abs();
+        d
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::Plane;
+    use crate::material::Surface;
+    use crate::sidedness::Sidedness;
     use crate::vector::Vec3;
     use crate::Element;
     use crate::Number;
@@ -106,20 +181,21 @@ mod test {
 
     #[test]
     fn test_plane_intersect() {
-        let plane = Plane {
-            origin: Vec3 {
+        let plane = Plane::new(
+            Vec3 {
                 x: Number::from(0),
                 y: Number::from(0),
                 z: Number::from(-5),
             },
-            normal: Vec3 {
+            Vec3 {
                 x: Number::from(0),
                 y: Number::from(0),
                 z: Number::from(-1),
             },
-            color: Number::from(0),
-            checkerboarded: false,
-        };
+            Number::from(0),
+            Surface::Diffuse,
+            Sidedness::Both,
+        );
 
         let ray = Ray {
             origin: Vec3 {
@@ -0,0 +1,229 @@
+use crate::bvh::Bounds;
+use crate::elements::plane::intersect_plane;
+use crate::hit::{across, Hit};
+use crate::material::Surface;
+use crate::sidedness::{facing_normal, Sidedness};
+use crate::Element;
+use crate::Number;
+use crate::Ray;
+use crate::Vec3;
+
+#[derive(Debug, Clone)]
+// A flat, convex polygon. Like Triangle, its front is the side its vertices go counter-clockwise
+// around.
+pub struct Polygon {
+    vertices: Vec<Vec3>,
+    color: Number,
+    surface: Surface,
+    sidedness: Sidedness,
+    // `new` leaves this on.
+    pub casts_shadows: bool,
+    facing: Vec3,
+    // How far out from the first vertex the others go, on whichever axis is furthest.
+    reach: Number,
+}
+
+impl Polygon {
+    // The vertices should all be on one plane, and the first three shouldn't be in a line.
+    pub fn new(
+        vertices: Vec<Vec3>,
+        color: Number,
+        surface: Surface,
+        sidedness: Sidedness,
+    ) -> Polygon {
+        assert!(vertices.len() >= 3);
+
+        let mut e1 = vertices[1];
+        e1.do_sub(&vertices[0]);
+        e1.do_normalize();
+        let mut e2 = vertices[2];
+        e2.do_sub(&vertices[0]);
+        e2.do_normalize();
+
+        let mut facing = e1;
+        facing.do_cross(&e2);
+        facing.do_normalize();
+
+        let mut reach = Number::from(0);
+        for v in vertices[1..].iter() {
+            let mut offset = *v;
+            offset.do_sub(&vertices[0]);
+            for c in [offset.x, offset.y, offset.z].iter_mut() {
+                c.do_abs();
+                if reach.is_less_than(c) {
+                    reach = *c;
+                }
+            }
+        }
+
+        Polygon {
+            vertices,
+            color,
+            surface,
+            sidedness,
+            casts_shadows: true,
+            facing,
+            reach,
+        }
+    }
+
+    // Inside a convex polygon is to the left of every edge. A point further from the first vertex
+    // than any of the others can't be inside, and is turned away before crossing anything with
+    // it, since it could be far enough off to overflow.
+    fn contains(&self, point: &Vec3) -> bool {
+        let mut from_first = *point;
+        from_first.do_sub(&self.vertices[0]);
+        let near = [from_first.x, from_first.y, from_first.z].iter().all(|c| {
+            let mut c = *c;
+            c.do_abs();
+            c.cmp(&self.reach) <= 0
+        });
+        if !near {
+            return false;
+        }
+
+        let count = self.vertices.len();
+
+        (0..count).all(|i| {
+            let from = self.vertices[i];
+            let mut edge = self.vertices[(i + 1) % count];
+            edge.do_sub(&from);
+
+            let mut to_point = *point;
+            to_point.do_sub(&from);
+
+            edge.do_cross(&to_point);
+            !edge.dot(&self.facing).is_negative()
+        })
+    }
+}
+
+impl Element for Polygon {
+    fn intersect(&self, ray: &Ray) -> Option<Number> {
+        let mut normal = self.facing;
+        normal.do_neg();
+
+        let distance = intersect_plane(&self.vertices[0], &normal, ray)?;
+
+        if self.contains(&ray.at(&distance)) {
+            Some(distance)
+        } else {
+            None
+        }
+    }
+
+    // Across the polygon in scene units, from its first vertex.
+    fn hit(&self, ray: &Ray, distance: Number) -> Hit<'_> {
+        let point = ray.at(&distance);
+        let (normal, front_face) = facing_normal(&ray.direction, &self.facing);
+
+        let mut from_first = point;
+        from_first.do_sub(&self.vertices[0]);
+        let (u, v) = across(&self.facing, &from_first);
+
+        Hit {
+            distance,
+            point,
+            normal,
+            geometric_normal: normal,
+            u,
+            v,
+            front_face,
+            color: self.color,
+            surface: self.surface,
+            element: self,
+        }
+    }
+
+    fn sidedness(&self) -> Sidedness {
+        self.sidedness
+    }
+
+    fn casts_shadows(&self) -> bool {
+        self.casts_shadows
+    }
+
+    fn is_flat(&self) -> bool {
+        true
+    }
+
+    fn bounds(&self) -> Option<Bounds> {
+        Some(Bounds::around(&self.vertices))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Polygon;
+    use crate::material::Surface;
+    use crate::sidedness::Sidedness;
+    use crate::vector::{vec3, Vec3};
+    use crate::Element;
+    use crate::Number;
+    use crate::Ray;
+    use rstest::rstest;
+
+    // A pentagon: a 4x2 rectangle with a point on top.
+    fn pentagon() -> Polygon {
+        Polygon::new(
+            vec![
+                vec3(-2, -1, -5),
+                vec3(2, -1, -5),
+                vec3(2, 1, -5),
+                vec3(0, 3, -5),
+                vec3(-2, 1, -5),
+            ],
+            Number::from(1),
+            Surface::Diffuse,
+            Sidedness::Both,
+        )
+    }
+
+    #[rstest]
+    #[case(vec3(0, 0, 0), Some(5.0))]
+    #[case(vec3(0, 2, 0), Some(5.0))]
+    #[case(vec3(-1, 1, 0), Some(5.0))]
+    #[case(vec3(-2, 2, 0), None)]
+    #[case(vec3(3, 0, 0), None)]
+    fn test_polygon_intersect(#[case] origin: Vec3, #[case] expected: Option<f64>) {
+        let hit = pentagon().intersect(&Ray {
+            origin,
+            direction: vec3(0, 0, -1),
+        });
+        assert_eq!(hit.map(|d| d.to_f64()), expected);
+    }
+
+    #[test]
+    fn test_polygon_normal() {
+        let ray = Ray {
+            origin: vec3(0, 0, -10),
+            direction: vec3(0, 0, 1),
+        };
+        let p = pentagon();
+        let hit = p.hit(&ray, Number::from(5));
+        assert!(!hit.front_face);
+        assert!(f64::abs(hit.normal.z.to_f64() + 1.0) < 0.01);
+    }
+
+    #[test]
+    fn test_polygon_far_grazing_ray() {
+        let floor = Polygon::new(
+            vec![
+                vec3(-10, -2, -10),
+                vec3(-10, -2, 10),
+                vec3(10, -2, 10),
+                vec3(10, -2, -10),
+            ],
+            Number::from(1),
+            Surface::Diffuse,
+            Sidedness::Both,
+        );
+        let mut direction = vec3(0, 0, -1);
+        direction.y = Number::from_f64(-1.0 / 3000.0);
+        let ray = Ray {
+            origin: vec3(0, 0, 0),
+            direction,
+        };
+        assert!(floor.intersect(&ray).is_none());
+    }
+}
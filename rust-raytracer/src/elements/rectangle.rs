@@ -0,0 +1,266 @@
+use crate::bvh::Bounds;
+use crate::elements::plane::intersect_plane;
+use crate::hit::Hit;
+use crate::material::Surface;
+use crate::sidedness::{facing_normal, Sidedness};
+use crate::Element;
+use crate::Number;
+use crate::Ray;
+use crate::Vec3;
+
+#[derive(Debug, Clone, Copy)]
+// The parallelogram with corners at origin, origin + edge1, origin + edge2 and the sum of all
+// three. Like Triangle, its front is the side from which edge1 turns counter-clockwise to edge2.
+pub struct Rectangle {
+    origin: Vec3,
+    edge1: Vec3,
+    edge2: Vec3,
+    color: Number,
+    surface: Surface,
+    sidedness: Sidedness,
+    // `new` leaves this on.
+    pub casts_shadows: bool,
+    // The edges as unit directions and lengths, so nothing ever multiplies two long edges.
+    along1: Vec3,
+    along2: Vec3,
+    length1: Number,
+    length2: Number,
+    facing: Vec3,
+    // How far apart the edges turn, 1 when they're square.
+    sin: Number,
+}
+
+impl Rectangle {
+    // The edges shouldn't be in a line.
+    pub fn new(
+        origin: Vec3,
+        edge1: Vec3,
+        edge2: Vec3,
+        color: Number,
+        surface: Surface,
+        sidedness: Sidedness,
+    ) -> Rectangle {
+        let (along1, length1) = unit(&edge1);
+        let (along2, length2) = unit(&edge2);
+
+        let mut facing = along1;
+        facing.do_cross(&along2);
+        let sin = facing.length();
+        facing.do_normalize();
+
+        Rectangle {
+            origin,
+            edge1,
+            edge2,
+            color,
+            surface,
+            sidedness,
+            casts_shadows: true,
+            along1,
+            along2,
+            length1,
+            length2,
+            facing,
+            sin,
+        }
+    }
+
+    // Whether a point in the plane is close enough that working out where it is won't overflow.
+    // Anything inside is within both edges' lengths of the origin on every axis.
+    fn is_near(&self, point: &Vec3) -> bool {
+        let mut reach = self.length1;
+        reach.do_add(&self.length2);
+
+        let mut p = *point;
+        p.do_sub(&self.origin);
+        [p.x, p.y, p.z].iter().all(|c| {
+            let mut c = *c;
+            c.do_abs();
+            c.cmp(&reach) <= 0
+        })
+    }
+
+    // How far a point in the plane is along each edge, from 0 to 1 across the rectangle.
+    fn along_edges(&self, point: &Vec3) -> (Number, Number) {
+        let mut p = *point;
+        p.do_sub(&self.origin);
+
+        // p = s along1 + t along2, so p x along2 = s (along1 x along2), and so on.
+        let mut u = p;
+        u.do_cross(&self.along2);
+        let mut u = u.dot(&self.facing);
+        u.do_div(&self.sin);
+        u.do_div(&self.length1);
+
+        let mut v = self.along1;
+        v.do_cross(&p);
+        let mut v = v.dot(&self.facing);
+        v.do_div(&self.sin);
+        v.do_div(&self.length2);
+
+        (u, v)
+    }
+}
+
+// The direction along an edge and how long it is. `length` keeps long edges from overflowing where
+// normalizing them directly would.
+fn unit(edge: &Vec3) -> (Vec3, Number) {
+    let length = edge.length();
+    let mut direction = *edge;
+    direction.x.do_div(&length);
+    direction.y.do_div(&length);
+    direction.z.do_div(&length);
+    (direction, length)
+}
+
+fn is_inside(part: &Number) -> bool {
+    !part.is_negative() && part.cmp(&Number::from(1)) <= 0
+}
+
+impl Element for Rectangle {
+    fn intersect(&self, ray: &Ray) -> Option<Number> {
+        let mut normal = self.facing;
+        normal.do_neg();
+
+        let distance = intersect_plane(&self.origin, &normal, ray)?;
+
+        let point = ray.at(&distance);
+        if !self.is_near(&point) {
+            return None;
+        }
+        let (u, v) = self.along_edges(&point);
+        if is_inside(&u) && is_inside(&v) {
+            Some(distance)
+        } else {
+            None
+        }
+    }
+
+    // From 0 to 1 along each edge.
+    fn hit(&self, ray: &Ray, distance: Number) -> Hit<'_> {
+        let point = ray.at(&distance);
+        let (normal, front_face) = facing_normal(&ray.direction, &self.facing);
+        let (u, v) = self.along_edges(&point);
+
+        Hit {
+            distance,
+            point,
+            normal,
+            geometric_normal: normal,
+            u,
+            v,
+            front_face,
+            color: self.color,
+            surface: self.surface,
+            element: self,
+        }
+    }
+
+    fn sidedness(&self) -> Sidedness {
+        self.sidedness
+    }
+
+    fn casts_shadows(&self) -> bool {
+        self.casts_shadows
+    }
+
+    fn is_flat(&self) -> bool {
+        true
+    }
+
+    fn bounds(&self) -> Option<Bounds> {
+        let mut along1 = self.origin;
+        along1.do_add(&self.edge1);
+        let mut along2 = self.origin;
+        along2.do_add(&self.edge2);
+        let mut opposite = along1;
+        opposite.do_add(&self.edge2);
+        Some(Bounds::around(&[self.origin, along1, along2, opposite]))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Rectangle;
+    use crate::material::Surface;
+    use crate::sidedness::Sidedness;
+    use crate::vector::{vec3, Vec3};
+    use crate::Element;
+    use crate::Number;
+    use crate::Ray;
+    use rstest::rstest;
+
+    // 40 wide and 20 high, big enough that squaring its area would overflow.
+    fn rectangle() -> Rectangle {
+        square(vec3(-20, -10, -5), vec3(40, 0, 0), vec3(0, 20, 0))
+    }
+
+    fn square(origin: Vec3, edge1: Vec3, edge2: Vec3) -> Rectangle {
+        Rectangle::new(
+            origin,
+            edge1,
+            edge2,
+            Number::from(1),
+            Surface::Diffuse,
+            Sidedness::Both,
+        )
+    }
+
+    #[rstest]
+    #[case(vec3(0, 0, 0), vec3(0, 0, -1), Some(5.0))]
+    #[case(vec3(19, -9, 0), vec3(0, 0, -1), Some(5.0))]
+    #[case(vec3(21, 0, 0), vec3(0, 0, -1), None)]
+    #[case(vec3(0, 11, 0), vec3(0, 0, -1), None)]
+    // From behind. Whether that counts is up to its sidedness.
+    #[case(vec3(0, 0, -10), vec3(0, 0, 1), Some(5.0))]
+    fn test_rectangle_intersect(
+        #[case] origin: Vec3,
+        #[case] direction: Vec3,
+        #[case] expected: Option<f64>,
+    ) {
+        let hit = rectangle().intersect(&Ray { origin, direction });
+        assert_eq!(hit.map(|d| d.to_f64()), expected);
+    }
+
+    #[test]
+    fn test_rectangle_hit() {
+        let ray = Ray {
+            origin: vec3(10, 0, 0),
+            direction: vec3(0, 0, -1),
+        };
+        let r = rectangle();
+        let hit = r.hit(&ray, Number::from(5));
+        assert_eq!(hit.normal.z.to_f64(), 1.0);
+        assert!(hit.front_face);
+        assert_eq!(hit.u.to_f64(), 0.75);
+        assert_eq!(hit.v.to_f64(), 0.5);
+    }
+
+    // Too big to normalize its edges by squaring them.
+    #[rstest]
+    #[case(vec3(0, 0, 0), Some(5.0))]
+    #[case(vec3(-99, 99, 0), Some(5.0))]
+    #[case(vec3(101, 0, 0), None)]
+    fn test_big_rectangle_intersect(#[case] origin: Vec3, #[case] expected: Option<f64>) {
+        let r = square(vec3(-100, -100, -5), vec3(200, 0, 0), vec3(0, 200, 0));
+        let ray = Ray {
+            origin,
+            direction: vec3(0, 0, -1),
+        };
+        assert_eq!(r.intersect(&ray).map(|d| d.to_f64()), expected);
+    }
+
+    // Dropping 1 in 3000, this meets the floor's plane about 6000 away, far enough that crossing
+    // where it lands with an edge would overflow.
+    #[test]
+    fn test_rectangle_far_grazing_ray() {
+        let floor = square(vec3(-10, -2, -10), vec3(20, 0, 0), vec3(0, 0, 20));
+        let mut direction = vec3(0, 0, -1);
+        direction.y = Number::from_f64(-1.0 / 3000.0);
+        let ray = Ray {
+            origin: vec3(0, 0, 0),
+            direction,
+        };
+        assert!(floor.intersect(&ray).is_none());
+    }
+}
@@ -0,0 +1,498 @@
+use crate::bvh::Bounds;
+use crate::elements::sphere::Sphere;
+use crate::hit::Hit;
+use crate::material::Surface;
+use crate::sidedness::{facing_normal, Sidedness};
+use crate::Element;
+use crate::Number;
+use crate::Ray;
+use crate::Vec3;
+use std::convert::TryFrom;
+
+// Sphere tracing gives up after this many steps, which only grazing rays ever need.
+const MAX_STEPS: i16 = 64;
+
+lazy_static! {
+    // Anything closer than this counts as on the surface. sqrt is only good to about 1/1000, so
+    // there's no point going much finer.
+    static ref HIT_DISTANCE: Number = Number::from_i16_frac(128);
+    // How far either side of a point the normal's central differences look.
+    static ref NORMAL_STEP: Number = Number::from_i16_frac(1024);
+}
+
+// Shapes described by how far any point is from their surface, negative inside. As long as that
+// never overestimates, a ray can safely step forward by it.
+#[derive(Debug, Clone)]
+pub enum Shape {
+    Sphere {
+        center: Vec3,
+        radius: Number,
+    },
+    Box {
+        center: Vec3,
+        half_size: Vec3,
+    },
+    // Still `half_size` overall, with the edges and corners rounded off by `radius`.
+    RoundedBox {
+        center: Vec3,
+        half_size: Vec3,
+        radius: Number,
+    },
+    // Lying flat, around the y axis.
+    Torus {
+        center: Vec3,
+        major_radius: Number,
+        minor_radius: Number,
+    },
+    // Both shapes, flowing into each other where they come within `smoothing` of touching.
+    SmoothUnion {
+        left: Box<Shape>,
+        right: Box<Shape>,
+        smoothing: Number,
+    },
+    // `right` carved out of `left`, with the cut's edges rounded over by about `smoothing`.
+    SmoothSubtract {
+        left: Box<Shape>,
+        right: Box<Shape>,
+        smoothing: Number,
+    },
+    // Copies of `shape` every `period` along each axis, with the original's cell centered on
+    // the origin. A zero period leaves that axis alone. The shape should fit inside its cell.
+    Repeat {
+        shape: Box<Shape>,
+        period: Vec3,
+    },
+}
+
+fn max(a: Number, b: Number) -> Number {
+    if a.is_less_than(&b) {
+        b
+    } else {
+        a
+    }
+}
+
+fn min(a: Number, b: Number) -> Number {
+    if b.is_less_than(&a) {
+        b
+    } else {
+        a
+    }
+}
+
+fn box_distance(center: &Vec3, half_size: &Vec3, point: &Vec3) -> Number {
+    let mut q = *point;
+    q.do_sub(center);
+    q.x.do_abs();
+    q.y.do_abs();
+    q.z.do_abs();
+    q.do_sub(half_size);
+
+    let zero = Number::from(0);
+    let mut d = Vec3 {
+        x: max(q.x, zero),
+        y: max(q.y, zero),
+        z: max(q.z, zero),
+    }
+    .length();
+    d.do_add(&min(max(q.x, max(q.y, q.z)), zero));
+    d
+}
+
+// How far between `a` (at 0) and `b` (at 1) the smooth operations blend, given the two
+// distances' gap.
+fn blend(gap: &Number, smoothing: &Number) -> Number {
+    let mut h = *gap;
+    h.do_div(smoothing);
+    h.do_div(&Number::from(2));
+    h.do_add(&Number::from_i16_frac(16384));
+    min(max(h, Number::from(0)), Number::from(1))
+}
+
+// a + (b - a) h - smoothing h (1 - h), the polynomial smooth minimum.
+fn smooth_mix(a: &Number, b: &Number, h: &Number, smoothing: &Number) -> Number {
+    let mut r = *b;
+    r.do_sub(a);
+    r.do_mul(h);
+    r.do_add(a);
+
+    let mut bulge = Number::from(1);
+    bulge.do_sub(h);
+    bulge.do_mul(h);
+    bulge.do_mul(smoothing);
+    r.do_sub(&bulge);
+    r
+}
+
+// `x` moved by whole periods into [-period/2, period/2].
+fn wrap(x: &Number, period: &Number) -> Number {
+    if period.is_zero() {
+        return *x;
+    }
+
+    let mut cells = *x;
+    cells.do_div(period);
+    // Rounds towards zero, so this leaves less than a period either way.
+    let whole = Number::from(i16::try_from(cells.to_int32().to_i32()).unwrap());
+
+    let mut offset = *period;
+    offset.do_mul(&whole);
+    let mut r = *x;
+    r.do_sub(&offset);
+
+    let mut half = *period;
+    half.do_div(&Number::from(2));
+    if half.is_less_than(&r) {
+        r.do_sub(period);
+    }
+    half.do_neg();
+    if r.is_less_than(&half) {
+        r.do_add(period);
+    }
+    r
+}
+
+impl Shape {
+    pub fn distance(&self, point: &Vec3) -> Number {
+        match self {
+            Shape::Sphere { center, radius } => {
+                let mut from_center = *point;
+                from_center.do_sub(center);
+                let mut d = from_center.length();
+                d.do_sub(radius);
+                d
+            }
+            Shape::Box { center, half_size } => box_distance(center, half_size, point),
+            Shape::RoundedBox {
+                center,
+                half_size,
+                radius,
+            } => {
+                let mut inner = *half_size;
+                inner.do_sub(&Vec3 {
+                    x: *radius,
+                    y: *radius,
+                    z: *radius,
+                });
+                let mut d = box_distance(center, &inner, point);
+                d.do_sub(radius);
+                d
+            }
+            Shape::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => {
+                let mut p = *point;
+                p.do_sub(center);
+
+                let mut ring = Vec3 {
+                    y: Number::from(0),
+                    ..p
+                }
+                .length();
+                ring.do_sub(major_radius);
+
+                let mut d = Vec3 {
+                    x: ring,
+                    y: p.y,
+                    z: Number::from(0),
+                }
+                .length();
+                d.do_sub(minor_radius);
+                d
+            }
+            Shape::SmoothUnion {
+                left,
+                right,
+                smoothing,
+            } => {
+                let a = left.distance(point);
+                let b = right.distance(point);
+
+                let mut gap = a;
+                gap.do_sub(&b);
+                let h = blend(&gap, smoothing);
+                smooth_mix(&a, &b, &h, smoothing)
+            }
+            Shape::SmoothSubtract {
+                left,
+                right,
+                smoothing,
+            } => {
+                // The smooth maximum of the left side and the inside-out right side, which is
+                // the smooth minimum with everything negated.
+                let mut a = left.distance(point);
+                a.do_neg();
+                let b = right.distance(point);
+
+                let mut gap = a;
+                gap.do_sub(&b);
+                let h = blend(&gap, smoothing);
+                let mut d = smooth_mix(&a, &b, &h, smoothing);
+                d.do_neg();
+                d
+            }
+            Shape::Repeat { shape, period } => shape.distance(&Vec3 {
+                x: wrap(&point.x, &period.x),
+                y: wrap(&point.y, &period.y),
+                z: wrap(&point.z, &period.z),
+            }),
+        }
+    }
+}
+
+// A distance field shape as a scene element, found by sphere tracing: stepping along the ray by
+// the distance to the nearest surface until that's next to nothing.
+#[derive(Debug, Clone)]
+pub struct Sdf {
+    pub shape: Shape,
+    // Only the inside of this sphere gets traced, so the shape has to fit in it.
+    pub bounds_center: Vec3,
+    pub bounds_radius: Number,
+    pub color: Number,
+    pub surface: Surface,
+    pub sidedness: Sidedness,
+    pub casts_shadows: bool,
+}
+
+impl Sdf {
+    fn bounding_sphere(&self) -> Sphere {
+        Sphere {
+            center: self.bounds_center,
+            radius: self.bounds_radius,
+            color: self.color,
+            surface: self.surface,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }
+    }
+
+    // The distance's gradient, from central differences.
+    fn surface_normal(&self, hit_point: &Vec3) -> Vec3 {
+        let difference = |axis: Vec3| {
+            let mut step = axis;
+            step.do_scale(&NORMAL_STEP);
+
+            let mut ahead = *hit_point;
+            ahead.do_add(&step);
+            let mut behind = *hit_point;
+            behind.do_sub(&step);
+
+            let mut d = self.shape.distance(&ahead);
+            d.do_sub(&self.shape.distance(&behind));
+            d
+        };
+
+        let zero = Number::from(0);
+        let one = Number::from(1);
+        let mut n = Vec3 {
+            x: difference(Vec3 {
+                x: one,
+                y: zero,
+                z: zero,
+            }),
+            y: difference(Vec3 {
+                x: zero,
+                y: one,
+                z: zero,
+            }),
+            z: difference(Vec3 {
+                x: zero,
+                y: zero,
+                z: one,
+            }),
+        };
+        n.do_normalize();
+        n
+    }
+}
+
+impl Element for Sdf {
+    fn intersect(&self, ray: &Ray) -> Option<Number> {
+        let bounds = self.bounding_sphere();
+        let (near, mut moved) = bounds.approach(ray)?;
+
+        let mut diameter = bounds.radius;
+        diameter.do_mul(&Number::from(2));
+
+        // A ray that starts inside is looking for the way out.
+        let inside = self.shape.distance(&near.origin).is_negative();
+
+        let mut t = Number::from(0);
+        for _ in 0..MAX_STEPS {
+            let mut p = near.direction;
+            p.do_scale(&t);
+            p.do_add(&near.origin);
+
+            let mut d = self.shape.distance(&p);
+            if inside {
+                d.do_neg();
+            }
+
+            if d.is_less_than(&HIT_DISTANCE) {
+                moved.do_add(&t);
+                return Some(moved);
+            }
+
+            t.do_add(&d);
+            if diameter.is_less_than(&t) {
+                return None;
+            }
+        }
+
+        None
+    }
+
+    // A distance field has no coordinates across its surface, so u and v stay at 0.
+    fn hit(&self, ray: &Ray, distance: Number) -> Hit<'_> {
+        let point = ray.at(&distance);
+        let (normal, front_face) = facing_normal(&ray.direction, &self.surface_normal(&point));
+
+        Hit {
+            distance,
+            point,
+            normal,
+            geometric_normal: normal,
+            u: Number::from(0),
+            v: Number::from(0),
+            front_face,
+            color: self.color,
+            surface: self.surface,
+            element: self,
+        }
+    }
+
+    fn sidedness(&self) -> Sidedness {
+        self.sidedness
+    }
+
+    fn casts_shadows(&self) -> bool {
+        self.casts_shadows
+    }
+
+    fn is_flat(&self) -> bool {
+        false
+    }
+
+    fn bounds(&self) -> Option<Bounds> {
+        Some(Bounds::around_sphere(
+            &self.bounds_center,
+            &self.bounds_radius,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Sdf, Shape};
+    use crate::material::Surface;
+    use crate::sidedness::Sidedness;
+    use crate::vector::{vec3, Vec3};
+    use crate::Element;
+    use crate::Number;
+    use crate::Ray;
+    use rstest::rstest;
+
+    fn sphere(x: i16, radius: i16) -> Shape {
+        Shape::Sphere {
+            center: vec3(x, 0, 0),
+            radius: Number::from(radius),
+        }
+    }
+
+    #[rstest]
+    #[case(sphere(0, 2), vec3(0, 0, 5), 3.0)]
+    #[case(sphere(0, 2), vec3(0, 0, 0), -2.0)]
+    #[case(Shape::Box { center: vec3(0, 0, 0), half_size: vec3(1, 2, 3) }, vec3(4, 0, 0), 3.0)]
+    #[case(Shape::Box { center: vec3(0, 0, 0), half_size: vec3(1, 2, 3) }, vec3(4, 6, 0), 5.0)]
+    #[case(Shape::Box { center: vec3(0, 0, 0), half_size: vec3(1, 2, 3) }, vec3(0, 0, 0), -1.0)]
+    // Past the rounded corner, the distance is from the corner's sphere.
+    #[case(
+        Shape::RoundedBox { center: vec3(0, 0, 0), half_size: vec3(2, 2, 2), radius: Number::from(1) },
+        vec3(4, 4, 0),
+        2.0_f64.sqrt() * 3.0 - 1.0
+    )]
+    #[case(
+        Shape::Torus { center: vec3(0, 0, 0), major_radius: Number::from(3), minor_radius: Number::from(1) },
+        vec3(3, 2, 0),
+        1.0
+    )]
+    #[case(
+        Shape::Torus { center: vec3(0, 0, 0), major_radius: Number::from(3), minor_radius: Number::from(1) },
+        vec3(0, 0, 0),
+        2.0
+    )]
+    // Well away from both, it's just the nearer one.
+    #[case(
+        Shape::SmoothUnion { left: Box::new(sphere(-3, 1)), right: Box::new(sphere(3, 1)), smoothing: Number::from(1) },
+        vec3(-3, 0, 5),
+        4.0
+    )]
+    // Halfway between, the blend pulls the surface in by a quarter of the smoothing.
+    #[case(
+        Shape::SmoothUnion { left: Box::new(sphere(-2, 1)), right: Box::new(sphere(2, 1)), smoothing: Number::from(2) },
+        vec3(0, 0, 0),
+        0.5
+    )]
+    #[case(
+        Shape::SmoothSubtract { left: Box::new(sphere(0, 3)), right: Box::new(sphere(3, 1)), smoothing: Number::from_i16_frac(8192) },
+        vec3(3, 0, 0),
+        1.0
+    )]
+    // The copy at x = 10 is the closest.
+    #[case(
+        Shape::Repeat { shape: Box::new(sphere(0, 1)), period: vec3(10, 0, 0) },
+        vec3(13, 0, 0),
+        2.0
+    )]
+    #[case(
+        Shape::Repeat { shape: Box::new(sphere(0, 1)), period: vec3(10, 0, 0) },
+        vec3(-18, 0, 0),
+        1.0
+    )]
+    fn test_distance(#[case] shape: Shape, #[case] point: Vec3, #[case] expected: f64) {
+        assert!(f64::abs(shape.distance(&point).to_f64() - expected) < 0.01);
+    }
+
+    fn sdf(shape: Shape) -> Sdf {
+        Sdf {
+            shape,
+            bounds_center: vec3(0, 0, 0),
+            bounds_radius: Number::from(5),
+            color: Number::from(1),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }
+    }
+
+    #[rstest]
+    #[case(vec3(0, 0, 10), vec3(0, 0, -1), Some(8.0))]
+    #[case(vec3(0, 3, 10), vec3(0, 0, -1), None)]
+    // From inside, out through the back.
+    #[case(vec3(0, 0, 0), vec3(0, 0, -1), Some(2.0))]
+    // Far enough off that only the bounding sphere keeps the numbers small.
+    #[case(vec3(0, 0, 3000), vec3(0, 0, -1), Some(2998.0))]
+    fn test_sphere_tracing(
+        #[case] origin: Vec3,
+        #[case] direction: Vec3,
+        #[case] expected: Option<f64>,
+    ) {
+        let hit = sdf(sphere(0, 2)).intersect(&Ray { origin, direction });
+        match expected {
+            None => assert!(hit.is_none()),
+            Some(d) => assert!(f64::abs(hit.unwrap().to_f64() - d) < 0.02),
+        }
+    }
+
+    #[rstest]
+    #[case(vec3(0, 2, 0), vec3(0, 1, 0))]
+    #[case(vec3(-2, 0, 0), vec3(-1, 0, 0))]
+    fn test_normals(#[case] point: Vec3, #[case] expected: Vec3) {
+        let n = sdf(sphere(0, 2)).surface_normal(&point);
+        assert!(f64::abs(n.x.to_f64() - expected.x.to_f64()) < 0.02);
+        assert!(f64::abs(n.y.to_f64() - expected.y.to_f64()) < 0.02);
+        assert!(f64::abs(n.z.to_f64() - expected.z.to_f64()) < 0.02);
+    }
+}
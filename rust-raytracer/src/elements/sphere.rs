@@ -1,3 +1,9 @@
+use crate::bvh::Bounds;
+use crate::elements::csg::{Solid, Span};
+use crate::fixed::PI;
+use crate::hit::{around, Hit};
+use crate::material::Surface;
+use crate::sidedness::{facing_normal, Sidedness};
 use crate::Element;
 use crate::Number;
 use crate::Ray;
@@ -8,22 +14,63 @@ pub struct Sphere {
     pub center: Vec3,
     pub radius: Number,
     pub color: Number,
+    pub surface: Surface,
+    pub sidedness: Sidedness,
+    pub casts_shadows: bool,
 }
 
-impl Element for Sphere {
-    fn intersect(&self, ray: &Ray) -> Option<Number> {
+impl Sphere {
+    // Moves the ray's origin up to where it enters the sphere, along with how far it moved. Shapes
+    // bounded by the sphere can then be solved with small numbers, however far away the ray
+    // started. None if the ray misses.
+    pub fn approach(&self, ray: &Ray) -> Option<(Ray, Number)> {
+        let t = self.intersect(ray)?;
+
+        let mut from_center = ray.origin;
+        from_center.do_sub(&self.center);
+
+        if from_center.is_within(&self.radius) {
+            return Some((*ray, Number::from(0)));
+        }
+
+        let mut origin = ray.direction;
+        origin.do_scale(&t);
+        origin.do_add(&ray.origin);
+        Some((
+            Ray {
+                origin,
+                direction: ray.direction,
+            },
+            t,
+        ))
+    }
+
+    // Where the ray's line goes in and comes out, nearest first. Either can be behind the ray.
+    fn crossings(&self, ray: &Ray) -> Option<(Number, Number)> {
         let mut to_center = self.center;
         to_center.do_sub(&ray.origin);
 
         let projected_onto_ray_dist = to_center.dot(&ray.direction);
-        let mut projected_onto_ray_dist_sq = projected_onto_ray_dist;
-        projected_onto_ray_dist_sq.do_mul(&projected_onto_ray_dist);
 
         let mut radius_sq = self.radius;
         radius_sq.do_mul(&self.radius);
 
-        let mut opposite_sq = to_center.dist_sq();
-        opposite_sq.do_sub(&projected_onto_ray_dist_sq);
+        // Secondary rays can start far away from the sphere, where squaring to_center would
+        // overflow. Work with the perpendicular offset instead, bailing out early if it's clearly
+        // too long to square safely.
+        let mut closest_approach = ray.direction;
+        closest_approach.do_scale(&projected_onto_ray_dist);
+        let mut opposite = to_center;
+        opposite.do_sub(&closest_approach);
+
+        for component in [opposite.x, opposite.y, opposite.z].iter_mut() {
+            component.do_abs();
+            if component.cmp(&self.radius) > 0 {
+                return None;
+            }
+        }
+
+        let opposite_sq = opposite.dist_sq();
 
         if opposite_sq.cmp(&radius_sq) > 0 {
             return None;
@@ -39,17 +86,10 @@ impl Element for Sphere {
         let mut t1 = projected_onto_ray_dist;
         t1.do_add(&thickness);
 
-        if t0.is_negative() && t1.is_negative() {
-            return None;
-        }
-
-        Some(if t0.is_less_than(&t1) { t0 } else { t1 })
-    }
-
-    fn color(&self, _: &Vec3) -> Number {
-        self.color
+        Some((t0, t1))
     }
 
+    // Always points outwards, even when the ray hit the sphere from the inside.
     fn surface_normal(&self, hit_point: &Vec3) -> Vec3 {
         let mut n = *hit_point;
         n.do_sub(&self.center);
@@ -58,9 +98,93 @@ impl Element for Sphere {
     }
 }
 
+impl Element for Sphere {
+    fn intersect(&self, ray: &Ray) -> Option<Number> {
+        let (t0, t1) = self.crossings(ray)?;
+
+        // t0 <= t1. If only t0 is behind the ray, the ray starts inside the sphere and exits at t1.
+        if !t0.is_negative() {
+            Some(t0)
+        } else if !t1.is_negative() {
+            Some(t1)
+        } else {
+            None
+        }
+    }
+
+    // Round the y axis like a globe, from the south pole (v = 0) to the north.
+    fn hit(&self, ray: &Ray, distance: Number) -> Hit<'_> {
+        let point = ray.at(&distance);
+        let outward = self.surface_normal(&point);
+        let (normal, front_face) = facing_normal(&ray.direction, &outward);
+
+        let up = Vec3 {
+            x: Number::from(0),
+            y: Number::from(1),
+            z: Number::from(0),
+        };
+        let mut half = Number::from(1);
+        half.do_div(&Number::from(2));
+
+        let mut v = outward.y;
+        v.do_asin();
+        v.do_div(&PI);
+        v.do_add(&half);
+
+        Hit {
+            distance,
+            point,
+            normal,
+            geometric_normal: normal,
+            u: around(&up, &outward),
+            v,
+            front_face,
+            color: self.color,
+            surface: self.surface,
+            element: self,
+        }
+    }
+
+    fn sidedness(&self) -> Sidedness {
+        self.sidedness
+    }
+
+    fn casts_shadows(&self) -> bool {
+        self.casts_shadows
+    }
+
+    fn is_flat(&self) -> bool {
+        false
+    }
+
+    fn bounds(&self) -> Option<Bounds> {
+        Some(Bounds::around_sphere(&self.center, &self.radius))
+    }
+}
+
+impl Solid for Sphere {
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        match self.crossings(ray) {
+            Some((enter, exit)) => vec![Span { enter, exit }],
+            None => vec![],
+        }
+    }
+
+    fn distance_to_surface(&self, point: &Vec3) -> Number {
+        let mut from_center = *point;
+        from_center.do_sub(&self.center);
+        let mut d = from_center.length();
+        d.do_sub(&self.radius);
+        d.do_abs();
+        d
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::Sphere;
+    use crate::material::Surface;
+    use crate::sidedness::Sidedness;
     use crate::vector::Vec3;
     use crate::Element;
     use crate::Number;
@@ -76,6 +200,9 @@ mod test {
             },
             radius: Number::from(2),
             color: Number::from(0),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
         };
 
         let ray = Ray {
@@ -96,4 +223,110 @@ mod test {
             .filter(|d| d.cmp(&Number::from(3)) == 0)
             .is_some());
     }
+
+    #[test]
+    fn test_sphere_intersect_from_inside() {
+        let sphere = Sphere {
+            center: Vec3 {
+                x: Number::from(0),
+                y: Number::from(0),
+                z: Number::from(-5),
+            },
+            radius: Number::from(2),
+            color: Number::from(0),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        };
+
+        let ray = Ray {
+            origin: Vec3 {
+                x: Number::from(0),
+                y: Number::from(0),
+                z: Number::from(-5),
+            },
+            direction: Vec3 {
+                x: Number::from(0),
+                y: Number::from(0),
+                z: Number::from(-1),
+            },
+        };
+
+        assert!(sphere
+            .intersect(&ray)
+            .filter(|d| d.cmp(&Number::from(2)) == 0)
+            .is_some());
+    }
+
+    #[test]
+    fn test_sphere_behind_ray() {
+        let sphere = Sphere {
+            center: Vec3 {
+                x: Number::from(0),
+                y: Number::from(0),
+                z: Number::from(5),
+            },
+            radius: Number::from(2),
+            color: Number::from(0),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        };
+
+        let ray = Ray {
+            origin: Vec3 {
+                x: Number::from(0),
+                y: Number::from(0),
+                z: Number::from(0),
+            },
+            direction: Vec3 {
+                x: Number::from(0),
+                y: Number::from(0),
+                z: Number::from(-1),
+            },
+        };
+
+        assert!(sphere.intersect(&ray).is_none());
+    }
+
+    #[test]
+    fn test_sphere_hit() {
+        let sphere = Sphere {
+            center: Vec3 {
+                x: Number::from(0),
+                y: Number::from(0),
+                z: Number::from(-5),
+            },
+            radius: Number::from(2),
+            color: Number::from(0),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        };
+
+        let ray = Ray {
+            origin: Vec3 {
+                x: Number::from(0),
+                y: Number::from(0),
+                z: Number::from(0),
+            },
+            direction: Vec3 {
+                x: Number::from(0),
+                y: Number::from(0),
+                z: Number::from(-1),
+            },
+        };
+
+        let outside = sphere.hit(&ray, sphere.intersect(&ray).unwrap());
+        assert!(outside.front_face);
+        assert!(f64::abs(outside.point.z.to_f64() + 3.0) < 0.01);
+        assert!(f64::abs(outside.normal.z.to_f64() - 1.0) < 0.01);
+        // On the equator.
+        assert!(f64::abs(outside.v.to_f64() - 0.5) < 0.01);
+
+        // Coming out the far side, the normal still faces back along the ray.
+        let inside = sphere.hit(&ray, Number::from(7));
+        assert!(!inside.front_face);
+        assert!(f64::abs(inside.normal.z.to_f64() - 1.0) < 0.01);
+    }
 }
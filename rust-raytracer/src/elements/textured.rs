@@ -0,0 +1,109 @@
+use crate::bvh::Bounds;
+use crate::hit::Hit;
+use crate::sidedness::Sidedness;
+use crate::texture::Texture;
+use crate::Element;
+use crate::Number;
+use crate::Ray;
+use std::rc::Rc;
+
+// An element colored by a texture instead of its own color. The texture is shared, so one can go
+// on any number of elements. Put inside a `Transformed`, the texture moves with the element.
+#[derive(Debug, Clone)]
+pub struct Textured<E: Element> {
+    element: E,
+    texture: Rc<dyn Texture>,
+}
+
+impl<E: Element> Textured<E> {
+    pub fn new(element: E, texture: Rc<dyn Texture>) -> Textured<E> {
+        Textured { element, texture }
+    }
+}
+
+impl<E: Element> Element for Textured<E> {
+    fn intersect(&self, ray: &Ray) -> Option<Number> {
+        self.element.intersect(ray)
+    }
+
+    fn hit(&self, ray: &Ray, distance: Number) -> Hit<'_> {
+        let hit = self.element.hit(ray, distance);
+        Hit {
+            color: self.texture.color(&hit.point, &hit.u, &hit.v),
+            element: self,
+            ..hit
+        }
+    }
+
+    fn sidedness(&self) -> Sidedness {
+        self.element.sidedness()
+    }
+
+    fn casts_shadows(&self) -> bool {
+        self.element.casts_shadows()
+    }
+
+    fn is_flat(&self) -> bool {
+        self.element.is_flat()
+    }
+
+    fn bounds(&self) -> Option<Bounds> {
+        self.element.bounds()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Textured;
+    use crate::elements::plane::Plane;
+    use crate::material::Surface;
+    use crate::sidedness::Sidedness;
+    use crate::texture::Checker;
+    use crate::vector::vec3;
+    use crate::Element;
+    use crate::Number;
+    use crate::Ray;
+    use rstest::rstest;
+    use std::rc::Rc;
+
+    fn floor() -> Textured<Plane> {
+        Textured::new(
+            Plane::new(
+                vec3(0, -2, 0),
+                vec3(0, -1, 0),
+                Number::from(0),
+                Surface::Diffuse,
+                Sidedness::Both,
+            ),
+            Rc::new(Checker {
+                scale: Number::from(1),
+                colors: [Number::from(1), Number::from(0)],
+            }),
+        )
+    }
+
+    // Neighbouring squares differ, and the pattern doesn't change across zero.
+    #[rstest]
+    #[case(0, -1)]
+    #[case(1, -1)]
+    #[case(1, -2)]
+    #[case(-1, -1)]
+    #[case(-1, -2)]
+    fn test_textured_plane(#[case] x: i16, #[case] z: i16) {
+        let mut origin = vec3(x, 0, z);
+        let mut half = Number::from(1);
+        half.do_div(&Number::from(2));
+        origin.x.do_add(&half);
+        origin.z.do_add(&half);
+        let ray = Ray {
+            origin,
+            direction: vec3(0, -1, 0),
+        };
+
+        let f = floor();
+        let hit = f.hit(&ray, f.intersect(&ray).unwrap());
+        let parity = (x + z).rem_euclid(2);
+        assert_eq!(hit.color.to_f64(), f64::from(1 - parity));
+        assert!(std::ptr::addr_eq(hit.element, &f));
+    }
+}
@@ -0,0 +1,371 @@
+use crate::bvh::Bounds;
+use crate::elements::csg::{everywhere, Solid, Span};
+use crate::elements::sphere::Sphere;
+use crate::fixed::PI;
+use crate::hit::{around, Hit};
+use crate::material::Surface;
+use crate::sidedness::{facing_normal, Sidedness};
+use crate::solver::roots_between;
+use crate::Element;
+use crate::Number;
+use crate::Ray;
+use crate::Vec3;
+
+// A ring around `axis` (which should be normalized). `major_radius` is from the center to the
+// middle of the tube, and `minor_radius` is the tube's own radius.
+#[derive(Debug, Clone, Copy)]
+pub struct Torus {
+    pub center: Vec3,
+    pub axis: Vec3,
+    pub major_radius: Number,
+    pub minor_radius: Number,
+    pub color: Number,
+    pub surface: Surface,
+    pub sidedness: Sidedness,
+    pub casts_shadows: bool,
+}
+
+impl Torus {
+    fn bounding_sphere(&self) -> Sphere {
+        let mut radius = self.major_radius;
+        radius.do_add(&self.minor_radius);
+        let mut slack = radius;
+        slack.do_div(&Number::from(100));
+        radius.do_add(&slack);
+
+        Sphere {
+            center: self.center,
+            radius,
+            color: self.color,
+            surface: self.surface,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }
+    }
+
+    // The part of `v` at right angles to the axis.
+    fn flatten(&self, v: &Vec3) -> Vec3 {
+        let mut along = self.axis;
+        along.do_scale(&v.dot(&self.axis));
+        let mut flat = *v;
+        flat.do_sub(&along);
+        flat
+    }
+
+    // Straight out from the nearest point on the ring through the middle of the tube.
+    fn surface_normal(&self, hit_point: &Vec3) -> Vec3 {
+        let mut p = *hit_point;
+        p.do_sub(&self.center);
+
+        let mut ring = self.flatten(&p);
+        ring.do_normalize();
+        ring.do_scale(&self.major_radius);
+
+        p.do_sub(&ring);
+        p.do_normalize();
+        p
+    }
+
+    // Everywhere the ray crosses the surface, nearest first, and whether it starts out inside the
+    // tube. With p = o + t d relative to the center, the surface is
+    //   (|p|^2 + R^2 - r^2)^2 = 4 R^2 |p flattened onto the ring's plane|^2
+    // which is a quartic in t, and the left side is the smaller one inside.
+    fn crossings(&self, ray: &Ray) -> Option<(Vec<Number>, bool)> {
+        let bounds = self.bounding_sphere();
+        // Starting on the bounding sphere means every hit is within its diameter.
+        let (near, moved) = bounds.approach(ray)?;
+
+        // Solving with everything shrunk to fit in a unit sphere stops a big torus's coefficients
+        // overflowing, then the root is scaled back up.
+        let scale = bounds.radius;
+        let shrink = |n: &Number| {
+            let mut n = *n;
+            n.do_div(&scale);
+            n
+        };
+
+        let mut o = near.origin;
+        o.do_sub(&self.center);
+        let o = Vec3 {
+            x: shrink(&o.x),
+            y: shrink(&o.y),
+            z: shrink(&o.z),
+        };
+        let d = near.direction;
+
+        let major = shrink(&self.major_radius);
+        let minor = shrink(&self.minor_radius);
+        let mut major_sq = major;
+        major_sq.do_mul(&major);
+        let mut minor_sq = minor;
+        minor_sq.do_mul(&minor);
+
+        // |p|^2 + R^2 - r^2 = t^2 + 2e t + k
+        let e = o.dot(&d);
+        let mut k = o.dist_sq();
+        k.do_add(&major_sq);
+        k.do_sub(&minor_sq);
+
+        // |p flattened|^2 = f2 t^2 + 2 f1 t + f0
+        let flat_o = self.flatten(&o);
+        let flat_d = self.flatten(&d);
+        let f2 = flat_d.dist_sq();
+        let f1 = flat_o.dot(&flat_d);
+        let f0 = flat_o.dist_sq();
+
+        let mut four_major_sq = major_sq;
+        four_major_sq.do_mul(&Number::from(4));
+
+        let mut e_sq = e;
+        e_sq.do_mul(&e);
+
+        let mut t0 = k;
+        t0.do_mul(&k);
+        let mut r = four_major_sq;
+        r.do_mul(&f0);
+        t0.do_sub(&r);
+
+        let mut t1 = e;
+        t1.do_mul(&k);
+        let mut r = four_major_sq;
+        r.do_mul(&f1);
+        r.do_div(&Number::from(2));
+        t1.do_sub(&r);
+        t1.do_mul(&Number::from(4));
+
+        let mut t2 = e_sq;
+        t2.do_mul(&Number::from(4));
+        let mut two_k = k;
+        two_k.do_mul(&Number::from(2));
+        t2.do_add(&two_k);
+        let mut r = four_major_sq;
+        r.do_mul(&f2);
+        t2.do_sub(&r);
+
+        let mut t3 = e;
+        t3.do_mul(&Number::from(4));
+
+        let roots = roots_between(
+            &[t0, t1, t2, t3, Number::from(1)],
+            &Number::from(0),
+            &Number::from(2),
+        );
+
+        let crossings = roots
+            .iter()
+            .map(|root| {
+                let mut t = *root;
+                t.do_mul(&scale);
+                t.do_add(&moved);
+                t
+            })
+            .collect();
+        Some((crossings, t0.is_negative()))
+    }
+}
+
+impl Element for Torus {
+    fn intersect(&self, ray: &Ray) -> Option<Number> {
+        self.crossings(ray)?.0.first().copied()
+    }
+
+    // Round the axis, then round the tube, starting from its inside edge.
+    fn hit(&self, ray: &Ray, distance: Number) -> Hit<'_> {
+        let point = ray.at(&distance);
+        let outward = self.surface_normal(&point);
+        let (normal, front_face) = facing_normal(&ray.direction, &outward);
+
+        let mut from_center = point;
+        from_center.do_sub(&self.center);
+        let mut out_from_axis = self.flatten(&from_center);
+        out_from_axis.do_normalize();
+
+        let mut v = Number::atan2(&outward.dot(&self.axis), &outward.dot(&out_from_axis));
+        v.do_div(&PI);
+        v.do_add(&Number::from(1));
+        v.do_div(&Number::from(2));
+
+        Hit {
+            distance,
+            point,
+            normal,
+            geometric_normal: normal,
+            u: around(&self.axis, &from_center),
+            v,
+            front_face,
+            color: self.color,
+            surface: self.surface,
+            element: self,
+        }
+    }
+
+    fn sidedness(&self) -> Sidedness {
+        self.sidedness
+    }
+
+    fn casts_shadows(&self) -> bool {
+        self.casts_shadows
+    }
+
+    fn is_flat(&self) -> bool {
+        false
+    }
+
+    fn bounds(&self) -> Option<Bounds> {
+        let mut reach = self.major_radius;
+        reach.do_add(&self.minor_radius);
+        Some(Bounds::around_sphere(&self.center, &reach))
+    }
+}
+
+impl Solid for Torus {
+    // Between each crossing into the tube and the next one out of it. One that starts inside is
+    // taken to have gone in somewhere behind it.
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        let (crossings, mut inside) = match self.crossings(ray) {
+            Some(found) => found,
+            None => return vec![],
+        };
+
+        let mut spans = vec![];
+        let mut enter = everywhere().enter;
+        for t in crossings {
+            if inside {
+                spans.push(Span { enter, exit: t });
+            } else {
+                enter = t;
+            }
+            inside = !inside;
+        }
+        spans
+    }
+
+    // How far off the circle through the middle of the tube, less the tube's radius.
+    fn distance_to_surface(&self, point: &Vec3) -> Number {
+        let mut p = *point;
+        p.do_sub(&self.center);
+
+        let mut out = self.flatten(&p).length();
+        out.do_sub(&self.major_radius);
+        let from_ring = Vec3 {
+            x: out,
+            y: p.dot(&self.axis),
+            z: Number::from(0),
+        };
+
+        let mut d = from_ring.length();
+        d.do_sub(&self.minor_radius);
+        d.do_abs();
+        d
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Torus;
+    use crate::elements::csg::Solid;
+    use crate::material::Surface;
+    use crate::sidedness::Sidedness;
+    use crate::vector::{vec3, Vec3};
+    use crate::Element;
+    use crate::Number;
+    use crate::Ray;
+    use rstest::rstest;
+
+    // Lying flat, 8 across on the outside with a hole 4 across.
+    fn torus() -> Torus {
+        sized(3, 1)
+    }
+
+    fn sized(major: i16, minor: i16) -> Torus {
+        Torus {
+            center: vec3(0, 0, -10),
+            axis: vec3(0, 1, 0),
+            major_radius: Number::from(major),
+            minor_radius: Number::from(minor),
+            color: Number::from(1),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }
+    }
+
+    #[rstest]
+    // Side on, into the near side of the tube.
+    #[case(3, 1, vec3(0, 0, 0), vec3(0, 0, -1), Some(6.0))]
+    // Down through the hole.
+    #[case(3, 1, vec3(0, 10, -10), vec3(0, -1, 0), None)]
+    // Down onto the top of the tube.
+    #[case(3, 1, vec3(3, 10, -10), vec3(0, -1, 0), Some(9.0))]
+    // Along the middle of the tube from inside it.
+    #[case(3, 1, vec3(3, 0, -10), vec3(1, 0, 0), Some(1.0))]
+    // From far off.
+    #[case(3, 1, vec3(0, 0, 3000), vec3(0, 0, -1), Some(3006.0))]
+    // Big enough that the quartic's coefficients would overflow without shrinking it first.
+    #[case(8, 1, vec3(8, 10, -10), vec3(0, -1, 0), Some(9.0))]
+    #[case(8, 1, vec3(0, 0, 20), vec3(0, 0, -1), Some(21.0))]
+    #[case(12, 3, vec3(0, 0, 20), vec3(0, 0, -1), Some(15.0))]
+    #[case(12, 3, vec3(0, 20, -10), vec3(0, -1, 0), None)]
+    #[case(12, 3, vec3(12, 20, -10), vec3(0, -1, 0), Some(17.0))]
+    #[case(12, 3, vec3(0, -20, 2), vec3(0, 1, 0), Some(17.0))]
+    #[case(12, 3, vec3(-30, 0, -10), vec3(1, 0, 0), Some(15.0))]
+    #[case(20, 5, vec3(0, 0, 40), vec3(0, 0, -1), Some(25.0))]
+    #[case(20, 5, vec3(0, 30, -10), vec3(0, -1, 0), None)]
+    #[case(20, 5, vec3(20, 30, -10), vec3(0, -1, 0), Some(25.0))]
+    #[case(20, 5, vec3(-20, -30, -10), vec3(0, 1, 0), Some(25.0))]
+    #[case(20, 5, vec3(-40, 0, -10), vec3(1, 0, 0), Some(15.0))]
+    fn test_torus_intersect(
+        #[case] major: i16,
+        #[case] minor: i16,
+        #[case] origin: Vec3,
+        #[case] direction: Vec3,
+        #[case] expected: Option<f64>,
+    ) {
+        let hit = sized(major, minor).intersect(&Ray { origin, direction });
+        match expected {
+            None => assert!(hit.is_none()),
+            Some(d) => assert!(f64::abs(hit.unwrap().to_f64() - d) < 0.01),
+        }
+    }
+
+    #[rstest]
+    #[case(vec3(0, 0, -6), vec3(0, 0, 1))]
+    #[case(vec3(0, 0, -8), vec3(0, 0, -1))]
+    #[case(vec3(3, 1, -10), vec3(0, 1, 0))]
+    fn test_torus_normals(#[case] point: Vec3, #[case] expected: Vec3) {
+        let n = torus().surface_normal(&point);
+        assert!(f64::abs(n.x.to_f64() - expected.x.to_f64()) < 0.01);
+        assert!(f64::abs(n.y.to_f64() - expected.y.to_f64()) < 0.01);
+        assert!(f64::abs(n.z.to_f64() - expected.z.to_f64()) < 0.01);
+    }
+
+    #[rstest]
+    // Through both sides of the tube.
+    #[case(vec3(0, 0, 0), vec3(0, 0, -1), vec![(6.0, 8.0), (12.0, 14.0)])]
+    #[case(vec3(0, 10, -10), vec3(0, -1, 0), vec![])]
+    #[case(vec3(3, 10, -10), vec3(0, -1, 0), vec![(9.0, 11.0)])]
+    // From inside the tube, so the first span goes in behind the origin.
+    #[case(vec3(3, 0, -10), vec3(-1, 0, 0), vec![(-16000.0, 1.0), (5.0, 7.0)])]
+    fn test_torus_spans(
+        #[case] origin: Vec3,
+        #[case] direction: Vec3,
+        #[case] expected: Vec<(f64, f64)>,
+    ) {
+        let spans = torus().spans(&Ray { origin, direction });
+        assert_eq!(spans.len(), expected.len());
+        for (span, (enter, exit)) in spans.iter().zip(expected.iter()) {
+            assert!(f64::abs(span.enter.to_f64() - enter) < 0.02);
+            assert!(f64::abs(span.exit.to_f64() - exit) < 0.02);
+        }
+    }
+
+    #[rstest]
+    #[case(vec3(0, 0, -6), 0.0)]
+    #[case(vec3(3, 0, -10), 1.0)]
+    #[case(vec3(0, 0, -10), 2.0)]
+    #[case(vec3(3, 3, -10), 2.0)]
+    fn test_torus_distance_to_surface(#[case] point: Vec3, #[case] expected: f64) {
+        let d = torus().distance_to_surface(&point);
+        assert!(f64::abs(d.to_f64() - expected) < 0.01);
+    }
+}
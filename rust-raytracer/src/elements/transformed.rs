@@ -0,0 +1,172 @@
+use crate::bvh::Bounds;
+use crate::hit::Hit;
+use crate::sidedness::Sidedness;
+use crate::solver::checked_div;
+use crate::transform::{Mat4, Transform};
+use crate::Element;
+use crate::Number;
+use crate::Ray;
+use crate::Vec3;
+use std::rc::Rc;
+
+// An element moved, turned or stretched into place. The element is shared, so one mesh can be
+// placed any number of times without copying its triangles.
+#[derive(Debug, Clone)]
+pub struct Transformed<E: Element> {
+    element: Rc<E>,
+    transform: Transform,
+}
+
+impl<E: Element> Transformed<E> {
+    // `to_world` takes the element from its own space to where it goes in the scene.
+    pub fn new(element: Rc<E>, to_world: &Mat4) -> Transformed<E> {
+        Transformed {
+            element,
+            transform: Transform::new(to_world),
+        }
+    }
+}
+
+impl<E: Element> Element for Transformed<E> {
+    fn intersect(&self, ray: &Ray) -> Option<Number> {
+        let (local, stretch) = self.transform.ray_to_object(ray);
+        let t = self.element.intersect(&local)?;
+        checked_div(&t, &stretch)
+    }
+
+    // Found in the element's own space and brought back out, keeping the distance as given.
+    fn hit(&self, ray: &Ray, distance: Number) -> Hit<'_> {
+        let (local, stretch) = self.transform.ray_to_object(ray);
+        let mut local_distance = distance;
+        local_distance.do_mul(&stretch);
+
+        let inner = self.element.hit(&local, local_distance);
+        Hit {
+            distance,
+            point: ray.at(&distance),
+            normal: self.transform.normal_to_world(&inner.normal),
+            geometric_normal: self.transform.normal_to_world(&inner.geometric_normal),
+            element: self,
+            ..inner
+        }
+    }
+
+    fn sidedness(&self) -> Sidedness {
+        self.element.sidedness()
+    }
+
+    fn casts_shadows(&self) -> bool {
+        self.element.casts_shadows()
+    }
+
+    // Flat stays flat however it's moved or stretched.
+    fn is_flat(&self) -> bool {
+        self.element.is_flat()
+    }
+
+    // Around the corners of the element's own box, wherever they end up.
+    fn bounds(&self) -> Option<Bounds> {
+        let corners: Vec<Vec3> = self
+            .element
+            .bounds()?
+            .corners()
+            .iter()
+            .map(|c| self.transform.point_to_world(c))
+            .collect();
+        Some(Bounds::around(&corners))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Transformed;
+    use crate::elements::sphere::Sphere;
+    use crate::fixed::PI;
+    use crate::material::Surface;
+    use crate::sidedness::Sidedness;
+    use crate::transform::Mat4;
+    use crate::vector::{vec3, Vec3};
+    use crate::Element;
+    use crate::Number;
+    use crate::Ray;
+    use rstest::rstest;
+    use std::rc::Rc;
+
+    fn unit_sphere() -> Rc<Sphere> {
+        Rc::new(Sphere {
+            center: vec3(0, 0, 0),
+            radius: Number::from(1),
+            color: Number::from(1),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        })
+    }
+
+    // An ellipsoid 4 wide, 2 tall and 2 deep, centered 5 in front of the origin and turned a
+    // quarter about y, so it's 4 deep instead.
+    fn ellipsoid() -> Transformed<Sphere> {
+        let mut angle = *PI;
+        angle.do_div(&Number::from(2));
+
+        let mut to_world = Mat4::translation(&vec3(0, 0, -5));
+        to_world.do_mul(&Mat4::rotation_y(&angle));
+        to_world.do_mul(&Mat4::scaling(&vec3(2, 1, 1)));
+        Transformed::new(unit_sphere(), &to_world)
+    }
+
+    #[rstest]
+    #[case(vec3(0, 0, 0), vec3(0, 0, -1), Some(3.0))]
+    #[case(vec3(-5, 0, -5), vec3(1, 0, 0), Some(4.0))]
+    #[case(vec3(0, 5, -5), vec3(0, -1, 0), Some(4.0))]
+    #[case(vec3(0, 5, -5), vec3(0, 1, 0), None)]
+    #[case(vec3(-5, 0, -4), vec3(1, 0, 0), Some(5.0 - 3.0_f64.sqrt() / 2.0))]
+    fn test_transformed_intersect(
+        #[case] origin: Vec3,
+        #[case] direction: Vec3,
+        #[case] expected: Option<f64>,
+    ) {
+        let hit = ellipsoid().intersect(&Ray { origin, direction });
+        match expected {
+            None => assert!(hit.is_none()),
+            Some(d) => assert!(f64::abs(hit.unwrap().to_f64() - d) < 0.01),
+        }
+    }
+
+    #[test]
+    fn test_transformed_normal() {
+        let e = ellipsoid();
+
+        let front = e.hit(
+            &Ray {
+                origin: vec3(0, 0, 0),
+                direction: vec3(0, 0, -1),
+            },
+            Number::from(3),
+        );
+        assert!(f64::abs(front.normal.z.to_f64() - 1.0) < 0.01);
+        assert!(f64::abs(front.point.z.to_f64() + 3.0) < 0.01);
+
+        let side = e.hit(
+            &Ray {
+                origin: vec3(5, 0, -5),
+                direction: vec3(-1, 0, 0),
+            },
+            Number::from(4),
+        );
+        assert!(f64::abs(side.normal.x.to_f64() - 1.0) < 0.01);
+        assert!(side.front_face);
+    }
+
+    #[test]
+    fn test_transformed_shares_element() {
+        let sphere = unit_sphere();
+        let moved = Transformed::new(Rc::clone(&sphere), &Mat4::translation(&vec3(0, 0, -5)));
+        let hit = moved.intersect(&Ray {
+            origin: vec3(0, 0, 0),
+            direction: vec3(0, 0, -1),
+        });
+        assert!(f64::abs(hit.unwrap().to_f64() - 4.0) < 0.01);
+        assert_eq!(Rc::strong_count(&sphere), 2);
+    }
+}
@@ -0,0 +1,378 @@
+use crate::bvh::Bounds;
+use crate::hit::Hit;
+use crate::material::Surface;
+use crate::sidedness::{facing_normal, Sidedness};
+use crate::solver::{checked_div, largest};
+use crate::Element;
+use crate::Number;
+use crate::Ray;
+use crate::Vec3;
+
+#[derive(Debug, Clone, Copy)]
+// Counter-clockwise vertices face the viewer.
+pub struct Triangle {
+    pub vertices: [Vec3; 3],
+    // Per-vertex normals to interpolate across the face. Flat shaded when None.
+    pub normals: Option<[Vec3; 3]>,
+    pub color: Number,
+    pub surface: Surface,
+    pub sidedness: Sidedness,
+    pub casts_shadows: bool,
+}
+
+lazy_static! {
+    // The determinant is the ray's direction dotted with the triangle's unnormalized normal, so
+    // this is roughly the cosine below which the ray is treated as parallel to the triangle.
+    static ref MIN_COSINE: Number = {
+        let mut n = Number::from(1);
+        n.do_div(&Number::from(4096));
+        n
+    };
+}
+
+impl Triangle {
+    fn edges(&self) -> (Vec3, Vec3) {
+        let mut e1 = self.vertices[1];
+        e1.do_sub(&self.vertices[0]);
+        let mut e2 = self.vertices[2];
+        e2.do_sub(&self.vertices[0]);
+        (e1, e2)
+    }
+
+    pub fn face_normal(&self) -> Vec3 {
+        let (mut e1, mut e2) = self.edges();
+        // Shrinking both to unit length first keeps the cross product in range for large
+        // triangles. `length` scales down before it squares, unlike `do_normalize`.
+        for e in [&mut e1, &mut e2] {
+            let length = e.length();
+            e.x.do_div(&length);
+            e.y.do_div(&length);
+            e.z.do_div(&length);
+        }
+        e1.do_cross(&e2);
+        e1.do_normalize();
+        e1
+    }
+
+    // The normal at barycentric `u` and `v`, blended from the vertex normals if there are any.
+    fn normal_at(&self, u: &Number, v: &Number) -> Vec3 {
+        match &self.normals {
+            None => self.face_normal(),
+            Some(normals) => {
+                let mut w = Number::from(1);
+                w.do_sub(u);
+                w.do_sub(v);
+
+                let mut n = normals[0];
+                n.do_scale(&w);
+                let mut n1 = normals[1];
+                n1.do_scale(u);
+                let mut n2 = normals[2];
+                n2.do_scale(v);
+                n.do_add(&n1);
+                n.do_add(&n2);
+                n.do_normalize();
+                n
+            }
+        }
+    }
+
+    // The side is the face's own, even where the blended normal leans past edge on.
+    pub fn hit_at(&self, ray: &Ray, distance: Number, u: Number, v: Number) -> Hit<'_> {
+        let point = ray.at(&distance);
+        let (geometric_normal, front_face) = facing_normal(&ray.direction, &self.face_normal());
+        let (normal, _) = facing_normal(&ray.direction, &self.normal_at(&u, &v));
+
+        Hit {
+            distance,
+            point,
+            normal,
+            geometric_normal,
+            u,
+            v,
+            front_face,
+            color: self.color,
+            surface: self.surface,
+            element: self,
+        }
+    }
+}
+
+// Möller–Trumbore, giving the distance along the ray and the barycentric u and v where it lands.
+// The barycentric tests compare numerators against the determinant instead of dividing by it,
+// since dividing by a tiny determinant overflows 16.16.
+pub fn intersect_triangle(vertices: &[Vec3; 3], ray: &Ray) -> Option<(Number, Number, Number)> {
+    let mut e1 = vertices[1];
+    e1.do_sub(&vertices[0]);
+    let mut e2 = vertices[2];
+    e2.do_sub(&vertices[0]);
+
+    let mut p = ray.direction;
+    p.do_cross(&e2);
+
+    let mut det = e1.dot(&p);
+
+    // Judged against the normal's size rather than on its own, which would lose small triangles
+    // altogether. Its largest component stands in for its length, to save a square root.
+    let mut normal = e1;
+    normal.do_cross(&e2);
+    let mut min_det = largest(&[normal.x, normal.y, normal.z]);
+    min_det.do_mul(&MIN_COSINE);
+
+    let mut abs_det = det;
+    abs_det.do_abs();
+    if abs_det.is_zero() || abs_det.is_less_than(&min_det) {
+        return None;
+    }
+
+    let mut s = ray.origin;
+    s.do_sub(&vertices[0]);
+
+    let mut q = s;
+    q.do_cross(&e1);
+
+    let mut u_num = s.dot(&p);
+    let mut v_num = ray.direction.dot(&q);
+    let mut t_num = e2.dot(&q);
+
+    // Flip everything so the determinant is positive, and the tests below are the same for
+    // either winding.
+    if det.is_negative() {
+        det.do_neg();
+        u_num.do_neg();
+        v_num.do_neg();
+        t_num.do_neg();
+    }
+
+    if u_num.is_negative() || u_num.cmp(&det) > 0 {
+        return None;
+    }
+
+    let mut uv_num = u_num;
+    uv_num.do_add(&v_num);
+    if v_num.is_negative() || uv_num.cmp(&det) > 0 {
+        return None;
+    }
+
+    if t_num.is_negative() {
+        return None;
+    }
+
+    // A grazing ray can still be too far from the triangle's corner for t to fit. u and v are
+    // within the determinant by now, so they divide safely.
+    let t = checked_div(&t_num, &det)?;
+    u_num.do_div(&det);
+    v_num.do_div(&det);
+    Some((t, u_num, v_num))
+}
+
+impl Element for Triangle {
+    fn intersect(&self, ray: &Ray) -> Option<Number> {
+        intersect_triangle(&self.vertices, ray).map(|(t, _, _)| t)
+    }
+
+    fn hit(&self, ray: &Ray, distance: Number) -> Hit<'_> {
+        let (_, u, v) = intersect_triangle(&self.vertices, ray)
+            .expect("hit with a ray that misses the triangle");
+        self.hit_at(ray, distance, u, v)
+    }
+
+    fn sidedness(&self) -> Sidedness {
+        self.sidedness
+    }
+
+    fn casts_shadows(&self) -> bool {
+        self.casts_shadows
+    }
+
+    fn is_flat(&self) -> bool {
+        true
+    }
+
+    fn bounds(&self) -> Option<Bounds> {
+        Some(Bounds::around(&self.vertices))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{intersect_triangle, Triangle};
+    use crate::material::Surface;
+    use crate::sidedness::Sidedness;
+    use crate::vector::{vec3, Vec3};
+    use crate::Element;
+    use crate::Number;
+    use crate::Ray;
+    use rstest::rstest;
+
+    fn triangle() -> Triangle {
+        Triangle {
+            vertices: [vec3(-1, -1, -5), vec3(1, -1, -5), vec3(0, 1, -5)],
+            normals: None,
+            color: Number::from(0),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }
+    }
+
+    fn ray_towards(x: i16, y: i16) -> Ray {
+        let mut direction = vec3(x, y, -5);
+        direction.do_normalize();
+        Ray {
+            origin: vec3(0, 0, 0),
+            direction,
+        }
+    }
+
+    #[test]
+    fn test_triangle_intersect() {
+        let t = triangle().intersect(&ray_towards(0, 0)).unwrap();
+        assert!(f64::abs(t.to_f64() - 5.0) < 0.001);
+    }
+
+    #[test]
+    fn test_triangle_miss() {
+        assert!(triangle().intersect(&ray_towards(1, 1)).is_none());
+        assert!(triangle().intersect(&ray_towards(0, -2)).is_none());
+    }
+
+    #[test]
+    fn test_triangle_parallel() {
+        let ray = Ray {
+            origin: vec3(-5, 0, -5),
+            direction: vec3(1, 0, 0),
+        };
+        assert!(triangle().intersect(&ray).is_none());
+    }
+
+    #[test]
+    fn test_sliver_triangle_does_not_overflow() {
+        let mut sliver = triangle();
+        sliver.vertices[2] = Vec3 {
+            y: {
+                let mut y = Number::from(-1);
+                y.do_add(&Number::from_i16_frac(1));
+                y
+            },
+            ..sliver.vertices[2]
+        };
+
+        assert!(sliver.intersect(&ray_towards(0, 0)).is_none());
+    }
+
+    // Too small for the determinant to get anywhere near 1/4096, even head on.
+    #[rstest]
+    #[case(16.0)]
+    #[case(64.0)]
+    #[case(256.0)]
+    fn test_small_triangle_intersect(#[case] shrink: f64) {
+        let corner = |x: f64, y: f64| Vec3 {
+            x: Number::from_f64(x / shrink),
+            y: Number::from_f64(y / shrink),
+            z: Number::from(-5),
+        };
+        let small = Triangle {
+            vertices: [corner(-1.0, -1.0), corner(1.0, -1.0), corner(0.0, 1.0)],
+            ..triangle()
+        };
+
+        let t = small.intersect(&ray_towards(0, 0)).unwrap();
+        assert!(f64::abs(t.to_f64() - 5.0) < 0.001);
+    }
+
+    // Where a ray straight down the z axis lands on the triangle at z = -5.
+    fn uv_at(t: &Triangle, point: &Vec3) -> (Number, Number) {
+        let ray = Ray {
+            origin: Vec3 {
+                z: Number::from(0),
+                ..*point
+            },
+            direction: vec3(0, 0, -1),
+        };
+        let (_, u, v) = intersect_triangle(&t.vertices, &ray).unwrap();
+        (u, v)
+    }
+
+    #[test]
+    fn test_barycentric() {
+        let t = triangle();
+
+        let (u, v) = uv_at(&t, &vec3(0, 1, -5));
+        assert!(f64::abs(u.to_f64()) < 0.001);
+        assert!(f64::abs(v.to_f64() - 1.0) < 0.001);
+
+        let (u, v) = uv_at(&t, &vec3(0, -1, -5));
+        assert!(f64::abs(u.to_f64() - 0.5) < 0.001);
+        assert!(f64::abs(v.to_f64()) < 0.001);
+    }
+
+    fn normal_at_point(t: &Triangle, point: &Vec3) -> Vec3 {
+        let (u, v) = uv_at(t, point);
+        t.normal_at(&u, &v)
+    }
+
+    #[test]
+    fn test_normals() {
+        let mut t = triangle();
+        let n = normal_at_point(&t, &vec3(0, 0, -5));
+        assert!(f64::abs(n.z.to_f64() - 1.0) < 0.001);
+
+        let mut tilted = vec3(1, 0, 1);
+        tilted.do_normalize();
+        t.normals = Some([vec3(0, 0, 1), tilted, vec3(0, 0, 1)]);
+
+        let at_vertex = normal_at_point(&t, &vec3(1, -1, -5));
+        assert!(f64::abs(at_vertex.x.to_f64() - tilted.x.to_f64()) < 0.01);
+
+        let between = normal_at_point(&t, &vec3(0, -1, -5));
+        assert!(between.x.is_positive());
+        assert!(between.x.is_less_than(&tilted.x));
+    }
+
+    #[test]
+    fn test_hit() {
+        let mut t = triangle();
+        let mut tilted = vec3(1, 0, 1);
+        tilted.do_normalize();
+        t.normals = Some([tilted, tilted, tilted]);
+
+        // From behind, so both normals get turned round to face the ray.
+        let ray = Ray {
+            origin: vec3(0, -1, -10),
+            direction: vec3(0, 0, 1),
+        };
+        let hit = t.hit(&ray, t.intersect(&ray).unwrap());
+        assert!(!hit.front_face);
+        assert!(f64::abs(hit.geometric_normal.z.to_f64() + 1.0) < 0.001);
+        assert!(f64::abs(hit.normal.x.to_f64() + tilted.x.to_f64()) < 0.01);
+        assert!(f64::abs(hit.u.to_f64() - 0.5) < 0.001);
+        assert!(f64::abs(hit.v.to_f64()) < 0.001);
+    }
+
+    // Edges 20 long and more, whose squares multiplied together are out of range, and whose own
+    // squares are too big to take the square root of.
+    #[rstest]
+    #[case(10)]
+    #[case(25)]
+    #[case(40)]
+    fn test_large_triangle_hit(#[case] size: i16) {
+        let t = Triangle {
+            vertices: [
+                vec3(-size, -size, -5),
+                vec3(size, -size, -5),
+                vec3(0, size, -5),
+            ],
+            ..triangle()
+        };
+        let ray = Ray {
+            origin: vec3(0, -size, 0),
+            direction: vec3(0, 0, -1),
+        };
+
+        let hit = t.hit(&ray, t.intersect(&ray).unwrap());
+        assert!(f64::abs(hit.distance.to_f64() - 5.0) < 0.001);
+        assert!(f64::abs(hit.u.to_f64() - 0.5) < 0.001);
+        assert!(f64::abs(hit.v.to_f64()) < 0.001);
+    }
+}
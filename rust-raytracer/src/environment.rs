@@ -0,0 +1,190 @@
+use crate::fixed::PI;
+use crate::Number;
+use crate::Vec3;
+use image::{DynamicImage, GenericImageView, ImageError, ImageResult};
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+// A latitude-longitude (equirectangular) image of the surroundings, stored as luminance.
+#[derive(Clone)]
+pub struct EnvironmentMap {
+    width: i16,
+    height: i16,
+    texels: Vec<Number>,
+}
+
+impl EnvironmentMap {
+    pub fn load<P: AsRef<Path>>(path: P) -> ImageResult<EnvironmentMap> {
+        EnvironmentMap::from_image(&image::open(path)?)
+    }
+
+    // Texels are found by i16 coordinates, so the image can be at most i16::MAX pixels either
+    // way, and needs at least one to sample.
+    pub fn from_image(image: &DynamicImage) -> ImageResult<EnvironmentMap> {
+        let (width, height) = image.dimensions();
+        let count = |pixels: u32| match i16::try_from(pixels) {
+            Ok(n) if n >= 1 => Ok(n),
+            _ => Err(ImageError::IoError(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("environment map can't be {}x{} pixels", width, height),
+            ))),
+        };
+        let (columns, rows) = (count(width)?, count(height)?);
+        let rgb = image.to_rgb8();
+
+        // Rec. 709 luma weights.
+        let weights = [2126, 7152, 722].iter().map(|w| {
+            let mut n = Number::from(*w);
+            n.do_div(&Number::from(10000));
+            n
+        });
+        let weights: Vec<Number> = weights.collect();
+
+        let mut texels = Vec::with_capacity(usize::try_from(width * height).unwrap());
+        for y in 0..height {
+            for x in 0..width {
+                let pixel = rgb.get_pixel(x, y);
+
+                let mut luminance = Number::from(0);
+                for (channel, weight) in pixel.0.iter().zip(weights.iter()) {
+                    let mut c = Number::from(i16::from(*channel));
+                    c.do_div(&Number::from(255));
+                    c.do_mul(weight);
+                    luminance.do_add(&c);
+                }
+
+                // Images are gamma encoded, and the renderer's own gamma is a square root, so
+                // squaring brings texels back into linear space.
+                let l = luminance;
+                luminance.do_mul(&l);
+
+                texels.push(luminance);
+            }
+        }
+
+        Ok(EnvironmentMap {
+            width: columns,
+            height: rows,
+            texels,
+        })
+    }
+
+    // `direction` should be normalized. -z is the center of the image and +y is the top row.
+    pub fn sample(&self, direction: &Vec3) -> Number {
+        let mut two_pi = *PI;
+        two_pi.do_mul(&Number::from(2));
+
+        let mut half = Number::from(1);
+        half.do_div(&Number::from(2));
+
+        let mut minus_z = direction.z;
+        minus_z.do_neg();
+
+        let mut u = Number::atan2(&direction.x, &minus_z);
+        u.do_div(&two_pi);
+        u.do_add(&half);
+
+        let mut v = direction.y;
+        v.do_asin();
+        v.do_div(&PI);
+        v.do_neg();
+        v.do_add(&half);
+
+        let x = texel_index(&u, self.width);
+        let y = texel_index(&v, self.height);
+
+        self.texels[y * usize::try_from(self.width).unwrap() + x]
+    }
+}
+
+// Maps [0, 1] onto 0..size, clamping at the edges.
+fn texel_index(t: &Number, size: i16) -> usize {
+    let mut scaled = *t;
+    scaled.do_mul(&Number::from(size));
+
+    let i = scaled.to_int32().to_i32();
+    let i = i.max(0).min(i32::from(size) - 1);
+    usize::try_from(i).unwrap()
+}
+
+impl fmt::Debug for EnvironmentMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "EnvironmentMap({}x{})", self.width, self.height)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::EnvironmentMap;
+    use crate::vector::Vec3;
+    use crate::Number;
+    use image::{DynamicImage, GenericImage, Rgba};
+    use rstest::rstest;
+
+    // 4x2 map: the top row is white, the bottom row is black, except the bottom-left texel.
+    fn test_map() -> EnvironmentMap {
+        let mut image = DynamicImage::new_rgb8(4, 2);
+        for x in 0..4 {
+            image.put_pixel(x, 0, Rgba([255, 255, 255, 255]));
+        }
+        image.put_pixel(0, 1, Rgba([0, 255, 0, 255]));
+
+        EnvironmentMap::from_image(&image).unwrap()
+    }
+
+    #[rstest]
+    #[case(0, 2)]
+    #[case(4, 0)]
+    #[case(40000, 1)]
+    fn test_bad_size(#[case] width: u32, #[case] height: u32) {
+        let image = DynamicImage::new_rgb8(width, height);
+        assert!(EnvironmentMap::from_image(&image).is_err());
+    }
+
+    #[test]
+    fn test_white_converts_to_one() {
+        let map = test_map();
+        assert!(f64::abs(map.texels[0].to_f64() - 1.0) < 0.001);
+        assert!(map.texels[5].is_zero());
+        assert!(f64::abs(map.texels[4].to_f64() - 0.7152 * 0.7152) < 0.001);
+    }
+
+    #[test]
+    fn test_sample() {
+        let map = test_map();
+
+        let up = Vec3 {
+            x: Number::from(0),
+            y: Number::from(1),
+            z: Number::from(0),
+        };
+        assert!(f64::abs(map.sample(&up).to_f64() - 1.0) < 0.001);
+
+        let mut down_back = Vec3 {
+            x: Number::from(0),
+            y: Number::from(-1),
+            z: Number::from(1),
+        };
+        down_back.do_normalize();
+        // Straight behind is the far left (and right) edge of the image.
+        let back_left = Vec3 {
+            x: {
+                let mut x = Number::from(-1);
+                x.do_div(&Number::from(100));
+                x
+            },
+            ..down_back
+        };
+        assert!(map.sample(&back_left).is_positive());
+
+        let mut down_forward = Vec3 {
+            x: Number::from(0),
+            y: Number::from(-1),
+            z: Number::from(-1),
+        };
+        down_forward.do_normalize();
+        assert!(map.sample(&down_forward).is_zero());
+    }
+}
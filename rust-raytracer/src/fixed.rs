@@ -49,6 +49,18 @@ impl Number {
         Number(r)
     }
 
+    // For loading data on the host side. Panics if `f` doesn't fit.
+    pub fn from_f64(f: f64) -> Number {
+        let scaled = (f * f64::from(SCALE_FACTOR.to_i32())).round();
+        assert!(
+            scaled >= f64::from(i32::MIN) && scaled <= f64::from(i32::MAX),
+            "{} is out of range",
+            f
+        );
+
+        Number(Int32::from_i32(scaled as i32))
+    }
+
     pub fn to_int32(mut self) -> Int32 {
         self.0.do_right_shift_bytes(*BYTES_FOR_FRAC);
         self.0
@@ -114,6 +126,170 @@ impl Number {
         self.0.do_mul(&SCALE_FACTOR_SQRT_SQRT_SQRT_POW_3);
     }
 
+    // e^x, for x up to about 10 before overflowing. Halves x until a short Taylor series is
+    // accurate, then squares the result back up.
+    pub fn do_exp(&mut self) {
+        if self.cmp(&Number::from(-12)) < 0 {
+            self.0.do_zero();
+            return;
+        }
+
+        let two = Number::from(2);
+
+        let mut limit = Number::from(1);
+        limit.do_div(&Number::from(4));
+
+        let mut halvings = 0;
+        loop {
+            let mut abs = *self;
+            abs.do_abs();
+            if abs.cmp(&limit) <= 0 {
+                break;
+            }
+            self.do_div(&two);
+            halvings += 1;
+        }
+
+        // 1 + x(1 + x/2(1 + x/3(1 + x/4))), in Horner form.
+        let x = *self;
+        let mut r = Number::from(1);
+        for n in (1..=4).rev() {
+            r.do_mul(&x);
+            r.do_div(&Number::from(n));
+            r.do_add(&Number::from(1));
+        }
+
+        for _ in 0..halvings {
+            let t = r;
+            r.do_mul(&t);
+        }
+
+        *self = r;
+    }
+
+    // atan2(y, x) in radians, from -PI to PI. Good to about 0.005 radians.
+    pub fn atan2(y: &Number, x: &Number) -> Number {
+        if x.is_zero() && y.is_zero() {
+            return Number::from(0);
+        }
+
+        let mut abs_x = *x;
+        abs_x.do_abs();
+        let mut abs_y = *y;
+        abs_y.do_abs();
+
+        // Approximate atan on [0, 1], and fold the rest of the circle onto that.
+        let steep = abs_y.cmp(&abs_x) > 0;
+        let mut z = if steep { abs_x } else { abs_y };
+        z.do_div(if steep { &abs_y } else { &abs_x });
+
+        // atan(z) ~= z * (PI/4 + 0.273 * (1 - z))
+        let mut r = Number::from(1);
+        r.do_sub(&z);
+        let mut k = Number::from(273);
+        k.do_div(&Number::from(1000));
+        r.do_mul(&k);
+        let mut quarter_pi = *PI;
+        quarter_pi.do_div(&Number::from(4));
+        r.do_add(&quarter_pi);
+        r.do_mul(&z);
+
+        if steep {
+            let mut half_pi = *PI;
+            half_pi.do_div(&Number::from(2));
+            half_pi.do_sub(&r);
+            r = half_pi;
+        }
+
+        if x.is_negative() {
+            let mut flipped = *PI;
+            flipped.do_sub(&r);
+            r = flipped;
+        }
+
+        if y.is_negative() {
+            r.do_neg();
+        }
+
+        r
+    }
+
+    // Clamps to [-1, 1] first, so slightly unnormalized inputs are fine.
+    pub fn do_asin(&mut self) {
+        let one = Number::from(1);
+        let mut minus_one = one;
+        minus_one.do_neg();
+
+        if self.cmp(&one) > 0 {
+            *self = one;
+        } else if self.cmp(&minus_one) < 0 {
+            *self = minus_one;
+        }
+
+        let mut cos = *self;
+        cos.do_mul(self);
+        cos.do_neg();
+        cos.do_add(&one);
+        cos.do_sqrt();
+
+        *self = Number::atan2(self, &cos);
+    }
+
+    // sin(x) in radians. Folds x onto [-PI/2, PI/2], where a short Taylor series is good to
+    // about 0.0001.
+    pub fn do_sin(&mut self) {
+        let mut two_pi = *PI;
+        two_pi.do_mul(&Number::from(2));
+        let mut half_pi = *PI;
+        half_pi.do_div(&Number::from(2));
+
+        // Take off whole turns, then any half turn left over.
+        let mut turns = *self;
+        turns.do_div(&two_pi);
+        let mut whole = two_pi;
+        whole.do_mul(&Number::from(i16::try_from(turns.to_int32().to_i32()).unwrap()));
+        self.do_sub(&whole);
+        let mut minus_pi = *PI;
+        minus_pi.do_neg();
+        if self.cmp(&PI) > 0 {
+            self.do_sub(&two_pi);
+        } else if self.cmp(&minus_pi) < 0 {
+            self.do_add(&two_pi);
+        }
+
+        // sin(PI - x) = sin(x)
+        let mut minus_half_pi = half_pi;
+        minus_half_pi.do_neg();
+        if self.cmp(&half_pi) > 0 {
+            let mut r = *PI;
+            r.do_sub(self);
+            *self = r;
+        } else if self.cmp(&minus_half_pi) < 0 {
+            minus_pi.do_sub(self);
+            *self = minus_pi;
+        }
+
+        // x(1 - x^2/6(1 - x^2/20(1 - x^2/42(1 - x^2/72)))), in Horner form.
+        let mut x_sq = *self;
+        x_sq.do_mul(self);
+        let mut r = Number::from(1);
+        for n in [72, 42, 20, 6].iter() {
+            r.do_mul(&x_sq);
+            r.do_div(&Number::from(*n));
+            r.do_neg();
+            r.do_add(&Number::from(1));
+        }
+        r.do_mul(self);
+        *self = r;
+    }
+
+    pub fn do_cos(&mut self) {
+        let mut half_pi = *PI;
+        half_pi.do_div(&Number::from(2));
+        self.do_add(&half_pi);
+        self.do_sin();
+    }
+
     pub fn do_neg(&mut self) {
         self.0.do_neg();
     }
@@ -122,6 +298,12 @@ impl Number {
         self.0.do_abs();
     }
 
+    // Down to the whole number below, so negatives go further from zero, unlike `to_int32`.
+    pub fn do_floor(&mut self) {
+        self.0.parts[0] = 0;
+        self.0.parts[1] = 0;
+    }
+
     pub fn to_f64(&self) -> f64 {
         f64::from(self.0.to_i32()) / f64::from(SCALE_FACTOR.to_i32())
     }
@@ -149,7 +331,7 @@ impl fmt::Display for Number {
 
 #[cfg(test)]
 mod test {
-    use super::Number;
+    use super::{Number, PI};
     use rstest::*;
 
     fn test_one_mul(x: i16, y: i16) {
@@ -299,6 +481,23 @@ mod test {
         );
     }
 
+    #[rstest]
+    #[case(0.0)]
+    #[case(1.5)]
+    #[case(-1.5)]
+    #[case(0.001)]
+    #[case(-12345.678)]
+    #[case(32767.0)]
+    fn test_from_f64(#[case] x: f64) {
+        let actual = Number::from_f64(x).to_f64();
+        assert!(
+            f64::abs(actual - x) <= 1.0 / 65536.0,
+            "{} converted to {}",
+            x,
+            actual
+        );
+    }
+
     #[test]
     fn test_is_negative() {
         assert_eq!(Number::from(0).is_negative(), false);
@@ -362,6 +561,20 @@ mod test {
         assert_eq!(x.to_f64(), 30000f64);
     }
 
+    #[rstest]
+    #[case(0.0)]
+    #[case(2.5)]
+    #[case(-2.5)]
+    #[case(-3.0)]
+    #[case(0.001)]
+    #[case(-0.001)]
+    #[case(-16000.75)]
+    fn test_floor(#[case] x: f64) {
+        let mut result = Number::from_f64(x);
+        result.do_floor();
+        assert_eq!(result.to_f64(), x.floor());
+    }
+
     #[rstest]
     #[case(0)]
     #[case(1)]
@@ -386,6 +599,122 @@ mod test {
         );
     }
 
+    #[rstest]
+    #[case(0, 1)]
+    #[case(1, 0)]
+    #[case(0, -1)]
+    #[case(-1, 0)]
+    #[case(1, 1)]
+    #[case(-3, 7)]
+    #[case(7, -3)]
+    #[case(-5, -2)]
+    #[case(100, 1)]
+    #[case(-1, -100)]
+    fn test_atan2(#[case] y: i16, #[case] x: i16) {
+        let result = Number::atan2(&Number::from(y), &Number::from(x));
+        let actual = result.to_f64();
+        let expected = f64::atan2(f64::from(y), f64::from(x));
+
+        assert!(
+            f64::abs(actual - expected) <= 0.005,
+            "atan2({}, {}) = {} but got {}",
+            y,
+            x,
+            expected,
+            actual
+        );
+    }
+
+    #[rstest]
+    #[case(0)]
+    #[case(3)]
+    #[case(5)]
+    #[case(-7)]
+    #[case(10)]
+    #[case(-10)]
+    fn test_asin(#[case] tenths: i16) {
+        let mut result = Number::from(tenths);
+        result.do_div(&Number::from(10));
+        result.do_asin();
+        let actual = result.to_f64();
+        let expected = f64::asin(f64::from(tenths) / 10.0);
+
+        assert!(
+            f64::abs(actual - expected) <= 0.01,
+            "asin({} / 10) = {} but got {}",
+            tenths,
+            expected,
+            actual
+        );
+    }
+
+    #[rstest]
+    #[case(0)]
+    #[case(30)]
+    #[case(90)]
+    #[case(135)]
+    #[case(180)]
+    #[case(-45)]
+    #[case(-170)]
+    #[case(270)]
+    #[case(750)]
+    #[case(-400)]
+    fn test_sin_cos(#[case] degrees: i16) {
+        let mut angle = Number::from(degrees);
+        angle.do_mul(&PI);
+        angle.do_div(&Number::from(180));
+        let radians = f64::from(degrees).to_radians();
+
+        let mut sin = angle;
+        sin.do_sin();
+        let mut cos = angle;
+        cos.do_cos();
+
+        assert!(
+            f64::abs(sin.to_f64() - radians.sin()) <= 0.001,
+            "sin({} degrees) = {} but got {}",
+            degrees,
+            radians.sin(),
+            sin
+        );
+        assert!(
+            f64::abs(cos.to_f64() - radians.cos()) <= 0.001,
+            "cos({} degrees) = {} but got {}",
+            degrees,
+            radians.cos(),
+            cos
+        );
+    }
+
+    #[rstest]
+    #[case(0, 1)]
+    #[case(1, 1)]
+    #[case(1, 10)]
+    #[case(-1, 10)]
+    #[case(-1, 1)]
+    #[case(-5, 2)]
+    #[case(-6, 1)]
+    #[case(-11, 1)]
+    #[case(-50, 1)]
+    #[case(5, 1)]
+    #[case(10, 1)]
+    fn test_exp(#[case] num: i16, #[case] den: i16) {
+        let mut result = Number::from(num);
+        result.do_div(&Number::from(den));
+        result.do_exp();
+        let actual = result.to_f64();
+        let expected = f64::exp(f64::from(num) / f64::from(den));
+
+        assert!(
+            f64::abs(actual - expected) <= 0.0001 || f64::abs(actual - expected) / expected <= 0.01,
+            "exp({} / {}) = {} but got {}",
+            num,
+            den,
+            expected,
+            actual
+        );
+    }
+
     #[rstest]
     #[case(-20)]
     #[case(-220)]
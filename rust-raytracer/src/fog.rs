@@ -0,0 +1,197 @@
+use crate::solver::checked_div;
+use crate::Number;
+use crate::Ray;
+
+#[derive(Debug, Clone, Copy)]
+pub enum FogFalloff {
+    // No fog before `start`, completely fogged after `end`.
+    Linear { start: Number, end: Number },
+    // e^(-density * distance)
+    Exponential { density: Number },
+    // e^(-(density * distance)^2)
+    ExponentialSquared { density: Number },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Fog {
+    pub falloff: FogFalloff,
+    pub color: Number,
+    // How high the fog reaches. Surfaces are fogged by their full distance wherever they are, but
+    // a ray that misses everything only passes through fog until it climbs above this.
+    pub ceiling: Number,
+}
+
+impl Fog {
+    // How much of a surface `distance` away is still visible through the fog, from 0 to 1.
+    pub fn transmittance(&self, distance: &Number) -> Number {
+        let zero = Number::from(0);
+        let one = Number::from(1);
+
+        match self.falloff {
+            FogFalloff::Linear { start, end } => {
+                if distance.cmp(&start) <= 0 {
+                    return one;
+                }
+                if distance.cmp(&end) >= 0 {
+                    return zero;
+                }
+
+                let mut t = end;
+                t.do_sub(distance);
+                let mut range = end;
+                range.do_sub(&start);
+                t.do_div(&range);
+                t
+            }
+            FogFalloff::Exponential { density } => {
+                let mut x = density;
+                x.do_mul(distance);
+                x.do_neg();
+                x.do_exp();
+                x
+            }
+            FogFalloff::ExponentialSquared { density } => {
+                let mut x = density;
+                x.do_mul(distance);
+
+                // Squaring anything past this would risk overflow, and e^-16 is zero anyway.
+                if x.cmp(&Number::from(4)) > 0 {
+                    return zero;
+                }
+
+                let t = x;
+                x.do_mul(&t);
+                x.do_neg();
+                x.do_exp();
+                x
+            }
+        }
+    }
+
+    // Blends `color`, seen from `distance` away, towards the fog color.
+    pub fn apply(&self, color: &mut Number, distance: &Number) {
+        let transmittance = self.transmittance(distance);
+
+        let mut fogged = Number::from(1);
+        fogged.do_sub(&transmittance);
+        fogged.do_mul(&self.color);
+
+        color.do_mul(&transmittance);
+        color.do_add(&fogged);
+    }
+
+    // Blends the background `color` seen along `ray` towards the fog color, by how much fog the
+    // ray goes through on its way up and out. One that never gets above the ceiling sees nothing
+    // but fog.
+    pub fn apply_to_background(&self, color: &mut Number, ray: &Ray) {
+        let mut height = self.ceiling;
+        height.do_sub(&ray.origin.y);
+        if height.is_negative() {
+            return;
+        }
+
+        if !ray.direction.y.is_positive() {
+            *color = self.color;
+            return;
+        }
+
+        match checked_div(&height, &ray.direction.y) {
+            Some(distance) => self.apply(color, &distance),
+            None => *color = self.color,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Fog, FogFalloff};
+    use crate::vector::Vec3;
+    use crate::Number;
+    use crate::Ray;
+    use rstest::rstest;
+
+    #[test]
+    fn test_linear_fog() {
+        let fog = Fog {
+            falloff: FogFalloff::Linear {
+                start: Number::from(10),
+                end: Number::from(20),
+            },
+            color: Number::from(1),
+            ceiling: Number::from(10),
+        };
+
+        assert_eq!(fog.transmittance(&Number::from(5)).to_f64(), 1.0);
+        assert_eq!(fog.transmittance(&Number::from(15)).to_f64(), 0.5);
+        assert_eq!(fog.transmittance(&Number::from(25)).to_f64(), 0.0);
+
+        let mut color = Number::from(0);
+        fog.apply(&mut color, &Number::from(15));
+        assert_eq!(color.to_f64(), 0.5);
+    }
+
+    #[test]
+    fn test_exponential_fog() {
+        let mut density = Number::from(1);
+        density.do_div(&Number::from(10));
+
+        let fog = Fog {
+            falloff: FogFalloff::Exponential { density },
+            color: Number::from(0),
+            ceiling: Number::from(10),
+        };
+        let t = fog.transmittance(&Number::from(10)).to_f64();
+        assert!(f64::abs(t - f64::exp(-1.0)) < 0.005);
+
+        let fog = Fog {
+            falloff: FogFalloff::ExponentialSquared { density },
+            color: Number::from(0),
+            ceiling: Number::from(10),
+        };
+        let t = fog.transmittance(&Number::from(20)).to_f64();
+        assert!(f64::abs(t - f64::exp(-4.0)) < 0.005);
+        assert!(fog.transmittance(&Number::from(30000)).is_zero());
+    }
+
+    // Fog from the ground up to 10, thickening linearly to full over 20. A ray gets halfway
+    // straight up, but has twice as far to go at 30 degrees up, and never gets out going level.
+    #[rstest]
+    #[case(0, 0.0, 1.0, 0.5)]
+    #[case(0, 0.75_f64.sqrt(), 0.5, 1.0)]
+    #[case(0, 1.0, 0.0, 1.0)]
+    #[case(0, 0.0, -1.0, 1.0)]
+    #[case(5, 0.0, 1.0, 0.25)]
+    // Above the fog already.
+    #[case(12, 1.0, 0.0, 0.0)]
+    fn test_fog_over_background(
+        #[case] height: i16,
+        #[case] x: f64,
+        #[case] y: f64,
+        #[case] expected: f64,
+    ) {
+        let fog = Fog {
+            falloff: FogFalloff::Linear {
+                start: Number::from(0),
+                end: Number::from(20),
+            },
+            color: Number::from(1),
+            ceiling: Number::from(10),
+        };
+        let ray = Ray {
+            origin: Vec3 {
+                x: Number::from(0),
+                y: Number::from(height),
+                z: Number::from(0),
+            },
+            direction: Vec3 {
+                x: Number::from_f64(x),
+                y: Number::from_f64(y),
+                z: Number::from(0),
+            },
+        };
+
+        let mut color = Number::from(0);
+        fog.apply_to_background(&mut color, &ray);
+        assert!(f64::abs(color.to_f64() - expected) < 0.01);
+    }
+}
@@ -0,0 +1,420 @@
+use crate::bvh::{blocks, test_element, Bounds, PADDING};
+use crate::elements::boxes::{component, slab_span};
+use crate::solver::{checked_div, FAR};
+use crate::Element;
+use crate::Number;
+use crate::Ray;
+use crate::Vec3;
+use std::convert::TryFrom;
+
+// No axis is cut into more cells than this, so a grid never has more than 16^3.
+const MAX_CELLS: i16 = 16;
+
+// One axis of a walk through a grid of cells (a DDA): which way it steps, how far along the ray
+// the next cell boundary is, and how far apart the boundaries are.
+pub struct Stepper {
+    pub step: i16,
+    pub next: Number,
+    pub delta: Number,
+}
+
+impl Stepper {
+    // `position` is in cells, and `direction` is in cells per unit along the ray.
+    pub fn new(position: &Number, index: i16, direction: &Number) -> Stepper {
+        let mut speed = *direction;
+        speed.do_abs();
+        let delta = checked_div(&Number::from(1), &speed).unwrap_or(*FAR);
+
+        // How far to the boundary on the side the ray's heading for.
+        let mut to_boundary = Number::from(index);
+        let step = if direction.is_negative() {
+            to_boundary.do_neg();
+            to_boundary.do_add(position);
+            -1
+        } else {
+            to_boundary.do_add(&Number::from(1));
+            to_boundary.do_sub(position);
+            1
+        };
+
+        let mut next = delta;
+        next.do_mul(&to_boundary);
+        if delta.cmp(&FAR) == 0 {
+            next = *FAR;
+        }
+
+        Stepper { step, next, delta }
+    }
+}
+
+// The elements of a scene or group, filed into equal cells across their bounds. A ray walks
+// through the cells it crosses in order, testing what's in each, and stops at the first cell
+// with a hit inside. Everything is flat lists and loops, with no recursion.
+#[derive(Debug)]
+pub struct Grid {
+    // None if every element goes on forever.
+    bounds: Option<Bounds>,
+    cell_size: Vec3,
+    counts: [i16; 3],
+    // Which elements overlap each cell, all in one list: cell i's are from starts[i] up to
+    // starts[i + 1]. Cells go along x first, then y, then z.
+    starts: Vec<usize>,
+    indices: Vec<usize>,
+    elements: Vec<Box<dyn Element>>,
+    // Anything without bounds, like an infinite plane, is tested against every ray.
+    unbounded: Vec<Box<dyn Element>>,
+}
+
+impl Grid {
+    // Cells are about as big as the average element, up to MAX_CELLS along each axis.
+    pub fn new(elements: Vec<Box<dyn Element>>) -> Grid {
+        let mut boxes = vec![];
+        let mut bounded = vec![];
+        let mut unbounded = vec![];
+        for elem in elements {
+            match elem.bounds() {
+                Some(b) => {
+                    boxes.push(b.grown(&PADDING));
+                    bounded.push(elem);
+                }
+                None => unbounded.push(elem),
+            }
+        }
+
+        let mut grid = Grid {
+            bounds: None,
+            cell_size: Vec3 {
+                x: Number::from(1),
+                y: Number::from(1),
+                z: Number::from(1),
+            },
+            counts: [1; 3],
+            starts: vec![0, 0],
+            indices: vec![],
+            elements: bounded,
+            unbounded,
+        };
+        if boxes.is_empty() {
+            return grid;
+        }
+
+        let bounds = boxes[1..].iter().fold(boxes[0], |all, b| all.union(b));
+        let mut extent = bounds.max;
+        extent.do_sub(&bounds.min);
+
+        // A running average, so adding up lots of sizes can't go out of range.
+        let mut typical = Number::from(0);
+        for (i, b) in boxes.iter().enumerate() {
+            let mut size = b.max;
+            size.do_sub(&b.min);
+            let mut step = size.x;
+            step.do_add(&size.y);
+            step.do_add(&size.z);
+            step.do_div(&Number::from(3));
+            step.do_sub(&typical);
+            step.do_div(&Number::from(i16::try_from(i + 1).unwrap_or(i16::MAX)));
+            typical.do_add(&step);
+        }
+
+        let mut sizes = [Number::from(1); 3];
+        for (axis, (count, size)) in grid.counts.iter_mut().zip(sizes.iter_mut()).enumerate() {
+            let length = component(&extent, axis);
+            let cells = checked_div(&length, &typical).unwrap_or(*FAR);
+            *count = i16::try_from(cells.to_int32().to_i32() + 1)
+                .unwrap_or(MAX_CELLS)
+                .min(MAX_CELLS);
+            *size = length;
+            size.do_div(&Number::from(*count));
+        }
+        grid.cell_size = Vec3 {
+            x: sizes[0],
+            y: sizes[1],
+            z: sizes[2],
+        };
+        grid.bounds = Some(bounds);
+
+        let total = grid.counts.iter().map(|c| *c as usize).product();
+        let mut cells: Vec<Vec<usize>> = vec![vec![]; total];
+        for (i, b) in boxes.iter().enumerate() {
+            let low = grid.cell_of(&bounds, &b.min);
+            let high = grid.cell_of(&bounds, &b.max);
+            for z in low[2]..=high[2] {
+                for y in low[1]..=high[1] {
+                    for x in low[0]..=high[0] {
+                        cells[grid.index(&[x, y, z])].push(i);
+                    }
+                }
+            }
+        }
+
+        grid.starts = vec![0];
+        for cell in cells {
+            grid.indices.extend(cell);
+            grid.starts.push(grid.indices.len());
+        }
+        grid
+    }
+
+    // How many cells `point` is along `axis` from the grid's corner, including fractions.
+    fn position(&self, bounds: &Bounds, point: &Vec3, axis: usize) -> Number {
+        let mut p = component(point, axis);
+        p.do_sub(&component(&bounds.min, axis));
+        p.do_div(&component(&self.cell_size, axis));
+        p
+    }
+
+    // The cell a point is in, or the nearest one if it's outside.
+    fn cell_of(&self, bounds: &Bounds, point: &Vec3) -> [i16; 3] {
+        let mut cell = [0; 3];
+        for (axis, c) in cell.iter_mut().enumerate() {
+            let p = self.position(bounds, point, axis);
+            if !p.is_negative() {
+                *c = i16::try_from(p.to_int32().to_i32())
+                    .unwrap_or(i16::MAX)
+                    .min(self.counts[axis] - 1);
+            }
+        }
+        cell
+    }
+
+    fn index(&self, cell: &[i16; 3]) -> usize {
+        (cell[2] as usize * self.counts[1] as usize + cell[1] as usize) * self.counts[0] as usize
+            + cell[0] as usize
+    }
+
+    // The nearest visible hit and what it's on, ignoring `skip` and anything at or beyond
+    // `max_distance`.
+    pub fn nearest(
+        &self,
+        ray: &Ray,
+        max_distance: Option<&Number>,
+        skip: Option<&dyn Element>,
+    ) -> Option<(Number, &dyn Element)> {
+        let mut nearest: Option<(Number, &dyn Element)> = None;
+        for elem in self.unbounded.iter() {
+            test_element(elem.as_ref(), ray, max_distance, skip, &mut nearest);
+        }
+        if let Some(bounds) = &self.bounds {
+            self.walk(bounds, ray, max_distance, |cell, exit| {
+                for e in cell.iter() {
+                    test_element(
+                        self.elements[*e].as_ref(),
+                        ray,
+                        max_distance,
+                        skip,
+                        &mut nearest,
+                    );
+                }
+                // Anything hit further on might still be beaten by something in a later cell.
+                nearest.is_some_and(|(d, _)| !exit.is_less_than(&d))
+            });
+        }
+        nearest
+    }
+
+    // Whether anything that casts shadows, other than `skip`, is hit before `max_distance`.
+    pub fn occluded(
+        &self,
+        ray: &Ray,
+        max_distance: Option<&Number>,
+        skip: Option<&dyn Element>,
+    ) -> bool {
+        self.unbounded
+            .iter()
+            .any(|elem| blocks(elem.as_ref(), ray, max_distance, skip))
+            || self.bounds.as_ref().is_some_and(|bounds| {
+                self.walk(bounds, ray, max_distance, |cell, _| {
+                    cell.iter()
+                        .any(|e| blocks(self.elements[*e].as_ref(), ray, max_distance, skip))
+                })
+            })
+    }
+
+    // Steps through the cells under the ray in order (a 3D DDA), handing `visit` the elements in
+    // each one and how far along the ray it ends, until `visit` says to stop or the ray leaves the
+    // grid or passes `limit`. Returns whether `visit` stopped it.
+    fn walk<F>(&self, bounds: &Bounds, ray: &Ray, limit: Option<&Number>, mut visit: F) -> bool
+    where
+        F: FnMut(&[usize], &Number) -> bool,
+    {
+        let span = match slab_span(&bounds.min, &bounds.max, ray) {
+            Some(span) if !span.exit.is_negative() => span,
+            _ => return false,
+        };
+        let start = if span.enter.is_negative() {
+            Number::from(0)
+        } else {
+            span.enter
+        };
+        let mut length = span.exit;
+        length.do_sub(&start);
+
+        let mut origin = ray.direction;
+        origin.do_scale(&start);
+        origin.do_add(&ray.origin);
+
+        let mut cell = self.cell_of(bounds, &origin);
+        let mut steppers: Vec<Stepper> = (0..3)
+            .map(|axis| {
+                let mut speed = component(&ray.direction, axis);
+                speed.do_div(&component(&self.cell_size, axis));
+                Stepper::new(&self.position(bounds, &origin, axis), cell[axis], &speed)
+            })
+            .collect();
+
+        // How far along the ray the current cell starts.
+        let mut entered = start;
+
+        loop {
+            if let Some(limit) = limit {
+                if !entered.is_less_than(limit) {
+                    return false;
+                }
+            }
+
+            let mut axis = 0;
+            for a in 1..3 {
+                if steppers[a].next.is_less_than(&steppers[axis].next) {
+                    axis = a;
+                }
+            }
+            let leaving = length.cmp(&steppers[axis].next) <= 0;
+            let mut exit = if leaving { length } else { steppers[axis].next };
+            exit.do_add(&start);
+
+            let i = self.index(&cell);
+            if visit(&self.indices[self.starts[i]..self.starts[i + 1]], &exit) {
+                return true;
+            }
+            if leaving {
+                return false;
+            }
+
+            let stepper = &mut steppers[axis];
+            entered = exit;
+            stepper.next.do_add(&stepper.delta);
+            cell[axis] += stepper.step;
+            if cell[axis] < 0 || cell[axis] >= self.counts[axis] {
+                return false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Grid;
+    use crate::elements::boxes::AlignedBox;
+    use crate::elements::plane::Plane;
+    use crate::elements::sphere::Sphere;
+    use crate::material::Surface;
+    use crate::sidedness::{intersect_visible, Sidedness};
+    use crate::vector::{vec3, Vec3};
+    use crate::Element;
+    use crate::Number;
+    use crate::Ray;
+    use rstest::rstest;
+
+    // Small spheres in a row behind a wall that spans lots of cells, over a floor.
+    fn elements() -> Vec<Box<dyn Element>> {
+        let mut elements: Vec<Box<dyn Element>> = vec![
+            Box::new(Plane::new(
+                vec3(0, -2, 0),
+                vec3(0, 1, 0),
+                Number::from(1),
+                Surface::Diffuse,
+                Sidedness::Both,
+            )),
+            Box::new(AlignedBox {
+                min: vec3(-8, -2, -12),
+                max: vec3(8, 4, -11),
+                face_colors: [Number::from(1); 6],
+                surface: Surface::Diffuse,
+                sidedness: Sidedness::Both,
+                casts_shadows: true,
+            }),
+        ];
+        for x in -3..4 {
+            elements.push(Box::new(Sphere {
+                center: vec3(x * 2, 0, -6),
+                radius: Number::from(1),
+                color: Number::from(1),
+                surface: Surface::Diffuse,
+                sidedness: Sidedness::Both,
+                casts_shadows: true,
+            }));
+        }
+        elements
+    }
+
+    #[rstest]
+    #[case(vec3(0, 0, 0), vec3(0, 0, -1))]
+    #[case(vec3(0, 0, 0), vec3(1, 0, -6))]
+    #[case(vec3(0, 0, 0), vec3(1, 1, -6))]
+    #[case(vec3(0, 0, 0), vec3(-3, -1, -9))]
+    #[case(vec3(0, 0, 0), vec3(1, 0, 0))]
+    #[case(vec3(-7, 0, -6), vec3(1, 0, 0))]
+    #[case(vec3(1, 0, -6), vec3(0, 0, -1))]
+    #[case(vec3(3, 3, -8), vec3(-1, -1, -1))]
+    fn test_grid_matches_every_element(#[case] origin: Vec3, #[case] direction: Vec3) {
+        let mut direction = direction;
+        direction.do_normalize();
+        let ray = Ray { origin, direction };
+
+        let expected = elements()
+            .iter()
+            .filter_map(|e| intersect_visible(e.as_ref(), &ray))
+            .min_by(|a, b| a.cmp(b).cmp(&0));
+        let actual = Grid::new(elements())
+            .nearest(&ray, None, None)
+            .map(|(d, _)| d);
+
+        match expected {
+            None => assert!(actual.is_none()),
+            Some(d) => assert!(f64::abs(actual.unwrap().to_f64() - d.to_f64()) < 0.001),
+        }
+    }
+
+    #[rstest]
+    #[case(vec3(0, 0, 0), vec3(0, 0, -1), None, true)]
+    #[case(vec3(0, 0, 0), vec3(0, 0, -1), Some(4), false)]
+    #[case(vec3(0, 0, 0), vec3(1, 1, -6), None, true)]
+    #[case(vec3(0, 0, 0), vec3(0, 1, 0), None, false)]
+    #[case(vec3(-7, 2, -6), vec3(1, 0, 0), None, false)]
+    fn test_grid_occluded(
+        #[case] origin: Vec3,
+        #[case] direction: Vec3,
+        #[case] limit: Option<i16>,
+        #[case] expected: bool,
+    ) {
+        let mut direction = direction;
+        direction.do_normalize();
+        let ray = Ray { origin, direction };
+        let limit = limit.map(Number::from);
+        assert_eq!(
+            Grid::new(elements()).occluded(&ray, limit.as_ref(), None),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_grid_max_distance() {
+        let grid = Grid::new(elements());
+        let ray = Ray {
+            origin: vec3(0, 0, 0),
+            direction: vec3(0, 0, -1),
+        };
+        // The middle sphere is 5 away.
+        assert!(grid.nearest(&ray, Some(&Number::from(4)), None).is_none());
+        assert!(grid.nearest(&ray, Some(&Number::from(6)), None).is_some());
+    }
+
+    #[test]
+    fn test_empty_grid() {
+        let ray = Ray {
+            origin: vec3(0, 0, 0),
+            direction: vec3(0, 0, -1),
+        };
+        assert!(Grid::new(vec![]).nearest(&ray, None, None).is_none());
+    }
+}
@@ -0,0 +1,274 @@
+use crate::accelerator::Accelerator;
+use crate::hit::Hit;
+use crate::material::Surface;
+use crate::transform::Transform;
+use crate::Element;
+use crate::Number;
+use crate::Ray;
+
+// A named branch of the scene. Everything in it is placed by its transform, on top of those of
+// the groups it's in, and can be hidden all at once.
+#[derive(Debug)]
+pub struct Group {
+    pub name: String,
+    // From the group's own space into its parent's. None leaves everything where it is.
+    pub transform: Option<Transform>,
+    // Replaces the surface of everything inside, unless a group further in replaces it again.
+    pub surface: Option<Surface>,
+    pub visible: bool,
+    pub elements: Accelerator,
+    pub groups: Vec<Group>,
+}
+
+impl Group {
+    // The nearest hit inside, brought back out into the parent's space. `surface` is what the
+    // groups around this one replace the surface with, if they do.
+    fn trace(
+        &self,
+        ray: &Ray,
+        max_distance: Option<&Number>,
+        skip: Option<&dyn Element>,
+        surface: Option<Surface>,
+    ) -> Option<Hit<'_>> {
+        let surface = self.surface.or(surface);
+        match self.to_local(ray, max_distance) {
+            None => nearest(
+                &self.elements,
+                &self.groups,
+                ray,
+                max_distance,
+                skip,
+                surface,
+            ),
+            Some((local, local_max, stretch)) => {
+                let hit = nearest(
+                    &self.elements,
+                    &self.groups,
+                    &local,
+                    local_max.as_ref(),
+                    skip,
+                    surface,
+                )?;
+                // Only groups with a transform have a local space.
+                hit.to_world(ray, self.transform.as_ref()?, &stretch)
+            }
+        }
+    }
+
+    fn occluded(
+        &self,
+        ray: &Ray,
+        max_distance: Option<&Number>,
+        skip: Option<&dyn Element>,
+    ) -> bool {
+        match self.to_local(ray, max_distance) {
+            None => occluded(&self.elements, &self.groups, ray, max_distance, skip),
+            Some((local, local_max, _)) => occluded(
+                &self.elements,
+                &self.groups,
+                &local,
+                local_max.as_ref(),
+                skip,
+            ),
+        }
+    }
+
+    // The ray in the group's own space, `max_distance` stretched to match, and the stretch
+    // itself. None if the group has no transform.
+    fn to_local(
+        &self,
+        ray: &Ray,
+        max_distance: Option<&Number>,
+    ) -> Option<(Ray, Option<Number>, Number)> {
+        let (local, stretch) = self.transform.as_ref()?.ray_to_object(ray);
+        let local_max = max_distance.map(|m| {
+            let mut m = *m;
+            m.do_mul(&stretch);
+            m
+        });
+        Some((local, local_max, stretch))
+    }
+}
+
+// The group called `name`, searching depth first.
+pub fn find_mut<'a>(groups: &'a mut [Group], name: &str) -> Option<&'a mut Group> {
+    for group in groups.iter_mut() {
+        if group.name == name {
+            return Some(group);
+        }
+        if let Some(found) = find_mut(&mut group.groups, name) {
+            return Some(found);
+        }
+    }
+    None
+}
+
+// The nearest hit among `elements` and inside the visible `groups`, ignoring `skip` and anything
+// at or beyond `max_distance`. Its surface is replaced by `surface`, if there is one.
+pub fn nearest<'a>(
+    elements: &'a Accelerator,
+    groups: &'a [Group],
+    ray: &Ray,
+    max_distance: Option<&Number>,
+    skip: Option<&dyn Element>,
+    surface: Option<Surface>,
+) -> Option<Hit<'a>> {
+    let mut nearest_hit = elements
+        .nearest(ray, max_distance, skip)
+        .map(|(d, element)| {
+            let mut hit = element.hit(ray, d);
+            if let Some(surface) = surface {
+                hit.surface = surface;
+            }
+            hit
+        });
+    for group in groups.iter().filter(|group| group.visible) {
+        // Only anything nearer than what's been hit so far is worth finding.
+        let limit = nearest_hit
+            .as_ref()
+            .map(|hit| hit.distance)
+            .or_else(|| max_distance.copied());
+        if let Some(hit) = group.trace(ray, limit.as_ref(), skip, surface) {
+            nearest_hit = Some(hit);
+        }
+    }
+    nearest_hit
+}
+
+// Whether anything that casts shadows among `elements` or inside the visible `groups`, other
+// than `skip`, is hit before `max_distance`.
+pub fn occluded(
+    elements: &Accelerator,
+    groups: &[Group],
+    ray: &Ray,
+    max_distance: Option<&Number>,
+    skip: Option<&dyn Element>,
+) -> bool {
+    elements.occluded(ray, max_distance, skip)
+        || groups
+            .iter()
+            .any(|group| group.visible && group.occluded(ray, max_distance, skip))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{find_mut, nearest, occluded, Group};
+    use crate::accelerator::Accelerator;
+    use crate::elements::sphere::Sphere;
+    use crate::material::Surface;
+    use crate::sidedness::Sidedness;
+    use crate::transform::{Mat4, Transform};
+    use crate::vector::vec3;
+    use crate::Number;
+    use crate::Ray;
+
+    fn sphere(z: i16) -> Box<Sphere> {
+        Box::new(Sphere {
+            center: vec3(0, 0, z),
+            radius: Number::from(1),
+            color: Number::from(1),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        })
+    }
+
+    fn forward() -> Ray {
+        Ray {
+            origin: vec3(0, 0, 0),
+            direction: vec3(0, 0, -1),
+        }
+    }
+
+    // A sphere with its surface at z = -4, and an "inner" group doubling one at z = -3 so that
+    // its surface is there too, then pulled 1 closer by an "outer" group around it.
+    fn nested() -> Vec<Group> {
+        let inner = Group {
+            name: String::from("inner"),
+            transform: Some(Transform::new(&Mat4::scaling(&vec3(2, 2, 2)))),
+            surface: Some(Surface::Reflective {
+                reflectivity: Number::from(1),
+            }),
+            visible: true,
+            elements: Accelerator::bvh(vec![sphere(-3)]),
+            groups: vec![],
+        };
+        vec![
+            Group {
+                name: String::from("far"),
+                transform: None,
+                surface: None,
+                visible: true,
+                elements: Accelerator::bvh(vec![sphere(-5)]),
+                groups: vec![],
+            },
+            Group {
+                name: String::from("outer"),
+                transform: Some(Transform::new(&Mat4::translation(&vec3(0, 0, 1)))),
+                // Replaced again by "inner".
+                surface: Some(Surface::Diffuse),
+                visible: true,
+                elements: Accelerator::bvh(vec![]),
+                groups: vec![inner],
+            },
+        ]
+    }
+
+    #[test]
+    fn test_nested_transforms() {
+        let groups = nested();
+        let none = Accelerator::bvh(vec![]);
+        let hit = nearest(&none, &groups, &forward(), None, None, None).unwrap();
+        assert!(f64::abs(hit.distance.to_f64() - 3.0) < 0.01);
+
+        // Brought all the way back out through both groups.
+        assert!(f64::abs(hit.point.z.to_f64() + 3.0) < 0.01);
+        assert!(f64::abs(hit.normal.z.to_f64() - 1.0) < 0.01);
+        assert!(matches!(hit.surface, Surface::Reflective { .. }));
+
+        // Further than that doesn't count.
+        assert!(nearest(
+            &none,
+            &groups,
+            &forward(),
+            Some(&Number::from(3)),
+            None,
+            None
+        )
+        .is_none());
+        assert!(!occluded(
+            &none,
+            &groups,
+            &forward(),
+            Some(&Number::from(3)),
+            None
+        ));
+        assert!(occluded(
+            &none,
+            &groups,
+            &forward(),
+            Some(&Number::from(4)),
+            None
+        ));
+    }
+
+    #[test]
+    fn test_hidden_groups() {
+        let mut groups = nested();
+        find_mut(&mut groups, "inner").unwrap().visible = false;
+
+        let none = Accelerator::bvh(vec![]);
+        let hit = nearest(&none, &groups, &forward(), None, None, None).unwrap();
+        assert!(f64::abs(hit.distance.to_f64() - 4.0) < 0.01);
+        assert!(f64::abs(hit.point.z.to_f64() + 4.0) < 0.01);
+        assert!(matches!(hit.surface, Surface::Diffuse));
+
+        // Shadow rays don't see it either, so only the far sphere is in the way.
+        let mut between = Number::from(7);
+        between.do_div(&Number::from(2));
+        assert!(!occluded(&none, &groups, &forward(), Some(&between), None));
+        assert!(occluded(&none, &groups, &forward(), None, None));
+
+        assert!(find_mut(&mut groups, "missing").is_none());
+    }
+}
@@ -0,0 +1,98 @@
+use crate::fixed::PI;
+use crate::material::Surface;
+use crate::solver::checked_div;
+use crate::transform::Transform;
+use crate::Element;
+use crate::Number;
+use crate::Ray;
+use crate::Vec3;
+
+// Everything shading needs to know about where a ray hit an element. The element works it all out
+// in one go, once it's known to be the nearest.
+#[derive(Debug, Clone, Copy)]
+pub struct Hit<'a> {
+    pub distance: Number,
+    pub point: Vec3,
+    // Faces back along the ray. May be smoothed, like across a mesh.
+    pub normal: Vec3,
+    // The normal of the actual surface, also facing back along the ray.
+    pub geometric_normal: Vec3,
+    // Where on the element's surface the hit is, in whatever suits its shape: scene units across
+    // an endless plane, say, or from 0 to 1 around a sphere and up it.
+    pub u: Number,
+    pub v: Number,
+    // Whether the ray hit the side the element's normals point out of.
+    pub front_face: bool,
+    pub color: Number,
+    pub surface: Surface,
+    // Which element was hit, compared by address, like when skipping the one a ray starts on.
+    pub element: &'a dyn Element,
+}
+
+impl<'a> Hit<'a> {
+    // The hit as seen by `ray`, from outside `transform`, given the ray in the element's own space
+    // was stretched by `stretch` going in. None if that can't be undone.
+    pub fn to_world(self, ray: &Ray, transform: &Transform, stretch: &Number) -> Option<Hit<'a>> {
+        let distance = checked_div(&self.distance, stretch)?;
+        Some(Hit {
+            distance,
+            // Along the outer ray, so the point doesn't pick up the transform's rounding.
+            point: ray.at(&distance),
+            normal: transform.normal_to_world(&self.normal),
+            geometric_normal: transform.normal_to_world(&self.geometric_normal),
+            ..self
+        })
+    }
+}
+
+// How far round `axis` the direction `offset` points, from 0 to 1. `axis` should be normalized.
+pub fn around(axis: &Vec3, offset: &Vec3) -> Number {
+    let (tangent, bitangent) = axis.orthonormal_basis();
+
+    let mut turn = Number::atan2(&offset.dot(&bitangent), &offset.dot(&tangent));
+    turn.do_div(&PI);
+    turn.do_add(&Number::from(1));
+    turn.do_div(&Number::from(2));
+    turn
+}
+
+// Where `offset` lands in the plane facing `normal`, which should be normalized.
+pub fn across(normal: &Vec3, offset: &Vec3) -> (Number, Number) {
+    let (tangent, bitangent) = normal.orthonormal_basis();
+    (offset.dot(&tangent), offset.dot(&bitangent))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{across, around};
+    use crate::vector::{vec3, Vec3};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(vec3(0, 0, 1))]
+    #[case(vec3(0, 1, 0))]
+    #[case(vec3(1, 0, 0))]
+    fn test_around(#[case] axis: Vec3) {
+        let (tangent, bitangent) = axis.orthonormal_basis();
+
+        // Turns are measured from the far side, so the tangent is half way round.
+        let start = around(&axis, &tangent).to_f64();
+        let quarter = around(&axis, &bitangent).to_f64();
+        assert!(f64::abs(start - 0.5) < 0.01);
+        assert!(f64::abs(quarter - 0.75) < 0.01);
+
+        // Sliding along the axis doesn't change how far round it is.
+        let mut lifted = bitangent;
+        lifted.do_add(&axis);
+        assert!(f64::abs(around(&axis, &lifted).to_f64() - quarter) < 0.01);
+    }
+
+    #[test]
+    fn test_across() {
+        let normal = vec3(0, 1, 0);
+        let (u, v) = across(&normal, &vec3(3, 7, 4));
+
+        // The height above the plane is dropped, leaving the 5 units out along it.
+        assert!(f64::abs(u.to_f64().hypot(v.to_f64()) - 5.0) < 0.01);
+    }
+}
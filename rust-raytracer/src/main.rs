@@ -3,48 +3,138 @@ extern crate image;
 extern crate lazy_static;
 extern crate rstest;
 
+mod accelerator;
+mod background;
+mod bvh;
 mod elements;
+mod environment;
+// The arithmetic underneath is left as it was written rather than restyled for clippy.
+#[allow(
+    clippy::bool_assert_comparison,
+    clippy::clone_on_copy,
+    clippy::needless_return,
+    clippy::wrong_self_convention
+)]
 mod fixed;
+mod fog;
+mod grid;
+mod group;
+mod hit;
+#[allow(
+    clippy::assign_op_pattern,
+    clippy::clone_on_copy,
+    clippy::identity_op,
+    clippy::int_plus_one,
+    clippy::needless_borrow,
+    clippy::needless_return,
+    clippy::useless_conversion,
+    clippy::wrong_self_convention
+)]
 mod int32;
 mod lights;
+mod material;
+mod obj;
+mod occlusion;
+mod path_tracer;
+mod random;
 mod ray;
+mod scenes;
+mod sidedness;
+mod solver;
+mod texture;
+mod transform;
 mod vector;
 
 use crate::ray::Ray;
-use elements::plane::Plane;
-use elements::sphere::Sphere;
+use accelerator::Accelerator;
+use background::Background;
+use bvh::Bounds;
+use fog::Fog;
+use group::Group;
+use hit::Hit;
 use image::{DynamicImage, GenericImage};
 use lights::directional::Directional;
+use material::Surface;
+use occlusion::{AmbientOcclusion, OcclusionMode};
+use random::Rng;
+use sidedness::Sidedness;
 use std::convert::TryInto;
 use vector::Vec3;
 
 const DO_DITHERING: bool = true;
+const MAX_RAY_DEPTH: i16 = 4;
+
+lazy_static! {
+    // Secondary rays start at least this far off the surface they leave...
+    static ref MIN_OFFSET: Number = Number::from_i16_frac(256);
+    // ...plus this much for every unit the surface is out from the origin.
+    static ref OFFSET_PER_UNIT: Number = Number::from_i16_frac(64);
+}
 
 pub type Number = fixed::Number;
 use crate::fixed::PI;
 
-#[derive(Debug)]
-pub struct Intersection<'a> {
-    distance_from_origin: Number,
-    object: &'a Box<dyn Element>,
-}
-
 pub trait Element: std::fmt::Debug {
+    // How far along the ray the element is first hit. Finding the nearest element only needs
+    // this, so it should be quick.
     fn intersect(&self, ray: &Ray) -> Option<Number>;
-    fn color(&self, hit_point: &Vec3) -> Number;
-    fn surface_normal(&self, hit_point: &Vec3) -> Vec3;
+    // Everything else about the hit `distance` along `ray`, once it's known to be the one.
+    fn hit(&self, ray: &Ray, distance: Number) -> Hit<'_>;
+    fn sidedness(&self) -> Sidedness;
+    // Whether shadow rays are stopped by it. Everything still shows up to other rays.
+    fn casts_shadows(&self) -> bool;
+    // A ray leaving a flat element can't hit it again, so rays can skip the one they start on.
+    fn is_flat(&self) -> bool;
+    // A box around everything the element covers, or None if it goes on forever.
+    fn bounds(&self) -> Option<Bounds>;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Integrator {
+    // Direct lighting plus recursive reflection and refraction.
+    Whitted,
+    // Monte Carlo global illumination, averaging `samples` jittered paths per pixel.
+    PathTraced { samples: i16 },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ToneMapping {
+    None,
+    // x / (1 + x). Keeps bright path traced pixels from swamping their neighbours when dithered.
+    Reinhard,
 }
 
 #[derive(Debug)]
 pub struct Scene {
     pub width: i16,
     pub height: i16,
-    pub elements: Vec<Box<dyn Element>>,
+    pub elements: Accelerator,
+    pub groups: Vec<Group>,
     pub lights: Vec<Directional>,
+    pub integrator: Integrator,
+    pub tone_mapping: ToneMapping,
+    // Only used by the Whitted integrator; path tracing already accounts for occlusion.
+    pub ambient_occlusion: Option<AmbientOcclusion>,
+    pub fog: Option<Fog>,
+    pub background: Background,
 }
 
 impl Scene {
     pub fn create_prime_ray(&self, pixel_x: i16, pixel_y: i16) -> Ray {
+        let mut half = Number::from(1);
+        half.do_div(&Number::from(2));
+
+        self.create_ray_through_pixel(pixel_x, pixel_y, &half, &half)
+    }
+
+    // `offset_x` and `offset_y` pick where inside the pixel the ray goes, from 0 to 1.
+    pub fn create_ray_through_pixel(
+        &self,
+        pixel_x: i16,
+        pixel_y: i16,
+        offset_x: &Number,
+        offset_y: &Number,
+    ) -> Ray {
         assert!(self.width > self.height);
 
         let scene_width = Number::from(self.width);
@@ -53,21 +143,18 @@ impl Scene {
         let one = Number::from(1);
         let two = Number::from(2);
 
-        let mut half = Number::from(1);
-        half.do_div(&two);
-
         let mut aspect_ratio = scene_width;
         aspect_ratio.do_div(&scene_height);
 
         let mut sensor_x = Number::from(pixel_x);
-        sensor_x.do_add(&half);
+        sensor_x.do_add(offset_x);
         sensor_x.do_div(&scene_width);
         sensor_x.do_mul(&two);
         sensor_x.do_sub(&one);
         sensor_x.do_mul(&aspect_ratio);
 
         let mut sensor_y = Number::from(pixel_y);
-        sensor_y.do_add(&half);
+        sensor_y.do_add(offset_y);
         sensor_y.do_div(&scene_height);
         sensor_y.do_neg();
         sensor_y.do_mul(&two);
@@ -86,85 +173,287 @@ impl Scene {
                 y: Number::from(0),
                 z: Number::from(0),
             },
-            direction: direction,
+            direction,
         }
     }
 
-    pub fn trace(&self, ray: &Ray) -> Option<Intersection> {
-        let mut intersection: Option<Intersection> = None;
-
-        for elem in &self.elements {
-            if let Some(d) = elem.intersect(ray) {
-                if !intersection
-                    .as_ref()
-                    .filter(|i| i.distance_from_origin.is_less_than(&d))
-                    .is_some()
-                {
-                    intersection = Some(Intersection {
-                        distance_from_origin: d,
-                        object: elem,
-                    })
-                }
-            }
-        }
+    // Walks into every visible group. `skip` is left out, like the flat element a ray starts on.
+    pub fn trace(&self, ray: &Ray, skip: Option<&dyn Element>) -> Option<Hit<'_>> {
+        group::nearest(&self.elements, &self.groups, ray, None, skip, None)
+    }
 
-        intersection
+    // Whether anything that casts shadows is in the way before `max_distance`. Only cares that
+    // something is, so it stops looking at the first hit rather than the nearest.
+    pub fn occluded(
+        &self,
+        ray: &Ray,
+        max_distance: Option<&Number>,
+        skip: Option<&dyn Element>,
+    ) -> bool {
+        group::occluded(&self.elements, &self.groups, ray, max_distance, skip)
     }
 }
 
 pub fn get_raw_pixel_color(scene: &Scene, x: i16, y: i16) -> Number {
+    match scene.integrator {
+        Integrator::Whitted => {
+            let ray = scene.create_prime_ray(x, y);
+            let mut rng = Rng::for_pixel(x, y);
+
+            cast_ray(scene, &ray, None, 0, &mut rng)
+        }
+        Integrator::PathTraced { samples } => {
+            // With no samples there's no light gathered to average.
+            if samples <= 0 {
+                return Number::from(0);
+            }
+
+            let mut rng = Rng::for_pixel(x, y);
+            let mut color = Number::from(0);
+
+            for _ in 0..samples {
+                let offset_x = rng.next_number();
+                let offset_y = rng.next_number();
+                let ray = scene.create_ray_through_pixel(x, y, &offset_x, &offset_y);
+
+                color.do_add(&path_tracer::trace_path(scene, &ray, &mut rng));
+            }
+
+            color.do_div(&Number::from(samples));
+            color
+        }
+    }
+}
+
+pub fn cast_ray(
+    scene: &Scene,
+    ray: &Ray,
+    skip: Option<&dyn Element>,
+    depth: i16,
+    rng: &mut Rng,
+) -> Number {
     let black = Number::from(0);
 
-    let ray = scene.create_prime_ray(x, y);
+    if depth > MAX_RAY_DEPTH {
+        return black;
+    }
+
+    let occlusion_only = matches!(
+        scene.ambient_occlusion,
+        Some(AmbientOcclusion {
+            mode: OcclusionMode::Only,
+            ..
+        })
+    );
+    // The occlusion pass is meant to be looked at on its own, so leave it unfogged.
+    let fog = if occlusion_only { None } else { scene.fog };
+
+    if let Some(hit) = scene.trace(ray, skip) {
+        let mut color = get_surface_color(scene, ray, &hit, depth, rng);
+
+        if let Some(fog) = fog {
+            fog.apply(&mut color, &hit.distance);
+        }
+
+        color
+    } else if occlusion_only {
+        // Nothing at all is in the way.
+        Number::from(1)
+    } else {
+        let mut color = scene.background.sample(&ray.direction);
+
+        if let Some(fog) = fog {
+            fog.apply_to_background(&mut color, ray);
+        }
+
+        color
+    }
+}
 
-    let intersection = scene.trace(&ray);
+fn get_surface_color(scene: &Scene, ray: &Ray, hit: &Hit, depth: i16, rng: &mut Rng) -> Number {
+    let from = Departure::leaving(hit);
 
-    if let Some(i) = intersection {
-        let mut hit_point = ray.origin;
-        let mut offset = ray.direction;
-        offset.do_scale(&i.distance_from_origin);
-        hit_point.do_add(&offset);
+    let mut color = get_diffuse_color(scene, hit, &from);
 
-        let surface_normal = i.object.surface_normal(&hit_point);
+    if let Some(ao) = scene.ambient_occlusion {
+        let visibility = ao.visibility(scene, &from, &hit.normal, rng);
 
-        let mut color = Number::from(0);
+        match ao.mode {
+            OcclusionMode::Only => return visibility,
+            OcclusionMode::ModulateDirect => color.do_mul(&visibility),
+        }
+    }
 
-        for light in &scene.lights {
-            let mut direction_to_light = light.direction;
-            direction_to_light.do_scale(&Number::from(-1));
+    match hit.surface {
+        Surface::Diffuse => color,
+        Surface::Reflective { reflectivity } => {
+            let mut diffuse_part = Number::from(1);
+            diffuse_part.do_sub(&reflectivity);
+            color.do_mul(&diffuse_part);
 
-            let mut shadow_bias = direction_to_light;
-            let mut epsilon = Number::from(1);
-            epsilon.do_div(&Number::from(20));
-            shadow_bias.do_scale(&epsilon);
+            let mut reflected = get_reflection_color(scene, ray, &from, &hit.normal, depth, rng);
+            reflected.do_mul(&reflectivity);
+            color.do_add(&reflected);
 
-            let mut origin = hit_point;
-            origin.do_add(&shadow_bias);
+            color
+        }
+        Surface::Refractive {
+            index,
+            transparency,
+        } => {
+            let mut diffuse_part = Number::from(1);
+            diffuse_part.do_sub(&transparency);
+            color.do_mul(&diffuse_part);
+
+            let mut transmitted = get_refraction_color(scene, ray, hit, &from, &index, depth, rng);
+            transmitted.do_mul(&transparency);
+            color.do_add(&transmitted);
+
+            color
+        }
+    }
+}
 
-            let shadow_ray = Ray {
-                origin: origin,
-                direction: direction_to_light,
-            };
-            let in_light = scene.trace(&shadow_ray).is_none();
+fn get_diffuse_color(scene: &Scene, hit: &Hit, from: &Departure) -> Number {
+    let mut color = Number::from(0);
 
-            if in_light {
-                let mut light_power = surface_normal.dot(&direction_to_light);
-                if light_power.is_negative() {
-                    light_power = Number::from(0);
-                }
+    for light in &scene.lights {
+        let mut direction_to_light = light.direction;
+        direction_to_light.do_scale(&Number::from(-1));
 
-                let mut added_color = light.color;
-                added_color.do_mul(&light_power);
-                added_color.do_div(&PI);
-                added_color.do_mul(&i.object.color(&hit_point));
+        let shadow_ray = from.ray(&direction_to_light);
+        let in_light = !scene.occluded(&shadow_ray, None, from.skip);
 
-                color.do_add(&added_color);
+        if in_light {
+            let mut light_power = hit.normal.dot(&direction_to_light);
+            if light_power.is_negative() {
+                light_power = Number::from(0);
             }
+
+            let mut added_color = light.color;
+            added_color.do_mul(&light_power);
+            added_color.do_div(&PI);
+            added_color.do_mul(&hit.color);
+
+            color.do_add(&added_color);
         }
+    }
 
-        color
+    color
+}
+
+fn get_reflection_color(
+    scene: &Scene,
+    ray: &Ray,
+    from: &Departure,
+    surface_normal: &Vec3,
+    depth: i16,
+    rng: &mut Rng,
+) -> Number {
+    let direction = material::reflect(&ray.direction, surface_normal);
+    cast_ray(scene, &from.ray(&direction), from.skip, depth + 1, rng)
+}
+
+fn get_refraction_color(
+    scene: &Scene,
+    ray: &Ray,
+    hit: &Hit,
+    from: &Departure,
+    index: &Number,
+    depth: i16,
+    rng: &mut Rng,
+) -> Number {
+    let outside_index = Number::from(1);
+
+    // The normal already faces the ray, so only the side that was hit says which way it's
+    // crossing.
+    let mut cos_incident = ray.direction.dot(&hit.normal);
+    cos_incident.do_neg();
+    let (from_index, to_index) = if hit.front_face {
+        (&outside_index, index)
     } else {
-        black
+        (index, &outside_index)
+    };
+
+    let reflection_color = get_reflection_color(scene, ray, from, &hit.normal, depth, rng);
+
+    match material::refract(
+        &ray.direction,
+        &hit.normal,
+        &cos_incident,
+        from_index,
+        to_index,
+    ) {
+        Some(refraction) => {
+            let refraction_ray = from.ray(&refraction.direction);
+            let mut color = cast_ray(scene, &refraction_ray, from.skip, depth + 1, rng);
+
+            let mut transmittance = Number::from(1);
+            transmittance.do_sub(&refraction.reflectance);
+            color.do_mul(&transmittance);
+
+            let mut reflected = reflection_color;
+            reflected.do_mul(&refraction.reflectance);
+            color.do_add(&reflected);
+
+            color
+        }
+        // Total internal reflection.
+        None => reflection_color,
+    }
+}
+
+// How far off a surface secondary rays start from `point`. Rounding errors grow with the size
+// of the numbers involved, so it's a little more the further out the point is.
+pub fn offset_distance(point: &Vec3) -> Number {
+    let mut largest = Number::from(0);
+    for c in [point.x, point.y, point.z].iter() {
+        let mut c = *c;
+        c.do_abs();
+        if largest.is_less_than(&c) {
+            largest = c;
+        }
+    }
+    largest.do_mul(&OFFSET_PER_UNIT);
+    largest.do_add(&MIN_OFFSET);
+    largest
+}
+
+// Where secondary rays leave a hit from. They're pushed off along the geometric normal, to
+// whichever side they're heading for, and if the element is flat they skip it altogether.
+#[derive(Clone, Copy)]
+pub struct Departure<'a> {
+    pub point: Vec3,
+    pub normal: Vec3,
+    pub skip: Option<&'a dyn Element>,
+}
+
+impl<'a> Departure<'a> {
+    pub fn leaving(hit: &Hit<'a>) -> Departure<'a> {
+        Departure {
+            point: hit.point,
+            normal: hit.geometric_normal,
+            skip: if hit.element.is_flat() {
+                Some(hit.element)
+            } else {
+                None
+            },
+        }
+    }
+
+    pub fn ray(&self, direction: &Vec3) -> Ray {
+        let mut offset = self.normal;
+        if direction.dot(&offset).is_negative() {
+            offset.do_neg();
+        }
+        offset.do_scale(&offset_distance(&self.point));
+
+        let mut origin = self.point;
+        origin.do_add(&offset);
+        Ray {
+            origin,
+            direction: *direction,
+        }
     }
 }
 
@@ -176,11 +465,21 @@ pub fn render(scene: &Scene) -> Vec<Vec<Number>> {
         row
     });
 
-    let mut dither_pixels = vec![];
-    dither_pixels.resize(scene.width.try_into().unwrap(), 0i16);
+    // Fill a linear framebuffer first so tone mapping happens before any dithering.
+    for y in 0..scene.height {
+        for x in 0..scene.width {
+            let xi: usize = x.try_into().unwrap();
+            let yi: usize = y.try_into().unwrap();
+
+            pixels[yi][xi] = get_raw_pixel_color(scene, x, y);
+        }
+
+        eprint!(".");
+    }
 
-    let mut next_dither_pixels = vec![];
-    next_dither_pixels.resize(scene.width.try_into().unwrap(), 0i16);
+    let mut dither_pixels = vec![0; scene.width.try_into().unwrap()];
+
+    let mut next_dither_pixels = vec![0; scene.width.try_into().unwrap()];
     let mut adjacent_dither = 0i16;
 
     for y in 0..scene.height {
@@ -193,12 +492,14 @@ pub fn render(scene: &Scene) -> Vec<Vec<Number>> {
         };
 
         for x in iter {
-            let color = get_raw_pixel_color(scene, x, y);
-
             let xi: usize = x.try_into().unwrap();
             let yi: usize = y.try_into().unwrap();
 
-            pixels[yi][xi].do_add(&color);
+            if let ToneMapping::Reinhard = scene.tone_mapping {
+                let mut denom = pixels[yi][xi];
+                denom.do_add(&Number::from(1));
+                pixels[yi][xi].do_div(&denom);
+            }
 
             // Poor math's gamma correction :sob: sqrt() is much easier than 1/2.2
             pixels[yi][xi].do_sqrt();
@@ -261,8 +562,6 @@ pub fn render(scene: &Scene) -> Vec<Vec<Number>> {
         for x in next_dither_pixels.iter_mut() {
             *x = 0;
         }
-
-        eprint!(".");
     }
 
     pixels
@@ -274,142 +573,18 @@ pub fn to_rgba(color: &Number) -> image::Rgba<u8> {
 }
 
 fn main() {
-    let scene = Scene {
-        width: 512,
-        height: 256,
-        elements: vec![
-            Box::new(Sphere {
-                center: Vec3 {
-                    x: Number::from(-6),
-                    y: {
-                        let mut r = Number::from(-1);
-                        r.do_div(&Number::from(2));
-                        r
-                    },
-                    z: Number::from(-5),
-                },
-                radius: {
-                    let mut r = Number::from(3);
-                    r.do_div(&Number::from(2));
-                    r
-                },
-                color: {
-                    let mut c = Number::from(8);
-                    c.do_div(&Number::from(10));
-                    c
-                },
-            }),
-            Box::new(Sphere {
-                center: Vec3 {
-                    x: Number::from(-1),
-                    y: Number::from(-1),
-                    z: Number::from(-5),
-                },
-                radius: Number::from(1),
-                color: {
-                    let mut c = Number::from(6);
-                    c.do_div(&Number::from(10));
-                    c
-                },
-            }),
-            Box::new(Sphere {
-                center: Vec3 {
-                    x: Number::from(2),
-                    y: Number::from(0),
-                    z: Number::from(-3),
-                },
-                radius: Number::from(2),
-                color: {
-                    let mut c = Number::from(10);
-                    c.do_div(&Number::from(10));
-                    c
-                },
-            }),
-            Box::new(Plane {
-                origin: Vec3 {
-                    x: Number::from(0),
-                    y: Number::from(0),
-                    z: Number::from(-25),
-                },
-                normal: Vec3 {
-                    x: Number::from(0),
-                    y: Number::from(0),
-                    z: Number::from(-1),
-                },
-                color: Number::from(1),
-                checkerboarded: false,
-            }),
-            Box::new(Plane {
-                origin: Vec3 {
-                    x: Number::from(0),
-                    y: Number::from(-2),
-                    z: Number::from(0),
-                },
-                normal: Vec3 {
-                    x: Number::from(0),
-                    y: Number::from(-1),
-                    z: Number::from(0),
-                },
-                color: Number::from(-1),
-                checkerboarded: true,
-            }),
-        ],
-        lights: vec![
-            Directional {
-                direction: {
-                    let mut v = Vec3 {
-                        x: Number::from(0),
-                        y: Number::from(-1),
-                        z: Number::from(-1),
-                    };
-                    v.do_normalize();
-                    v
-                },
-                color: {
-                    let mut c = Number::from(2);
-                    c.do_div(&Number::from(100));
-                    c
-                },
-            },
-            Directional {
-                direction: {
-                    let mut v = Vec3 {
-                        x: Number::from(-1),
-                        y: Number::from(-1),
-                        z: Number::from(0),
-                    };
-                    v.do_normalize();
-                    v
-                },
-                color: {
-                    let mut c = Number::from(90);
-                    c.do_div(&Number::from(100));
-                    c
-                },
-            },
-            Directional {
-                direction: {
-                    let mut v = Vec3 {
-                        x: {
-                            let mut v = Number::from(1);
-                            v.do_div(&Number::from(2));
-                            v
-                        },
-                        y: Number::from(-1),
-                        z: Number::from(0),
-                    };
-                    v.do_normalize();
-                    v
-                },
-                color: {
-                    let mut c = Number::from(30);
-                    c.do_div(&Number::from(100));
-                    c
-                },
-            },
-        ],
+    let mut scene = match std::env::args().nth(1) {
+        Some(name) => scenes::by_name(&name).unwrap_or_else(|| panic!("No scene named {}", name)),
+        None => scenes::spheres(),
     };
 
+    // Any further arguments name groups to hide.
+    for name in std::env::args().skip(2) {
+        group::find_mut(&mut scene.groups, &name)
+            .unwrap_or_else(|| panic!("No group named {}", name))
+            .visible = false;
+    }
+
     let pixels = render(&scene);
 
     let mut image = DynamicImage::new_rgb8(
@@ -418,13 +593,140 @@ fn main() {
     );
     for (y, row) in pixels.iter().enumerate() {
         for (x, color) in row.iter().enumerate() {
-            image.put_pixel(
-                x.try_into().unwrap(),
-                y.try_into().unwrap(),
-                to_rgba(&color),
-            );
+            image.put_pixel(x.try_into().unwrap(), y.try_into().unwrap(), to_rgba(color));
         }
     }
 
     image.save("render.png").unwrap();
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        get_raw_pixel_color, offset_distance, Departure, Element, Integrator, Scene, ToneMapping,
+    };
+    use crate::accelerator::Accelerator;
+    use crate::background::Background;
+    use crate::elements::plane::Plane;
+    use crate::elements::rectangle::Rectangle;
+    use crate::material::Surface;
+    use crate::sidedness::Sidedness;
+    use crate::vector::{vec3, Vec3};
+    use crate::Number;
+    use crate::Ray;
+    use rstest::rstest;
+
+    // 1/128 off the surface, plus 1/512 for every unit out along the furthest axis.
+    #[rstest]
+    #[case(vec3(0, 0, 0), 1.0 / 128.0)]
+    #[case(vec3(1, -2, 0), 1.0 / 128.0 + 2.0 / 512.0)]
+    #[case(vec3(0, 0, -3000), 1.0 / 128.0 + 3000.0 / 512.0)]
+    #[case(vec3(20000, 100, -300), 1.0 / 128.0 + 20000.0 / 512.0)]
+    fn test_offset_distance(#[case] point: Vec3, #[case] expected: f64) {
+        let offset = offset_distance(&point).to_f64();
+        assert!(f64::abs(offset - expected) < 0.001);
+    }
+
+    // Reflected rays start off on the side the normal points to, transmitted ones on the other.
+    #[rstest]
+    #[case(vec3(1, 1, 0), 1.0)]
+    #[case(vec3(1, -1, 0), -1.0)]
+    #[case(vec3(0, -1, 0), -1.0)]
+    fn test_departure_ray(#[case] mut direction: Vec3, #[case] side: f64) {
+        direction.do_normalize();
+        let from = Departure {
+            point: vec3(0, 0, -10),
+            normal: vec3(0, 1, 0),
+            skip: None,
+        };
+
+        let ray = from.ray(&direction);
+        let expected = side * offset_distance(&from.point).to_f64();
+        assert!(f64::abs(ray.origin.y.to_f64() - expected) < 0.001);
+        assert_eq!(ray.origin.x.to_f64(), 0.0);
+        assert_eq!(ray.origin.z.to_f64(), -10.0);
+        assert_eq!(ray.direction.y.cmp(&direction.y), 0);
+    }
+
+    fn floor_plane() -> Box<dyn Element> {
+        Box::new(Plane::new(
+            vec3(0, 0, 0),
+            vec3(0, 1, 0),
+            Number::from(1),
+            Surface::Diffuse,
+            Sidedness::Both,
+        ))
+    }
+
+    fn floor_rectangle() -> Box<dyn Element> {
+        Box::new(Rectangle::new(
+            vec3(-5, 0, -5),
+            vec3(10, 0, 0),
+            vec3(0, 0, 10),
+            Number::from(1),
+            Surface::Diffuse,
+            Sidedness::Both,
+        ))
+    }
+
+    #[rstest]
+    #[case(0)]
+    #[case(-1)]
+    fn test_no_path_samples_is_black(#[case] samples: i16) {
+        let scene = Scene {
+            width: 1,
+            height: 1,
+            elements: Accelerator::bvh(vec![floor_plane()]),
+            groups: vec![],
+            lights: vec![],
+            integrator: Integrator::PathTraced { samples },
+            tone_mapping: ToneMapping::None,
+            ambient_occlusion: None,
+            fog: None,
+            background: Background::Constant(Number::from(1)),
+        };
+
+        assert!(get_raw_pixel_color(&scene, 0, 0).is_zero());
+    }
+
+    // A shadow ray that rounding has left just under the floor it leaves would hit the floor on
+    // its way up, unless it skips it.
+    #[rstest]
+    #[case(floor_plane())]
+    #[case(floor_rectangle())]
+    fn test_shadow_ray_skips_flat_element(#[case] floor: Box<dyn Element>) {
+        let scene = Scene {
+            width: 1,
+            height: 1,
+            elements: Accelerator::bvh(vec![floor]),
+            groups: vec![],
+            lights: vec![],
+            integrator: Integrator::Whitted,
+            tone_mapping: ToneMapping::None,
+            ambient_occlusion: None,
+            fog: None,
+            background: Background::Constant(Number::from(0)),
+        };
+
+        let hit = scene
+            .trace(
+                &Ray {
+                    origin: vec3(1, 3, 1),
+                    direction: vec3(0, -1, 0),
+                },
+                None,
+            )
+            .unwrap();
+        let from = Departure::leaving(&hit);
+        assert!(std::ptr::addr_eq(from.skip.unwrap(), hit.element));
+
+        let mut origin = hit.point;
+        origin.y.do_sub(&Number::from_f64(1.0 / 1024.0));
+        let shadow_ray = Ray {
+            origin,
+            direction: vec3(0, 1, 0),
+        };
+        assert!(scene.occluded(&shadow_ray, None, None));
+        assert!(!scene.occluded(&shadow_ray, None, from.skip));
+    }
+}
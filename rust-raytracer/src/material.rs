@@ -0,0 +1,226 @@
+use crate::Number;
+use crate::Vec3;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Surface {
+    Diffuse,
+    Reflective { reflectivity: Number },
+    // Transparency blends between the diffuse color and the refracted/reflected light.
+    Refractive { index: Number, transparency: Number },
+}
+
+// Reflects `direction` about `normal`. Both should be normalized.
+pub fn reflect(direction: &Vec3, normal: &Vec3) -> Vec3 {
+    let mut offset = *normal;
+    let mut d = direction.dot(normal);
+    d.do_mul(&Number::from(2));
+    offset.do_scale(&d);
+
+    let mut r = *direction;
+    r.do_sub(&offset);
+    r
+}
+
+pub struct Refraction {
+    pub direction: Vec3,
+    pub reflectance: Number,
+}
+
+// Snell's law plus Schlick's approximation of the Fresnel term. `normal` must face against
+// `direction`, and `cos_incident` is -dot(direction, normal). Returns None on total internal
+// reflection.
+pub fn refract(
+    direction: &Vec3,
+    normal: &Vec3,
+    cos_incident: &Number,
+    from_index: &Number,
+    to_index: &Number,
+) -> Option<Refraction> {
+    let one = Number::from(1);
+
+    let mut eta = *from_index;
+    eta.do_div(to_index);
+
+    let mut eta_sq = eta;
+    eta_sq.do_mul(&eta);
+
+    let mut sin_sq = *cos_incident;
+    sin_sq.do_mul(cos_incident);
+    sin_sq.do_neg();
+    sin_sq.do_add(&one);
+
+    let mut k = eta_sq;
+    k.do_mul(&sin_sq);
+    k.do_neg();
+    k.do_add(&one);
+
+    if k.is_negative() {
+        return None;
+    }
+
+    let mut cos_transmitted = k;
+    cos_transmitted.do_sqrt();
+
+    let mut refracted = *direction;
+    refracted.do_scale(&eta);
+
+    let mut normal_scale = eta;
+    normal_scale.do_mul(cos_incident);
+    normal_scale.do_sub(&cos_transmitted);
+
+    let mut normal_offset = *normal;
+    normal_offset.do_scale(&normal_scale);
+    refracted.do_add(&normal_offset);
+    refracted.do_normalize();
+
+    // Going into a less dense medium, Schlick needs the cosine on the far side.
+    let cos = if from_index.cmp(to_index) > 0 {
+        cos_transmitted
+    } else {
+        *cos_incident
+    };
+
+    Some(Refraction {
+        direction: refracted,
+        reflectance: schlick(&cos, from_index, to_index),
+    })
+}
+
+pub fn schlick(cos: &Number, from_index: &Number, to_index: &Number) -> Number {
+    let one = Number::from(1);
+
+    let mut r0 = *from_index;
+    r0.do_sub(to_index);
+    let mut sum = *from_index;
+    sum.do_add(to_index);
+    r0.do_div(&sum);
+    let r0_copy = r0;
+    r0.do_mul(&r0_copy);
+
+    let mut x = one;
+    x.do_sub(cos);
+    if x.is_negative() {
+        x = Number::from(0);
+    }
+
+    let mut x5 = x;
+    for _ in 0..4 {
+        x5.do_mul(&x);
+    }
+
+    let mut r = one;
+    r.do_sub(&r0);
+    r.do_mul(&x5);
+    r.do_add(&r0);
+    r
+}
+
+#[cfg(test)]
+mod test {
+    use super::{reflect, refract, schlick};
+    use crate::vector::Vec3;
+    use crate::Number;
+
+    fn glass_index() -> Number {
+        let mut n = Number::from(3);
+        n.do_div(&Number::from(2));
+        n
+    }
+
+    #[test]
+    fn test_reflect() {
+        let mut direction = Vec3 {
+            x: Number::from(1),
+            y: Number::from(-1),
+            z: Number::from(0),
+        };
+        direction.do_normalize();
+
+        let normal = Vec3 {
+            x: Number::from(0),
+            y: Number::from(1),
+            z: Number::from(0),
+        };
+
+        let r = reflect(&direction, &normal);
+        assert!(f64::abs(r.x.to_f64() - direction.x.to_f64()) < 0.001);
+        assert!(f64::abs(r.y.to_f64() + direction.y.to_f64()) < 0.001);
+        assert!(r.z.is_zero());
+    }
+
+    #[test]
+    fn test_refract_head_on() {
+        let direction = Vec3 {
+            x: Number::from(0),
+            y: Number::from(0),
+            z: Number::from(-1),
+        };
+        let normal = Vec3 {
+            x: Number::from(0),
+            y: Number::from(0),
+            z: Number::from(1),
+        };
+
+        let r = refract(
+            &direction,
+            &normal,
+            &Number::from(1),
+            &Number::from(1),
+            &glass_index(),
+        )
+        .unwrap();
+
+        assert!(f64::abs(r.direction.z.to_f64() + 1.0) < 0.01);
+        // ((1 - 1.5) / (1 + 1.5))^2 = 0.04
+        assert!(f64::abs(r.reflectance.to_f64() - 0.04) < 0.001);
+    }
+
+    #[test]
+    fn test_refract_bends_towards_normal() {
+        let mut direction = Vec3 {
+            x: Number::from(1),
+            y: Number::from(0),
+            z: Number::from(-1),
+        };
+        direction.do_normalize();
+        let normal = Vec3 {
+            x: Number::from(0),
+            y: Number::from(0),
+            z: Number::from(1),
+        };
+        let mut cos = direction.dot(&normal);
+        cos.do_neg();
+
+        let r = refract(&direction, &normal, &cos, &Number::from(1), &glass_index()).unwrap();
+
+        // sin(45deg) / 1.5
+        let expected_sin = f64::sqrt(0.5) / 1.5;
+        assert!(f64::abs(r.direction.x.to_f64() - expected_sin) < 0.01);
+        assert!(r.direction.z.is_negative());
+    }
+
+    #[test]
+    fn test_total_internal_reflection() {
+        let mut direction = Vec3 {
+            x: Number::from(1),
+            y: Number::from(0),
+            z: Number::from(-1),
+        };
+        direction.do_normalize();
+        let normal = Vec3 {
+            x: Number::from(0),
+            y: Number::from(0),
+            z: Number::from(1),
+        };
+        let mut cos = direction.dot(&normal);
+        cos.do_neg();
+
+        assert!(refract(&direction, &normal, &cos, &glass_index(), &Number::from(1)).is_none());
+    }
+
+    #[test]
+    fn test_schlick_grazing() {
+        let r = schlick(&Number::from(0), &Number::from(1), &glass_index());
+        assert!(f64::abs(r.to_f64() - 1.0) < 0.001);
+    }
+}
@@ -0,0 +1,324 @@
+use crate::elements::mesh::Mesh;
+use crate::elements::triangle::Triangle;
+use crate::material::Surface;
+use crate::sidedness::Sidedness;
+use crate::Number;
+use crate::Vec3;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Wavefront OBJ import. Only vertices, normals and faces are read; everything else is skipped.
+
+#[derive(Debug, Clone, Copy)]
+pub struct Fit {
+    pub center: Vec3,
+    // Length of the model's longest side after scaling.
+    pub size: Number,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ObjOptions {
+    // Models are often far bigger or smaller than 16.16 can represent well, so they can be
+    // recentered and uniformly rescaled before converting to Number.
+    pub fit: Option<Fit>,
+    pub color: Number,
+    pub surface: Surface,
+    pub sidedness: Sidedness,
+    pub casts_shadows: bool,
+}
+
+pub fn load_obj<P: AsRef<Path>>(path: P, options: &ObjOptions) -> io::Result<Mesh> {
+    let triangles = parse_obj(&fs::read_to_string(path)?, options)?;
+
+    if triangles.is_empty() {
+        return Err(invalid("no faces"));
+    }
+
+    let mut mesh = Mesh::new(triangles, options.color, options.surface, options.sidedness);
+    mesh.casts_shadows = options.casts_shadows;
+    Ok(mesh)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn parse_coords(line_number: usize, words: &[&str]) -> io::Result<[f64; 3]> {
+    if words.len() < 3 {
+        return Err(invalid(&format!(
+            "line {}: expected 3 coordinates",
+            line_number
+        )));
+    }
+
+    let mut coords = [0f64; 3];
+    for (c, word) in coords.iter_mut().zip(words) {
+        let bad = || invalid(&format!("line {}: bad number {}", line_number, word));
+        // "nan" and "inf" parse, but there's no fixed point for them.
+        *c = word.parse().map_err(|_| bad())?;
+        if !c.is_finite() {
+            return Err(bad());
+        }
+    }
+    Ok(coords)
+}
+
+// OBJ indices are 1-based, and negative ones count back from the latest element.
+fn resolve_index(line_number: usize, word: &str, count: usize) -> io::Result<usize> {
+    let bad = || invalid(&format!("line {}: bad index {}", line_number, word));
+
+    let i: i64 = word.parse().map_err(|_| bad())?;
+    let resolved = if i < 0 { count as i64 + i } else { i - 1 };
+
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(bad());
+    }
+    Ok(resolved as usize)
+}
+
+fn to_vec3(c: &[f64; 3]) -> Vec3 {
+    Vec3 {
+        x: Number::from_f64(c[0]),
+        y: Number::from_f64(c[1]),
+        z: Number::from_f64(c[2]),
+    }
+}
+
+pub fn parse_obj(source: &str, options: &ObjOptions) -> io::Result<Vec<Triangle>> {
+    let mut positions: Vec<[f64; 3]> = vec![];
+    let mut normals: Vec<[f64; 3]> = vec![];
+    // (position, normal) index pairs for each polygon.
+    let mut faces: Vec<Vec<(usize, Option<usize>)>> = vec![];
+
+    for (i, line) in source.lines().enumerate() {
+        let line_number = i + 1;
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        match words.first() {
+            Some(&"v") => positions.push(parse_coords(line_number, &words[1..])?),
+            Some(&"vn") => {
+                let n = parse_coords(line_number, &words[1..])?;
+                let length = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+                // Zero, or so small or large it squares to zero or infinity, with no direction
+                // left to keep.
+                if length == 0.0 || !length.is_finite() {
+                    return Err(invalid(&format!(
+                        "line {}: normal can't be normalized",
+                        line_number
+                    )));
+                }
+                normals.push([n[0] / length, n[1] / length, n[2] / length]);
+            }
+            Some(&"f") => {
+                let mut face = vec![];
+                for corner in &words[1..] {
+                    // v, v/vt, v//vn or v/vt/vn.
+                    let mut parts = corner.split('/');
+                    let v = resolve_index(line_number, parts.next().unwrap(), positions.len())?;
+                    let n = match parts.nth(1) {
+                        Some(n) if !n.is_empty() => {
+                            Some(resolve_index(line_number, n, normals.len())?)
+                        }
+                        _ => None,
+                    };
+                    face.push((v, n));
+                }
+
+                if face.len() < 3 {
+                    return Err(invalid(&format!(
+                        "line {}: face needs 3 vertices",
+                        line_number
+                    )));
+                }
+                faces.push(face);
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(fit) = options.fit {
+        fit_positions(&mut positions, &fit);
+    }
+
+    let out_of_range = positions
+        .iter()
+        .flat_map(|p| p.iter())
+        .any(|c| c.abs() >= 32767.0);
+    if out_of_range {
+        return Err(invalid(
+            "model is too large for fixed point, try fitting it",
+        ));
+    }
+
+    let vertices: Vec<Vec3> = positions.iter().map(to_vec3).collect();
+    let normals: Vec<Vec3> = normals.iter().map(to_vec3).collect();
+
+    let mut triangles = vec![];
+    for face in &faces {
+        // Fan triangulation around the first corner. Fine for the convex polygons OBJ exporters
+        // produce.
+        for i in 1..face.len() - 1 {
+            let corners = [face[0], face[i], face[i + 1]];
+
+            let corner_normals = match corners {
+                [(_, Some(a)), (_, Some(b)), (_, Some(c))] => {
+                    Some([normals[a], normals[b], normals[c]])
+                }
+                _ => None,
+            };
+
+            triangles.push(Triangle {
+                vertices: [
+                    vertices[corners[0].0],
+                    vertices[corners[1].0],
+                    vertices[corners[2].0],
+                ],
+                normals: corner_normals,
+                color: options.color,
+                surface: options.surface,
+                sidedness: options.sidedness,
+                casts_shadows: true,
+            });
+        }
+    }
+
+    Ok(triangles)
+}
+
+fn fit_positions(positions: &mut [[f64; 3]], fit: &Fit) {
+    if positions.is_empty() {
+        return;
+    }
+
+    let mut min = positions[0];
+    let mut max = positions[0];
+    for p in positions.iter() {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(p[axis]);
+            max[axis] = max[axis].max(p[axis]);
+        }
+    }
+
+    let longest = (0..3).map(|a| max[a] - min[a]).fold(0f64, f64::max);
+    let scale = if longest > 0.0 {
+        fit.size.to_f64() / longest
+    } else {
+        1.0
+    };
+    let center = [fit.center.x, fit.center.y, fit.center.z];
+
+    for p in positions.iter_mut() {
+        for axis in 0..3 {
+            let middle = (min[axis] + max[axis]) / 2.0;
+            p[axis] = (p[axis] - middle) * scale + center[axis].to_f64();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_obj, Fit, ObjOptions};
+    use crate::material::Surface;
+    use crate::sidedness::Sidedness;
+    use crate::vector::Vec3;
+    use crate::Number;
+    use rstest::rstest;
+
+    fn options(fit: Option<Fit>) -> ObjOptions {
+        ObjOptions {
+            fit,
+            color: Number::from(1),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }
+    }
+
+    #[test]
+    fn test_fan_triangulation() {
+        let source = "
+            # A unit square as one quad, plus a triangle using negative indices.
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            f 1 2 3 4
+            f -4 -3 -1
+        ";
+
+        let triangles = parse_obj(source, &options(None)).unwrap();
+        assert_eq!(triangles.len(), 3);
+        assert_eq!(triangles[1].vertices[1].x.to_f64(), 1.0);
+        assert_eq!(triangles[1].vertices[1].y.to_f64(), 1.0);
+        assert_eq!(triangles[2].vertices[2].y.to_f64(), 1.0);
+    }
+
+    #[test]
+    fn test_normals() {
+        let source = "
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            vn 0 0 2
+            vt 0 0
+            f 1/1/1 2/1/1 3/1/1
+            f 1//1 2//1 3
+        ";
+
+        let triangles = parse_obj(source, &options(None)).unwrap();
+        let normals = triangles[0].normals.unwrap();
+        assert_eq!(normals[2].z.to_f64(), 1.0);
+        assert!(triangles[1].normals.is_none());
+    }
+
+    #[test]
+    fn test_fit() {
+        let source = "
+            v 100000 0 0
+            v 300000 0 0
+            v 100000 50000 0
+            f 1 2 3
+        ";
+
+        assert!(parse_obj(source, &options(None)).is_err());
+
+        let fit = Fit {
+            center: Vec3 {
+                x: Number::from(0),
+                y: Number::from(0),
+                z: Number::from(-5),
+            },
+            size: Number::from(4),
+        };
+        let triangles = parse_obj(source, &options(Some(fit))).unwrap();
+        let v = triangles[0].vertices;
+        assert_eq!(v[0].x.to_f64(), -2.0);
+        assert_eq!(v[1].x.to_f64(), 2.0);
+        assert_eq!(v[2].y.to_f64(), 0.5);
+        assert_eq!(v[0].z.to_f64(), -5.0);
+    }
+
+    #[test]
+    fn test_bad_index() {
+        let source = "
+            v 0 0 0
+            f 1 2 3
+        ";
+
+        assert!(parse_obj(source, &options(None)).is_err());
+    }
+
+    // Numbers that parse as floats but have no fixed point, and normals with no direction.
+    #[rstest]
+    #[case("v nan 0 0")]
+    #[case("v 0 inf 0")]
+    #[case("v 0 0 -inf")]
+    #[case("vn 0 0 0")]
+    #[case("vn 0 1e-200 0")]
+    #[case("vn 1e200 0 0")]
+    fn test_bad_coordinates(#[case] line: &str) {
+        let source = format!("v 0 0 0\nv 1 0 0\nv 0 1 0\n{}\nf 1 2 3\n", line);
+        assert!(parse_obj(&source, &options(None)).is_err());
+    }
+}
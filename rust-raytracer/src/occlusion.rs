@@ -0,0 +1,176 @@
+use crate::path_tracer::sample_cosine_hemisphere;
+use crate::random::Rng;
+use crate::{Departure, Number, Scene, Vec3};
+
+#[derive(Debug, Clone, Copy)]
+pub enum OcclusionMode {
+    // Render nothing but the occlusion term, white where fully open.
+    Only,
+    // Scale direct lighting by the occlusion term.
+    ModulateDirect,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AmbientOcclusion {
+    pub samples: i16,
+    // How far away geometry can be and still count as occluding.
+    pub distance: Number,
+    pub mode: OcclusionMode,
+}
+
+impl AmbientOcclusion {
+    // Fraction of cosine-weighted hemisphere rays that escape within `distance`. With no samples
+    // nothing is found in the way, so everything is fully open.
+    pub fn visibility(
+        &self,
+        scene: &Scene,
+        from: &Departure,
+        surface_normal: &Vec3,
+        rng: &mut Rng,
+    ) -> Number {
+        if self.samples <= 0 {
+            return Number::from(1);
+        }
+
+        let mut unoccluded = 0i16;
+
+        for _ in 0..self.samples {
+            let ray = from.ray(&sample_cosine_hemisphere(surface_normal, rng));
+
+            if !scene.occluded(&ray, Some(&self.distance), from.skip) {
+                unoccluded += 1;
+            }
+        }
+
+        let mut v = Number::from(unoccluded);
+        v.do_div(&Number::from(self.samples));
+        v
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AmbientOcclusion, OcclusionMode};
+    use crate::accelerator::Accelerator;
+    use crate::background::Background;
+    use crate::elements::plane::Plane;
+    use crate::material::Surface;
+    use crate::random::Rng;
+    use crate::sidedness::Sidedness;
+    use crate::vector::Vec3;
+    use crate::{Departure, Integrator, Number, Scene, ToneMapping};
+
+    fn floor_and_wall() -> Scene {
+        Scene {
+            width: 2,
+            height: 1,
+            elements: Accelerator::bvh(vec![
+                Box::new(Plane::new(
+                    Vec3 {
+                        x: Number::from(0),
+                        y: Number::from(0),
+                        z: Number::from(0),
+                    },
+                    Vec3 {
+                        x: Number::from(0),
+                        y: Number::from(-1),
+                        z: Number::from(0),
+                    },
+                    Number::from(1),
+                    Surface::Diffuse,
+                    Sidedness::Both,
+                )),
+                Box::new(Plane::new(
+                    Vec3 {
+                        x: Number::from(0),
+                        y: Number::from(0),
+                        z: Number::from(0),
+                    },
+                    Vec3 {
+                        x: Number::from(1),
+                        y: Number::from(0),
+                        z: Number::from(0),
+                    },
+                    Number::from(1),
+                    Surface::Diffuse,
+                    Sidedness::Both,
+                )),
+            ]),
+            groups: vec![],
+            lights: vec![],
+            integrator: Integrator::Whitted,
+            tone_mapping: ToneMapping::None,
+            ambient_occlusion: None,
+            fog: None,
+            background: Background::Constant(Number::from(0)),
+        }
+    }
+
+    #[test]
+    fn test_corner_is_darker_than_open_floor() {
+        let scene = floor_and_wall();
+        let ao = AmbientOcclusion {
+            samples: 64,
+            distance: Number::from(2),
+            mode: OcclusionMode::Only,
+        };
+        let up = Vec3 {
+            x: Number::from(0),
+            y: Number::from(1),
+            z: Number::from(0),
+        };
+        let mut rng = Rng::new(1);
+
+        let corner = Vec3 {
+            x: {
+                let mut x = Number::from(-1);
+                x.do_div(&Number::from(10));
+                x
+            },
+            y: Number::from(0),
+            z: Number::from(0),
+        };
+        let open = Vec3 {
+            x: Number::from(-10),
+            y: Number::from(0),
+            z: Number::from(0),
+        };
+
+        let leaving = |point: Vec3| Departure {
+            point,
+            normal: up,
+            skip: None,
+        };
+        let corner_visibility = ao.visibility(&scene, &leaving(corner), &up, &mut rng);
+        let open_visibility = ao.visibility(&scene, &leaving(open), &up, &mut rng);
+
+        assert_eq!(open_visibility.to_f64(), 1.0);
+        assert!(corner_visibility.to_f64() < 0.8);
+    }
+
+    #[test]
+    fn test_no_samples_is_fully_open() {
+        let ao = AmbientOcclusion {
+            samples: 0,
+            distance: Number::from(2),
+            mode: OcclusionMode::Only,
+        };
+        let up = Vec3 {
+            x: Number::from(0),
+            y: Number::from(1),
+            z: Number::from(0),
+        };
+        let from = Departure {
+            point: Vec3 {
+                x: Number::from(-1),
+                y: Number::from(0),
+                z: Number::from(0),
+            },
+            normal: up,
+            skip: None,
+        };
+
+        let visibility = ao.visibility(&floor_and_wall(), &from, &up, &mut Rng::new(1));
+        assert_eq!(visibility.to_f64(), 1.0);
+    }
+}
@@ -0,0 +1,200 @@
+use crate::hit::Hit;
+use crate::material;
+use crate::material::Surface;
+use crate::random::Rng;
+use crate::{get_diffuse_color, Departure};
+use crate::{Number, Ray, Scene, Vec3};
+
+const MAX_PATH_BOUNCES: i16 = 8;
+// Bounces that always happen before Russian roulette can end a path.
+const MIN_PATH_BOUNCES: i16 = 2;
+
+// Unbiased diffuse path tracing. Directional lights can't be hit by a random bounce, so each
+// vertex samples them directly (next event estimation), and the bounce carries indirect light.
+pub fn trace_path(scene: &Scene, ray: &Ray, rng: &mut Rng) -> Number {
+    let mut radiance = Number::from(0);
+    let mut throughput = Number::from(1);
+    let mut ray = *ray;
+    let mut skip = None;
+
+    for bounce in 0..MAX_PATH_BOUNCES {
+        let hit = match scene.trace(&ray, skip) {
+            Some(hit) => hit,
+            None => {
+                // The background acts as a light source surrounding the scene.
+                let mut escaped = scene.background.sample(&ray.direction);
+                if let Some(fog) = scene.fog {
+                    fog.apply_to_background(&mut escaped, &ray);
+                }
+                escaped.do_mul(&throughput);
+                radiance.do_add(&escaped);
+                break;
+            }
+        };
+
+        // Fog in front of the hit glows with its own color and hides what's behind it.
+        if let Some(fog) = scene.fog {
+            let transmittance = fog.transmittance(&hit.distance);
+
+            let mut fogged = Number::from(1);
+            fogged.do_sub(&transmittance);
+            fogged.do_mul(&fog.color);
+            fogged.do_mul(&throughput);
+            radiance.do_add(&fogged);
+
+            throughput.do_mul(&transmittance);
+        }
+
+        let from = Departure::leaving(&hit);
+
+        let direction = match next_specular_direction(&hit, &ray, rng) {
+            Some(direction) => direction,
+            None => {
+                let mut direct = get_diffuse_color(scene, &hit, &from);
+                direct.do_mul(&throughput);
+                radiance.do_add(&direct);
+
+                // With cosine-weighted sampling, the cosine and the 1/PI of the BRDF cancel with
+                // the PDF, leaving just the albedo.
+                throughput.do_mul(&hit.color);
+
+                sample_cosine_hemisphere(&hit.normal, rng)
+            }
+        };
+
+        if bounce >= MIN_PATH_BOUNCES {
+            let mut survival = throughput;
+            let mut max_survival = Number::from(95);
+            max_survival.do_div(&Number::from(100));
+            if survival.cmp(&max_survival) > 0 {
+                survival = max_survival;
+            }
+
+            if !rng.next_number().is_less_than(&survival) {
+                break;
+            }
+
+            throughput.do_div(&survival);
+        }
+
+        ray = from.ray(&direction);
+        skip = from.skip;
+    }
+
+    radiance
+}
+
+// Picks between the specular and diffuse parts of a surface at random, in proportion to how much
+// each contributes in the Whitted integrator. None means the diffuse part was chosen.
+fn next_specular_direction(hit: &Hit, ray: &Ray, rng: &mut Rng) -> Option<Vec3> {
+    match hit.surface {
+        Surface::Diffuse => None,
+        Surface::Reflective { reflectivity } => {
+            if rng.next_number().is_less_than(&reflectivity) {
+                Some(material::reflect(&ray.direction, &hit.normal))
+            } else {
+                None
+            }
+        }
+        Surface::Refractive {
+            index,
+            transparency,
+        } => {
+            if !rng.next_number().is_less_than(&transparency) {
+                return None;
+            }
+
+            let outside_index = Number::from(1);
+
+            let mut cos_incident = ray.direction.dot(&hit.normal);
+            cos_incident.do_neg();
+            let (from_index, to_index) = if hit.front_face {
+                (&outside_index, &index)
+            } else {
+                (&index, &outside_index)
+            };
+
+            match material::refract(
+                &ray.direction,
+                &hit.normal,
+                &cos_incident,
+                from_index,
+                to_index,
+            ) {
+                Some(refraction) if !rng.next_number().is_less_than(&refraction.reflectance) => {
+                    Some(refraction.direction)
+                }
+                _ => Some(material::reflect(&ray.direction, &hit.normal)),
+            }
+        }
+    }
+}
+
+// Malley's method: pick a uniform point on the unit disk and project it up onto the hemisphere.
+// Rejection sampling avoids needing sin and cos.
+pub fn sample_cosine_hemisphere(normal: &Vec3, rng: &mut Rng) -> Vec3 {
+    let one = Number::from(1);
+
+    let (disk_x, disk_y, disk_dist_sq) = loop {
+        let x = rng.next_signed_number();
+        let y = rng.next_signed_number();
+
+        let mut dist_sq = x;
+        dist_sq.do_mul(&x);
+        let mut y_sq = y;
+        y_sq.do_mul(&y);
+        dist_sq.do_add(&y_sq);
+
+        if dist_sq.is_less_than(&one) {
+            break (x, y, dist_sq);
+        }
+    };
+
+    let mut height = one;
+    height.do_sub(&disk_dist_sq);
+    height.do_sqrt();
+
+    let (mut tangent, mut bitangent) = normal.orthonormal_basis();
+    tangent.do_scale(&disk_x);
+    bitangent.do_scale(&disk_y);
+
+    let mut direction = *normal;
+    direction.do_scale(&height);
+    direction.do_add(&tangent);
+    direction.do_add(&bitangent);
+    direction.do_normalize();
+    direction
+}
+
+#[cfg(test)]
+mod test {
+    use super::sample_cosine_hemisphere;
+    use crate::random::Rng;
+    use crate::vector::Vec3;
+    use crate::Number;
+
+    #[test]
+    fn test_cosine_hemisphere_samples() {
+        let mut normal = Vec3 {
+            x: Number::from(1),
+            y: Number::from(2),
+            z: Number::from(-1),
+        };
+        normal.do_normalize();
+
+        let mut rng = Rng::new(42);
+        let mut cos_sum = 0f64;
+
+        for _ in 0..500 {
+            let d = sample_cosine_hemisphere(&normal, &mut rng);
+            let cos = d.dot(&normal).to_f64();
+
+            assert!(f64::abs(d.dist_sq().to_f64() - 1.0) < 0.02);
+            assert!(cos >= -0.001);
+            cos_sum += cos;
+        }
+
+        // E[cos] under a cosine-weighted distribution is 2/3.
+        assert!(f64::abs(cos_sum / 500.0 - 2.0 / 3.0) < 0.05);
+    }
+}
@@ -0,0 +1,107 @@
+use crate::Number;
+
+// 16-bit xorshift generator with the (7, 9, 8) triple. Jack has no xor or shift operators, so
+// they're built out of the operations it does have.
+#[derive(Debug, Clone, Copy)]
+pub struct Rng {
+    state: i16,
+}
+
+const POWERS_OF_TWO: [i16; 15] = [
+    1, 2, 4, 8, 16, 32, 64, 128, 256, 512, 1024, 2048, 4096, 8192, 16384,
+];
+
+fn xor(a: i16, b: i16) -> i16 {
+    (a | b) & !(a & b)
+}
+
+fn shift_left(x: i16, n: usize) -> i16 {
+    // Multiplication wraps just like Jack's does.
+    x.wrapping_mul(POWERS_OF_TWO[n])
+}
+
+fn shift_right_logical(x: i16, n: usize) -> i16 {
+    if x >= 0 {
+        x / POWERS_OF_TWO[n]
+    } else {
+        // Shift the sign bit down separately so it isn't smeared.
+        ((x & 0x7FFF) / POWERS_OF_TWO[n]) | POWERS_OF_TWO[15 - n]
+    }
+}
+
+impl Rng {
+    pub fn new(seed: i16) -> Rng {
+        // Zero is the one state xorshift never leaves.
+        let state = if seed == 0 { 1 } else { seed };
+        let mut rng = Rng { state };
+
+        // The first few outputs are closely related to the seed.
+        for _ in 0..4 {
+            rng.next_i16();
+        }
+
+        rng
+    }
+
+    pub fn for_pixel(x: i16, y: i16) -> Rng {
+        Rng::new(xor(x.wrapping_mul(7919), y.wrapping_mul(-31153)))
+    }
+
+    pub fn next_i16(&mut self) -> i16 {
+        let mut s = self.state;
+        s = xor(s, shift_left(s, 7));
+        s = xor(s, shift_right_logical(s, 9));
+        s = xor(s, shift_left(s, 8));
+        self.state = s;
+        s
+    }
+
+    // Uniform in [0, 1).
+    pub fn next_number(&mut self) -> Number {
+        Number::from_i16_frac(self.next_i16() & 0x7FFF)
+    }
+
+    // Uniform in [-1, 1).
+    pub fn next_signed_number(&mut self) -> Number {
+        Number::from_i16_frac(self.next_i16())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{shift_right_logical, Rng};
+
+    #[test]
+    fn test_shift_right_logical() {
+        assert_eq!(shift_right_logical(0x4000, 9), 0x4000 >> 9);
+        assert_eq!(shift_right_logical(-1, 9), (0xFFFFu16 >> 9) as i16);
+        assert_eq!(shift_right_logical(-32768, 1), 0x4000);
+    }
+
+    #[test]
+    fn test_matches_xorshift() {
+        let mut rng = Rng { state: 1 };
+        let mut expected: u16 = 1;
+
+        for _ in 0..1000 {
+            expected ^= expected << 7;
+            expected ^= expected >> 9;
+            expected ^= expected << 8;
+            assert_eq!(rng.next_i16(), expected as i16);
+        }
+    }
+
+    #[test]
+    fn test_next_number_range() {
+        let mut rng = Rng::for_pixel(3, 7);
+        let mut sum = 0f64;
+
+        for _ in 0..1000 {
+            let n = rng.next_number().to_f64();
+            assert!((0.0..1.0).contains(&n));
+            sum += n;
+        }
+
+        assert!(f64::abs(sum / 1000.0 - 0.5) < 0.05);
+    }
+}
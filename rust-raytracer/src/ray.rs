@@ -1,3 +1,4 @@
+use crate::Number;
 use crate::Vec3;
 use std::fmt;
 
@@ -12,3 +13,13 @@ impl fmt::Display for Ray {
         write!(f, "Ray({} -> {})", self.origin, self.direction)
     }
 }
+
+impl Ray {
+    // The point `distance` along the ray.
+    pub fn at(&self, distance: &Number) -> Vec3 {
+        let mut point = self.direction;
+        point.do_scale(distance);
+        point.do_add(&self.origin);
+        point
+    }
+}
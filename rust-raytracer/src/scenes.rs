@@ -0,0 +1,1436 @@
+use crate::accelerator::Accelerator;
+use crate::background::Background;
+use crate::elements::boxes::{AlignedBox, OrientedBox};
+use crate::elements::capsule::Capsule;
+use crate::elements::cone::Cone;
+use crate::elements::csg::{CsgDifference, CsgIntersection, CsgUnion};
+use crate::elements::cylinder::Cylinder;
+use crate::elements::disc::Disc;
+use crate::elements::heightfield::{Heightfield, HeightfieldOptions};
+use crate::elements::metaballs::{Ball, Metaballs};
+use crate::elements::plane::Plane;
+use crate::elements::polygon::Polygon;
+use crate::elements::rectangle::Rectangle;
+use crate::elements::sdf::{Sdf, Shape};
+use crate::elements::sphere::Sphere;
+use crate::elements::textured::Textured;
+use crate::elements::torus::Torus;
+use crate::elements::transformed::Transformed;
+use crate::elements::triangle::Triangle;
+use crate::environment::EnvironmentMap;
+use crate::fixed::PI;
+use crate::fog::{Fog, FogFalloff};
+use crate::group::Group;
+use crate::lights::directional::Directional;
+use crate::material::Surface;
+use crate::obj::{load_obj, Fit, ObjOptions};
+use crate::occlusion::{AmbientOcclusion, OcclusionMode};
+use crate::sidedness::Sidedness;
+use crate::texture::{Checker, Checker3d, Gradient, Grid, Rings, SolidColor, Stripes, Texture};
+use crate::transform::{Mat4, Transform};
+use crate::vector::{vec3, Vec3};
+use crate::{Element, Integrator, Number, Scene, ToneMapping};
+use std::rc::Rc;
+
+// Picks a scene by the name given on the command line.
+pub fn by_name(name: &str) -> Option<Scene> {
+    match name {
+        "spheres" => Some(spheres()),
+        "triangles" => Some(triangles()),
+        "mesh" => Some(mesh()),
+        "boxes" => Some(boxes()),
+        "occlusion" => Some(occlusion(OcclusionMode::Only)),
+        "occlusion_lit" => Some(occlusion(OcclusionMode::ModulateDirect)),
+        "shapes" => Some(shapes()),
+        "torus" => Some(torus()),
+        "planar" => Some(planar()),
+        "csg" => Some(csg()),
+        "sdf" => Some(sdf()),
+        "metaballs" => Some(metaballs()),
+        "terrain" => Some(terrain()),
+        "instances" => Some(instances()),
+        "groups" => Some(groups()),
+        "crowd" => Some(crowd()),
+        "textures" => Some(textures()),
+        "room" => Some(room()),
+        "environment" => Some(environment()),
+        _ => None,
+    }
+}
+
+fn ratio(numerator: i16, denominator: i16) -> Number {
+    let mut n = Number::from(numerator);
+    n.do_div(&Number::from(denominator));
+    n
+}
+
+fn default_lights() -> Vec<Directional> {
+    vec![
+        Directional {
+            direction: {
+                let mut v = Vec3 {
+                    x: Number::from(0),
+                    y: Number::from(-1),
+                    z: Number::from(-1),
+                };
+                v.do_normalize();
+                v
+            },
+            color: {
+                let mut c = Number::from(2);
+                c.do_div(&Number::from(100));
+                c
+            },
+        },
+        Directional {
+            direction: {
+                let mut v = Vec3 {
+                    x: Number::from(-1),
+                    y: Number::from(-1),
+                    z: Number::from(0),
+                };
+                v.do_normalize();
+                v
+            },
+            color: {
+                let mut c = Number::from(90);
+                c.do_div(&Number::from(100));
+                c
+            },
+        },
+        Directional {
+            direction: {
+                let mut v = Vec3 {
+                    x: {
+                        let mut v = Number::from(1);
+                        v.do_div(&Number::from(2));
+                        v
+                    },
+                    y: Number::from(-1),
+                    z: Number::from(0),
+                };
+                v.do_normalize();
+                v
+            },
+            color: {
+                let mut c = Number::from(30);
+                c.do_div(&Number::from(100));
+                c
+            },
+        },
+    ]
+}
+
+// Light and dark unit squares.
+fn checkerboard() -> Rc<dyn Texture> {
+    Rc::new(Checker {
+        scale: Number::from(1),
+        colors: [ratio(90, 100), ratio(3, 100)],
+    })
+}
+
+fn checkerboard_floor() -> Textured<Plane> {
+    Textured::new(
+        Plane::new(
+            vec3(0, -2, 0),
+            vec3(0, -1, 0),
+            Number::from(0),
+            Surface::Diffuse,
+            // Only ever seen from above.
+            Sidedness::Front,
+        ),
+        checkerboard(),
+    )
+}
+
+pub fn spheres() -> Scene {
+    Scene {
+        width: 512,
+        height: 256,
+        elements: Accelerator::bvh(vec![
+            Box::new(Sphere {
+                center: Vec3 {
+                    x: Number::from(-6),
+                    y: {
+                        let mut r = Number::from(-1);
+                        r.do_div(&Number::from(2));
+                        r
+                    },
+                    z: Number::from(-5),
+                },
+                radius: {
+                    let mut r = Number::from(3);
+                    r.do_div(&Number::from(2));
+                    r
+                },
+                color: {
+                    let mut c = Number::from(8);
+                    c.do_div(&Number::from(10));
+                    c
+                },
+                surface: Surface::Diffuse,
+                sidedness: Sidedness::Both,
+                casts_shadows: true,
+            }),
+            Box::new(Sphere {
+                center: Vec3 {
+                    x: Number::from(-1),
+                    y: Number::from(-1),
+                    z: Number::from(-5),
+                },
+                radius: Number::from(1),
+                color: {
+                    let mut c = Number::from(6);
+                    c.do_div(&Number::from(10));
+                    c
+                },
+                surface: Surface::Refractive {
+                    index: {
+                        let mut n = Number::from(3);
+                        n.do_div(&Number::from(2));
+                        n
+                    },
+                    transparency: {
+                        let mut t = Number::from(9);
+                        t.do_div(&Number::from(10));
+                        t
+                    },
+                },
+                sidedness: Sidedness::Both,
+                casts_shadows: true,
+            }),
+            Box::new(Sphere {
+                center: Vec3 {
+                    x: Number::from(2),
+                    y: Number::from(0),
+                    z: Number::from(-3),
+                },
+                radius: Number::from(2),
+                color: {
+                    let mut c = Number::from(10);
+                    c.do_div(&Number::from(10));
+                    c
+                },
+                surface: Surface::Diffuse,
+                sidedness: Sidedness::Both,
+                casts_shadows: true,
+            }),
+            Box::new(Plane::new(
+                Vec3 {
+                    x: Number::from(0),
+                    y: Number::from(0),
+                    z: Number::from(-25),
+                },
+                Vec3 {
+                    x: Number::from(0),
+                    y: Number::from(0),
+                    z: Number::from(-1),
+                },
+                Number::from(1),
+                Surface::Diffuse,
+                Sidedness::Both,
+            )),
+            Box::new(Textured::new(
+                Plane::new(
+                    Vec3 {
+                        x: Number::from(0),
+                        y: Number::from(-2),
+                        z: Number::from(0),
+                    },
+                    Vec3 {
+                        x: Number::from(0),
+                        y: Number::from(-1),
+                        z: Number::from(0),
+                    },
+                    Number::from(0),
+                    Surface::Diffuse,
+                    Sidedness::Both,
+                ),
+                checkerboard(),
+            )),
+        ]),
+        groups: vec![],
+        lights: default_lights(),
+        integrator: Integrator::Whitted,
+        tone_mapping: ToneMapping::None,
+        ambient_occlusion: None,
+        fog: Some(Fog {
+            falloff: FogFalloff::Exponential {
+                density: {
+                    let mut d = Number::from(2);
+                    d.do_div(&Number::from(100));
+                    d
+                },
+            },
+            color: {
+                let mut c = Number::from(1);
+                c.do_div(&Number::from(10));
+                c
+            },
+            ceiling: Number::from(20),
+        }),
+        // Anything that gets past the wall and the floor is heading level or down, so never climbs
+        // out of the fog and only ever sees the fog color.
+        background: Background::Constant(ratio(1, 10)),
+    }
+}
+
+pub fn triangles() -> Scene {
+    // A square pyramid, flat shaded, next to a fan with smoothed normals.
+    let apex = vec3(-2, 1, -6);
+    let base = [
+        vec3(-4, -2, -4),
+        vec3(0, -2, -4),
+        vec3(0, -2, -8),
+        vec3(-4, -2, -8),
+    ];
+    let mut elements: Vec<Box<dyn Element>> = vec![];
+    for i in 0..4 {
+        elements.push(Box::new(Triangle {
+            vertices: [base[i], base[(i + 1) % 4], apex],
+            normals: None,
+            color: ratio(8, 10),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }));
+    }
+
+    let center = vec3(3, 0, -6);
+    let rim = [
+        vec3(1, -2, -5),
+        vec3(5, -2, -5),
+        vec3(5, 2, -7),
+        vec3(1, 2, -7),
+    ];
+    let mut center_normal = vec3(0, 1, 2);
+    center_normal.do_normalize();
+    for i in 0..4 {
+        let mut rim_normals = [rim[i], rim[(i + 1) % 4]];
+        for n in rim_normals.iter_mut() {
+            n.do_sub(&center);
+            n.do_normalize();
+            n.do_add(&center_normal);
+            n.do_normalize();
+        }
+        elements.push(Box::new(Triangle {
+            vertices: [rim[i], rim[(i + 1) % 4], center],
+            normals: Some([rim_normals[0], rim_normals[1], center_normal]),
+            color: ratio(6, 10),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }));
+    }
+
+    elements.push(Box::new(checkerboard_floor()));
+
+    Scene {
+        width: 512,
+        height: 256,
+        elements: Accelerator::bvh(elements),
+        groups: vec![],
+        lights: default_lights(),
+        integrator: Integrator::Whitted,
+        tone_mapping: ToneMapping::None,
+        ambient_occlusion: None,
+        fog: None,
+        background: Background::Constant(Number::from(0)),
+    }
+}
+
+// An icosahedron loaded from models/, so run it from the crate directory.
+pub fn mesh() -> Scene {
+    let options = ObjOptions {
+        fit: Some(Fit {
+            center: vec3(0, -1, -4),
+            size: Number::from(2),
+        }),
+        color: ratio(9, 10),
+        surface: Surface::Reflective {
+            reflectivity: ratio(2, 10),
+        },
+        sidedness: Sidedness::Both,
+        casts_shadows: true,
+    };
+    let model = load_obj("models/icosahedron.obj", &options)
+        .unwrap_or_else(|e| panic!("Couldn't load models/icosahedron.obj: {}", e));
+
+    Scene {
+        width: 512,
+        height: 256,
+        elements: Accelerator::bvh(vec![Box::new(model), Box::new(checkerboard_floor())]),
+        groups: vec![],
+        lights: default_lights(),
+        integrator: Integrator::Whitted,
+        tone_mapping: ToneMapping::None,
+        ambient_occlusion: None,
+        fog: None,
+        background: Background::Gradient {
+            bottom: Number::from(0),
+            top: ratio(1, 2),
+        },
+    }
+}
+
+// A row of pillars and a tilted crate. The faces are shaded by color rather than just lighting.
+pub fn boxes() -> Scene {
+    let sides = [
+        ratio(5, 10),
+        ratio(5, 10),
+        ratio(2, 10),
+        ratio(9, 10),
+        ratio(7, 10),
+        ratio(7, 10),
+    ];
+
+    let mut elements: Vec<Box<dyn Element>> = vec![];
+    for i in 0..4 {
+        let x = -6 + i * 4;
+        elements.push(Box::new(AlignedBox {
+            min: vec3(x - 1, -2, -10),
+            max: vec3(x, 3, -9),
+            face_colors: sides,
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }));
+    }
+
+    elements.push(Box::new(OrientedBox::new(
+        vec3(0, -1, -5),
+        vec3(1, 1, 1),
+        &vec3(1, 0, -2),
+        &vec3(0, 1, 0),
+        [ratio(8, 10); 6],
+        Surface::Reflective {
+            reflectivity: ratio(3, 10),
+        },
+        Sidedness::Both,
+    )));
+    elements.push(Box::new(checkerboard_floor()));
+
+    Scene {
+        width: 512,
+        height: 256,
+        elements: Accelerator::bvh(elements),
+        groups: vec![],
+        lights: default_lights(),
+        integrator: Integrator::Whitted,
+        tone_mapping: ToneMapping::None,
+        ambient_occlusion: None,
+        fog: None,
+        background: Background::Gradient {
+            bottom: Number::from(0),
+            top: ratio(1, 2),
+        },
+    }
+}
+
+// The boxes again, with ambient occlusion darkening the floor round the pillars' feet and under
+// the crate. `Only` shows the occlusion on its own.
+pub fn occlusion(mode: OcclusionMode) -> Scene {
+    Scene {
+        ambient_occlusion: Some(AmbientOcclusion {
+            samples: 16,
+            distance: Number::from(2),
+            mode,
+        }),
+        ..boxes()
+    }
+}
+
+// A cylinder, a cone and a lying-down capsule.
+pub fn shapes() -> Scene {
+    let up = vec3(0, 1, 0);
+
+    Scene {
+        width: 512,
+        height: 256,
+        elements: Accelerator::bvh(vec![
+            Box::new(Cylinder {
+                base: vec3(-4, -2, -7),
+                axis: up,
+                height: Number::from(3),
+                radius: Number::from(1),
+                color: ratio(8, 10),
+                surface: Surface::Diffuse,
+                sidedness: Sidedness::Both,
+                casts_shadows: true,
+            }),
+            Box::new(Cone {
+                base: vec3(0, -2, -8),
+                axis: up,
+                height: Number::from(4),
+                base_radius: ratio(3, 2),
+                top_radius: Number::from(0),
+                color: ratio(8, 10),
+                surface: Surface::Diffuse,
+                sidedness: Sidedness::Both,
+                casts_shadows: true,
+            }),
+            Box::new(Capsule {
+                start: vec3(2, -1, -5),
+                end: vec3(5, -1, -7),
+                radius: ratio(3, 4),
+                color: ratio(9, 10),
+                surface: Surface::Reflective {
+                    reflectivity: ratio(3, 10),
+                },
+                sidedness: Sidedness::Both,
+                casts_shadows: true,
+            }),
+            Box::new(checkerboard_floor()),
+        ]),
+        groups: vec![],
+        lights: default_lights(),
+        integrator: Integrator::Whitted,
+        tone_mapping: ToneMapping::None,
+        ambient_occlusion: None,
+        fog: None,
+        background: Background::Gradient {
+            bottom: Number::from(0),
+            top: ratio(1, 2),
+        },
+    }
+}
+
+// A tilted ring, with a sphere through its hole.
+pub fn torus() -> Scene {
+    let mut axis = vec3(0, 2, 1);
+    axis.do_normalize();
+
+    Scene {
+        width: 512,
+        height: 256,
+        elements: Accelerator::bvh(vec![
+            Box::new(Torus {
+                center: vec3(0, 0, -7),
+                axis,
+                major_radius: ratio(5, 2),
+                minor_radius: ratio(3, 4),
+                color: ratio(8, 10),
+                surface: Surface::Reflective {
+                    reflectivity: ratio(2, 10),
+                },
+                sidedness: Sidedness::Both,
+                casts_shadows: true,
+            }),
+            Box::new(Sphere {
+                center: vec3(0, 0, -7),
+                radius: Number::from(1),
+                color: ratio(6, 10),
+                surface: Surface::Diffuse,
+                sidedness: Sidedness::Both,
+                casts_shadows: true,
+            }),
+            Box::new(checkerboard_floor()),
+        ]),
+        groups: vec![],
+        lights: default_lights(),
+        integrator: Integrator::Whitted,
+        tone_mapping: ToneMapping::None,
+        ambient_occlusion: None,
+        fog: None,
+        background: Background::Gradient {
+            bottom: Number::from(0),
+            top: ratio(1, 2),
+        },
+    }
+}
+
+// A table with a rug under it, in front of a picture on the wall.
+pub fn planar() -> Scene {
+    let mut elements: Vec<Box<dyn Element>> = vec![
+        Box::new(Rectangle::new(
+            vec3(-2, -1, -5),
+            vec3(4, 0, 0),
+            vec3(0, 0, -2),
+            ratio(7, 10),
+            Surface::Diffuse,
+            Sidedness::Both,
+        )),
+        Box::new(Disc {
+            center: Vec3 {
+                y: ratio(-199, 100),
+                ..vec3(0, 0, -6)
+            },
+            normal: vec3(0, -1, 0),
+            radius: Number::from(3),
+            color: ratio(5, 10),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }),
+        // The frame, then a hexagon picture a little in front of it.
+        Box::new(Rectangle::new(
+            vec3(-3, 0, -12),
+            vec3(6, 0, 0),
+            vec3(0, 4, 0),
+            ratio(3, 10),
+            Surface::Reflective {
+                reflectivity: ratio(3, 10),
+            },
+            Sidedness::Both,
+        )),
+        Box::new(Polygon::new(
+            vec![
+                vec3(-2, 2, -11),
+                vec3(-1, 1, -11),
+                vec3(1, 1, -11),
+                vec3(2, 2, -11),
+                vec3(1, 3, -11),
+                vec3(-1, 3, -11),
+            ],
+            ratio(9, 10),
+            Surface::Diffuse,
+            Sidedness::Both,
+        )),
+        Box::new(checkerboard_floor()),
+    ];
+
+    for (x, z) in [(-2, -5), (2, -5), (-2, -7), (2, -7)].iter() {
+        elements.push(Box::new(Cylinder {
+            base: vec3(*x, -2, *z),
+            axis: vec3(0, 1, 0),
+            height: Number::from(1),
+            radius: ratio(1, 10),
+            color: ratio(7, 10),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }));
+    }
+
+    Scene {
+        width: 512,
+        height: 256,
+        elements: Accelerator::bvh(elements),
+        groups: vec![],
+        lights: default_lights(),
+        integrator: Integrator::Whitted,
+        tone_mapping: ToneMapping::None,
+        ambient_occlusion: None,
+        fog: None,
+        background: Background::Gradient {
+            bottom: Number::from(0),
+            top: ratio(1, 2),
+        },
+    }
+}
+
+// A ball drilled through along all three axes, a cube with its corners rounded off, and a bowl.
+pub fn csg() -> Scene {
+    let ball_center = Vec3 {
+        y: ratio(-1, 2),
+        ..vec3(-3, 0, -8)
+    };
+    let drill = |axis: Vec3| {
+        let mut base = axis;
+        base.do_scale(&Number::from(-2));
+        base.do_add(&ball_center);
+        Box::new(Cylinder {
+            base,
+            axis,
+            height: Number::from(4),
+            radius: ratio(1, 2),
+            color: ratio(2, 10),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        })
+    };
+
+    let drilled = CsgDifference {
+        left: Box::new(Sphere {
+            center: ball_center,
+            radius: ratio(3, 2),
+            color: ratio(8, 10),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }),
+        right: Box::new(CsgUnion {
+            left: drill(vec3(1, 0, 0)),
+            right: Box::new(CsgUnion {
+                left: drill(vec3(0, 1, 0)),
+                right: drill(vec3(0, 0, 1)),
+                surface: Surface::Diffuse,
+                sidedness: Sidedness::Both,
+                casts_shadows: true,
+            }),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }),
+        surface: Surface::Diffuse,
+        sidedness: Sidedness::Both,
+        casts_shadows: true,
+    };
+
+    let rounded_cube = CsgIntersection {
+        left: Box::new(AlignedBox {
+            min: vec3(-1, -2, -7),
+            max: vec3(1, 0, -5),
+            face_colors: [ratio(7, 10); 6],
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }),
+        right: Box::new(Sphere {
+            center: vec3(0, -1, -6),
+            radius: ratio(13, 10),
+            color: ratio(7, 10),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }),
+        surface: Surface::Reflective {
+            reflectivity: ratio(2, 10),
+        },
+        sidedness: Sidedness::Both,
+        casts_shadows: true,
+    };
+
+    // The bottom half of a sphere, hollowed out.
+    let rim = Vec3 {
+        y: ratio(-1, 2),
+        ..vec3(3, 0, -7)
+    };
+    let bowl = CsgDifference {
+        left: Box::new(CsgIntersection {
+            left: Box::new(Sphere {
+                center: rim,
+                radius: ratio(3, 2),
+                color: ratio(6, 10),
+                surface: Surface::Diffuse,
+                sidedness: Sidedness::Both,
+                casts_shadows: true,
+            }),
+            right: Box::new(Plane::new(
+                rim,
+                vec3(0, -1, 0),
+                ratio(6, 10),
+                Surface::Diffuse,
+                Sidedness::Both,
+            )),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }),
+        right: Box::new(Sphere {
+            center: rim,
+            radius: ratio(13, 10),
+            color: ratio(9, 10),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }),
+        surface: Surface::Diffuse,
+        sidedness: Sidedness::Both,
+        casts_shadows: true,
+    };
+
+    Scene {
+        width: 512,
+        height: 256,
+        elements: Accelerator::bvh(vec![
+            Box::new(drilled),
+            Box::new(rounded_cube),
+            Box::new(bowl),
+            Box::new(checkerboard_floor()),
+        ]),
+        groups: vec![],
+        lights: default_lights(),
+        integrator: Integrator::Whitted,
+        tone_mapping: ToneMapping::None,
+        ambient_occlusion: None,
+        fog: None,
+        background: Background::Gradient {
+            bottom: Number::from(0),
+            top: ratio(1, 2),
+        },
+    }
+}
+
+// Distance field shapes next to an ordinary sphere: a rounded box with a scoop out of its front,
+// two blobs melting together, a ring, and a row of blocks along the back.
+pub fn sdf() -> Scene {
+    let scooped_box = Shape::SmoothSubtract {
+        left: Box::new(Shape::RoundedBox {
+            center: vec3(-3, -1, -7),
+            half_size: vec3(1, 1, 1),
+            radius: ratio(1, 4),
+        }),
+        right: Box::new(Shape::Sphere {
+            center: vec3(-3, -1, -6),
+            radius: ratio(3, 4),
+        }),
+        smoothing: ratio(1, 4),
+    };
+
+    let blobs = Shape::SmoothUnion {
+        left: Box::new(Shape::Sphere {
+            center: Vec3 {
+                x: ratio(-1, 2),
+                ..vec3(0, -1, -8)
+            },
+            radius: ratio(3, 4),
+        }),
+        right: Box::new(Shape::Sphere {
+            center: Vec3 {
+                x: ratio(1, 2),
+                y: ratio(-1, 2),
+                ..vec3(0, 0, -8)
+            },
+            radius: ratio(1, 2),
+        }),
+        smoothing: ratio(1, 2),
+    };
+
+    let ring = Shape::Torus {
+        center: vec3(3, -1, -7),
+        major_radius: Number::from(1),
+        minor_radius: ratio(3, 10),
+    };
+
+    // Every 2 along x, cut down to the bounding sphere.
+    let blocks = Shape::Repeat {
+        shape: Box::new(Shape::Box {
+            center: vec3(0, 1, -14),
+            half_size: Vec3 {
+                x: ratio(1, 2),
+                y: ratio(1, 2),
+                z: ratio(1, 2),
+            },
+        }),
+        period: vec3(2, 0, 0),
+    };
+
+    let sdf = |shape: Shape, bounds_center: Vec3, bounds_radius: Number| {
+        Box::new(Sdf {
+            shape,
+            bounds_center,
+            bounds_radius,
+            color: ratio(8, 10),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        })
+    };
+
+    Scene {
+        width: 512,
+        height: 256,
+        elements: Accelerator::bvh(vec![
+            sdf(scooped_box, vec3(-3, -1, -7), Number::from(2)),
+            sdf(blobs, vec3(0, -1, -8), Number::from(2)),
+            sdf(ring, vec3(3, -1, -7), Number::from(2)),
+            sdf(blocks, vec3(0, 1, -14), Number::from(7)),
+            Box::new(Sphere {
+                center: vec3(1, -1, -5),
+                radius: ratio(1, 2),
+                color: ratio(9, 10),
+                surface: Surface::Reflective {
+                    reflectivity: ratio(3, 10),
+                },
+                sidedness: Sidedness::Both,
+                casts_shadows: true,
+            }),
+            Box::new(checkerboard_floor()),
+        ]),
+        groups: vec![],
+        lights: default_lights(),
+        integrator: Integrator::Whitted,
+        tone_mapping: ToneMapping::None,
+        ambient_occlusion: None,
+        fog: None,
+        background: Background::Gradient {
+            bottom: Number::from(0),
+            top: ratio(1, 2),
+        },
+    }
+}
+
+// A blob pulling apart into three, with a dent where a negative ball pushes into it.
+pub fn metaballs() -> Scene {
+    let ball = |x: Number, y: Number, radius: Number, weight: Number| Ball {
+        center: Vec3 {
+            x,
+            y,
+            z: Number::from(-7),
+        },
+        radius,
+        weight,
+    };
+
+    Scene {
+        width: 512,
+        height: 256,
+        elements: Accelerator::bvh(vec![
+            Box::new(Metaballs {
+                balls: vec![
+                    ball(ratio(-3, 2), ratio(-1, 2), Number::from(2), Number::from(1)),
+                    ball(ratio(1, 2), Number::from(-1), ratio(7, 4), Number::from(1)),
+                    ball(Number::from(2), ratio(1, 2), ratio(3, 2), Number::from(1)),
+                    ball(ratio(-1, 2), ratio(1, 2), Number::from(1), ratio(-1, 2)),
+                ],
+                threshold: ratio(1, 8),
+                color: ratio(8, 10),
+                surface: Surface::Reflective {
+                    reflectivity: ratio(2, 10),
+                },
+                sidedness: Sidedness::Both,
+                casts_shadows: true,
+            }),
+            Box::new(checkerboard_floor()),
+        ]),
+        groups: vec![],
+        lights: default_lights(),
+        integrator: Integrator::Whitted,
+        tone_mapping: ToneMapping::None,
+        ambient_occlusion: None,
+        fog: None,
+        background: Background::Gradient {
+            bottom: Number::from(0),
+            top: ratio(1, 2),
+        },
+    }
+}
+
+pub fn terrain() -> Scene {
+    let options = HeightfieldOptions {
+        origin: vec3(-12, -3, -24),
+        size: vec3(24, 5, 22),
+        color: ratio(7, 10),
+        surface: Surface::Diffuse,
+        sidedness: Sidedness::Both,
+        casts_shadows: true,
+    };
+    let terrain = Heightfield::load("models/terrain.png", &options)
+        .unwrap_or_else(|e| panic!("Couldn't load models/terrain.png: {}", e));
+
+    Scene {
+        width: 512,
+        height: 256,
+        elements: Accelerator::bvh(vec![Box::new(terrain)]),
+        groups: vec![],
+        lights: default_lights(),
+        integrator: Integrator::Whitted,
+        tone_mapping: ToneMapping::None,
+        ambient_occlusion: None,
+        // Hazes the far hills into the sky. It only reaches a little above the hilltops, so the sky
+        // clears going up.
+        fog: Some(Fog {
+            falloff: FogFalloff::Linear {
+                start: Number::from(8),
+                end: Number::from(40),
+            },
+            color: ratio(6, 10),
+            ceiling: Number::from(4),
+        }),
+        // The sun is off to the right, where the main light comes from.
+        background: Background::Sky {
+            horizon: ratio(6, 10),
+            zenith: ratio(1, 4),
+            ground: ratio(3, 10),
+            sun_direction: {
+                let mut v = vec3(1, 1, 0);
+                v.do_normalize();
+                v
+            },
+            sun_size: ratio(95, 100),
+            sun_color: Number::from(1),
+        },
+    }
+}
+
+pub fn instances() -> Scene {
+    let options = ObjOptions {
+        fit: Some(Fit {
+            center: vec3(0, 0, 0),
+            size: Number::from(1),
+        }),
+        color: ratio(9, 10),
+        surface: Surface::Diffuse,
+        sidedness: Sidedness::Both,
+        casts_shadows: true,
+    };
+    let model = Rc::new(
+        load_obj("models/icosahedron.obj", &options)
+            .unwrap_or_else(|e| panic!("Couldn't load models/icosahedron.obj: {}", e)),
+    );
+
+    // Fractions of a half turn.
+    let angle = |numerator: i16, denominator: i16| {
+        let mut a = ratio(numerator, denominator);
+        a.do_mul(&PI);
+        a
+    };
+
+    // The same mesh three times over, growing and turning from left to right.
+    let mut elements: Vec<Box<dyn Element>> = (0..3)
+        .map(|i| {
+            let mut to_world = Mat4::translation(&Vec3 {
+                x: Number::from(i * 5 - 7),
+                y: ratio(i - 2, 2),
+                z: Number::from(-7),
+            });
+            to_world.do_mul(&Mat4::rotation_y(&angle(i, 5)));
+            to_world.do_mul(&Mat4::rotation_x(&angle(i, 7)));
+            to_world.do_mul(&Mat4::scaling(&Vec3 {
+                x: Number::from(i + 2),
+                y: Number::from(i + 2),
+                z: Number::from(i + 2),
+            }));
+            Box::new(Transformed::new(Rc::clone(&model), &to_world)) as Box<dyn Element>
+        })
+        .collect();
+
+    // A sphere squashed into a tilted ellipsoid.
+    let mut to_world = Mat4::translation(&vec3(8, 0, -7));
+    to_world.do_mul(&Mat4::rotation_z(&angle(1, 4)));
+    to_world.do_mul(&Mat4::scaling(&Vec3 {
+        x: Number::from(2),
+        y: Number::from(1),
+        z: Number::from(1),
+    }));
+    elements.push(Box::new(Transformed::new(
+        Rc::new(Sphere {
+            center: vec3(0, 0, 0),
+            radius: Number::from(1),
+            color: ratio(7, 10),
+            surface: Surface::Reflective {
+                reflectivity: ratio(3, 10),
+            },
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        }),
+        &to_world,
+    )));
+    elements.push(Box::new(checkerboard_floor()));
+
+    Scene {
+        width: 512,
+        height: 256,
+        elements: Accelerator::bvh(elements),
+        groups: vec![],
+        lights: default_lights(),
+        integrator: Integrator::Whitted,
+        tone_mapping: ToneMapping::None,
+        ambient_occlusion: None,
+        fog: None,
+        background: Background::Gradient {
+            bottom: Number::from(0),
+            top: ratio(1, 2),
+        },
+    }
+}
+
+pub fn groups() -> Scene {
+    let block = |min: Vec3, max: Vec3| -> Box<dyn Element> {
+        Box::new(AlignedBox {
+            min,
+            max,
+            face_colors: [ratio(6, 10); 6],
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        })
+    };
+    let ball = |x: Number, radius: Number| -> Box<dyn Element> {
+        Box::new(Sphere {
+            center: Vec3 {
+                x,
+                y: radius,
+                z: Number::from(0),
+            },
+            radius,
+            color: ratio(8, 10),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        })
+    };
+
+    // Legs at the corners of a 5 by 2 rectangle, up to a top at height 2.
+    let mut table = vec![block(
+        Vec3 {
+            x: Number::from(-3),
+            y: Number::from(2),
+            z: ratio(-3, 2),
+        },
+        Vec3 {
+            x: Number::from(3),
+            y: ratio(5, 2),
+            z: ratio(3, 2),
+        },
+    )];
+    // In eighths, each a quarter wide.
+    for (x, z) in [(-20, -8), (-20, 8), (20, -8), (20, 8)].iter() {
+        table.push(block(
+            Vec3 {
+                x: ratio(x - 1, 8),
+                y: Number::from(0),
+                z: ratio(z - 1, 8),
+            },
+            Vec3 {
+                x: ratio(x + 1, 8),
+                y: Number::from(2),
+                z: ratio(z + 1, 8),
+            },
+        ));
+    }
+
+    let mut angle = ratio(1, 8);
+    angle.do_mul(&PI);
+    let mut on_floor = Mat4::translation(&vec3(0, -2, -7));
+    on_floor.do_mul(&Mat4::rotation_y(&angle));
+
+    Scene {
+        width: 512,
+        height: 256,
+        elements: Accelerator::bvh(vec![Box::new(checkerboard_floor())]),
+        groups: vec![Group {
+            name: String::from("table"),
+            transform: Some(Transform::new(&on_floor)),
+            surface: None,
+            visible: true,
+            elements: Accelerator::bvh(table),
+            // Set out on the tabletop, all polished to the same shine.
+            groups: vec![Group {
+                name: String::from("tableware"),
+                transform: Some(Transform::new(&Mat4::translation(&Vec3 {
+                    x: Number::from(0),
+                    y: ratio(5, 2),
+                    z: Number::from(0),
+                }))),
+                surface: Some(Surface::Reflective {
+                    reflectivity: ratio(6, 10),
+                }),
+                visible: true,
+                elements: Accelerator::bvh(vec![
+                    ball(ratio(-3, 2), ratio(1, 2)),
+                    ball(Number::from(0), ratio(3, 4)),
+                    ball(ratio(7, 4), ratio(1, 3)),
+                ]),
+                groups: vec![],
+            }],
+        }],
+        lights: default_lights(),
+        integrator: Integrator::Whitted,
+        tone_mapping: ToneMapping::None,
+        ambient_occlusion: None,
+        fog: None,
+        background: Background::Gradient {
+            bottom: Number::from(0),
+            top: ratio(1, 2),
+        },
+    }
+}
+
+// Rows of small balls over the floor, filed into a uniform grid rather than a tree. Every third
+// one is polished.
+pub fn crowd() -> Scene {
+    let mut elements: Vec<Box<dyn Element>> = vec![Box::new(checkerboard_floor())];
+    for row in 0..5 {
+        for column in -4..5 {
+            let i = row * 9 + column;
+            elements.push(Box::new(Sphere {
+                center: Vec3 {
+                    x: Number::from(column * 2 + row % 2),
+                    y: ratio(-3, 2),
+                    z: Number::from(-5 - row * 2),
+                },
+                radius: ratio(1, 2),
+                color: ratio(4 + (i + 9) % 5, 10),
+                surface: if i % 3 == 0 {
+                    Surface::Reflective {
+                        reflectivity: ratio(5, 10),
+                    }
+                } else {
+                    Surface::Diffuse
+                },
+                sidedness: Sidedness::Both,
+                casts_shadows: true,
+            }));
+        }
+    }
+
+    Scene {
+        width: 512,
+        height: 256,
+        elements: Accelerator::grid(elements),
+        groups: vec![],
+        lights: default_lights(),
+        integrator: Integrator::Whitted,
+        tone_mapping: ToneMapping::None,
+        ambient_occlusion: None,
+        fog: None,
+        background: Background::Gradient {
+            bottom: Number::from(0),
+            top: ratio(1, 2),
+        },
+    }
+}
+
+// One of each texture, in a row on the floor in front of a gridded board leaning back.
+pub fn textures() -> Scene {
+    let up = vec3(0, 1, 0);
+    let sphere = |x: i16, texture: Rc<dyn Texture>| -> Box<dyn Element> {
+        Box::new(Textured::new(
+            Sphere {
+                center: vec3(x, -1, -7),
+                radius: Number::from(1),
+                color: Number::from(0),
+                surface: Surface::Diffuse,
+                sidedness: Sidedness::Both,
+                casts_shadows: true,
+            },
+            texture,
+        ))
+    };
+
+    // The rings are around the cylinder's own axis, so it's made standing at the origin, then laid
+    // down facing the camera.
+    let log = Rc::new(Textured::new(
+        Cylinder {
+            base: vec3(0, 0, 0),
+            axis: up,
+            height: ratio(3, 2),
+            radius: Number::from(1),
+            color: Number::from(0),
+            surface: Surface::Diffuse,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        },
+        Rc::new(Rings {
+            // Not a whole number, so the side isn't right on the edge of a ring.
+            scale: ratio(9, 2),
+            colors: [ratio(7, 10), ratio(4, 10)],
+        }) as Rc<dyn Texture>,
+    ));
+
+    Scene {
+        width: 512,
+        height: 256,
+        elements: Accelerator::bvh(vec![
+            sphere(
+                -5,
+                Rc::new(Stripes {
+                    scale: Number::from(8),
+                    colors: [ratio(9, 10), ratio(2, 10)],
+                }),
+            ),
+            // Kept off whole units, so its faces don't lie on the edges of the cubes.
+            Box::new(Textured::new(
+                AlignedBox {
+                    min: Vec3 {
+                        x: ratio(-11, 4),
+                        y: Number::from(-2),
+                        z: ratio(-31, 4),
+                    },
+                    max: Vec3 {
+                        x: ratio(-5, 4),
+                        y: ratio(-1, 4),
+                        z: ratio(-25, 4),
+                    },
+                    face_colors: [Number::from(0); 6],
+                    surface: Surface::Diffuse,
+                    sidedness: Sidedness::Both,
+                    casts_shadows: true,
+                },
+                Rc::new(Checker3d {
+                    scale: Number::from(2),
+                    colors: [ratio(9, 10), ratio(2, 10)],
+                }),
+            )),
+            sphere(
+                1,
+                Rc::new(Gradient {
+                    scale: Number::from(1),
+                    colors: [ratio(1, 10), ratio(9, 10)],
+                }),
+            ),
+            Box::new(Transformed::new(log, &{
+                let mut quarter = *PI;
+                quarter.do_div(&Number::from(2));
+                let mut to_world = Mat4::translation(&vec3(4, -1, -8));
+                to_world.do_mul(&Mat4::rotation_x(&quarter));
+                to_world
+            })),
+            Box::new(Textured::new(
+                Capsule {
+                    start: vec3(7, -1, -8),
+                    end: vec3(7, 0, -8),
+                    radius: ratio(1, 2),
+                    color: Number::from(0),
+                    surface: Surface::Diffuse,
+                    sidedness: Sidedness::Both,
+                    casts_shadows: true,
+                },
+                Rc::new(SolidColor {
+                    color: ratio(6, 10),
+                }),
+            )),
+            // 10 across each way, so the squares come out a unit each.
+            Box::new(Textured::new(
+                Rectangle::new(
+                    vec3(-5, -2, -10),
+                    vec3(10, 0, 0),
+                    vec3(0, 6, -8),
+                    Number::from(0),
+                    Surface::Diffuse,
+                    Sidedness::Both,
+                ),
+                Rc::new(Grid {
+                    scale: Number::from(10),
+                    width: ratio(1, 10),
+                    fill: ratio(7, 10),
+                    line: ratio(2, 10),
+                }),
+            )),
+            Box::new(checkerboard_floor()),
+        ]),
+        groups: vec![],
+        lights: default_lights(),
+        integrator: Integrator::Whitted,
+        tone_mapping: ToneMapping::None,
+        ambient_occlusion: None,
+        fog: None,
+        background: Background::Gradient {
+            bottom: Number::from(0),
+            top: ratio(1, 2),
+        },
+    }
+}
+
+// A room open at the top and front, with sunlight coming in over the dark right wall. Path traced,
+// so the floor in that wall's shadow is lit by what bounces off the pale left wall.
+pub fn room() -> Scene {
+    let wall = |origin: Vec3, edge1: Vec3, edge2: Vec3, color: Number| -> Box<dyn Element> {
+        Box::new(Rectangle::new(
+            origin,
+            edge1,
+            edge2,
+            color,
+            Surface::Diffuse,
+            Sidedness::Both,
+        ))
+    };
+
+    Scene {
+        width: 512,
+        height: 256,
+        elements: Accelerator::bvh(vec![
+            wall(
+                vec3(-4, -2, -4),
+                vec3(8, 0, 0),
+                vec3(0, 0, -8),
+                ratio(8, 10),
+            ),
+            wall(
+                vec3(-4, -2, -12),
+                vec3(8, 0, 0),
+                vec3(0, 6, 0),
+                ratio(8, 10),
+            ),
+            wall(
+                vec3(-4, -2, -4),
+                vec3(0, 0, -8),
+                vec3(0, 6, 0),
+                ratio(9, 10),
+            ),
+            wall(vec3(4, -2, -12), vec3(0, 0, 8), vec3(0, 6, 0), ratio(2, 10)),
+            Box::new(Sphere {
+                center: vec3(-1, -1, -8),
+                radius: Number::from(1),
+                color: ratio(8, 10),
+                surface: Surface::Diffuse,
+                sidedness: Sidedness::Both,
+                casts_shadows: true,
+            }),
+            Box::new(AlignedBox {
+                min: vec3(1, -2, -10),
+                max: vec3(3, 1, -8),
+                face_colors: [ratio(8, 10); 6],
+                surface: Surface::Diffuse,
+                sidedness: Sidedness::Both,
+                casts_shadows: true,
+            }),
+        ]),
+        groups: vec![],
+        lights: vec![Directional {
+            direction: {
+                let mut v = vec3(-1, -2, -1);
+                v.do_normalize();
+                v
+            },
+            color: Number::from(1),
+        }],
+        integrator: Integrator::PathTraced { samples: 16 },
+        tone_mapping: ToneMapping::Reinhard,
+        ambient_occlusion: None,
+        fog: None,
+        background: Background::Gradient {
+            bottom: Number::from(0),
+            top: ratio(1, 2),
+        },
+    }
+}
+
+// Mirror, glass and chalk balls hanging in the open, with the sky and hills all round coming from
+// a lat-long map in models/, so run it from the crate directory. The light comes from where the
+// sun is in the map, behind the camera and off to the right.
+pub fn environment() -> Scene {
+    let map = EnvironmentMap::load("models/sky.png")
+        .unwrap_or_else(|e| panic!("Couldn't load models/sky.png: {}", e));
+
+    let ball = |x: i16, color: Number, surface: Surface| -> Box<dyn Element> {
+        Box::new(Sphere {
+            center: vec3(x, 0, -6),
+            radius: ratio(3, 2),
+            color,
+            surface,
+            sidedness: Sidedness::Both,
+            casts_shadows: true,
+        })
+    };
+
+    Scene {
+        width: 512,
+        height: 256,
+        elements: Accelerator::bvh(vec![
+            ball(
+                -4,
+                ratio(9, 10),
+                Surface::Refractive {
+                    index: ratio(3, 2),
+                    transparency: ratio(9, 10),
+                },
+            ),
+            ball(
+                0,
+                ratio(9, 10),
+                Surface::Reflective {
+                    reflectivity: ratio(9, 10),
+                },
+            ),
+            ball(4, ratio(8, 10), Surface::Diffuse),
+        ]),
+        groups: vec![],
+        lights: vec![Directional {
+            direction: {
+                let mut v = vec3(-4, -4, -3);
+                v.do_normalize();
+                v
+            },
+            color: Number::from(1),
+        }],
+        integrator: Integrator::Whitted,
+        tone_mapping: ToneMapping::None,
+        ambient_occlusion: None,
+        fog: None,
+        background: Background::Environment(map),
+    }
+}
@@ -0,0 +1,130 @@
+use crate::offset_distance;
+use crate::Element;
+use crate::Number;
+use crate::Ray;
+use crate::Vec3;
+
+// Which sides of an element rays can hit. The front is the side its surface normal points out
+// of. Anything a ray can see through (refraction, or the inside of a closed shape) wants Both.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sidedness {
+    Front,
+    Back,
+    Both,
+}
+
+impl Sidedness {
+    pub fn shows(self, front_face: bool) -> bool {
+        match self {
+            Sidedness::Front => front_face,
+            Sidedness::Back => !front_face,
+            Sidedness::Both => true,
+        }
+    }
+}
+
+// The surface normal turned to face back along the ray, and whether it already did, i.e. whether
+// the ray hit the front.
+pub fn facing_normal(direction: &Vec3, surface_normal: &Vec3) -> (Vec3, bool) {
+    let mut n = *surface_normal;
+    let front_face = !direction.dot(&n).is_positive();
+    if !front_face {
+        n.do_neg();
+    }
+    (n, front_face)
+}
+
+// Closed elements can be crossed several times, up to four for a torus.
+const MAX_HIDDEN_HITS: i16 = 4;
+
+// The nearest hit on a side of `element` that its sidedness shows. Hits on hidden sides are
+// stepped past, so a back-only sphere is hit where the ray leaves it.
+pub fn intersect_visible(element: &dyn Element, ray: &Ray) -> Option<Number> {
+    let sidedness = element.sidedness();
+    if sidedness == Sidedness::Both {
+        return element.intersect(ray);
+    }
+
+    let mut ray = *ray;
+    let mut travelled = Number::from(0);
+
+    for _ in 0..MAX_HIDDEN_HITS {
+        let mut distance = element.intersect(&ray)?;
+
+        let hit = element.hit(&ray, distance);
+        if sidedness.shows(hit.front_face) {
+            distance.do_add(&travelled);
+            return Some(distance);
+        }
+
+        let step = offset_distance(&hit.point);
+        let mut origin = ray.direction;
+        origin.do_scale(&step);
+        origin.do_add(&hit.point);
+        travelled.do_add(&distance);
+        travelled.do_add(&step);
+        ray.origin = origin;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::{intersect_visible, Sidedness};
+    use crate::elements::plane::Plane;
+    use crate::elements::sphere::Sphere;
+    use crate::material::Surface;
+    use crate::vector::vec3;
+    use crate::Number;
+    use crate::Ray;
+    use rstest::rstest;
+
+    fn forward() -> Ray {
+        Ray {
+            origin: vec3(0, 0, 0),
+            direction: vec3(0, 0, -1),
+        }
+    }
+
+    #[rstest]
+    #[case(Sidedness::Front, 3.0)]
+    #[case(Sidedness::Back, 7.0)]
+    #[case(Sidedness::Both, 3.0)]
+    fn test_sphere_sides(#[case] sidedness: Sidedness, #[case] expected: f64) {
+        let sphere = Sphere {
+            center: vec3(0, 0, -5),
+            radius: Number::from(2),
+            color: Number::from(1),
+            surface: Surface::Diffuse,
+            sidedness,
+            casts_shadows: true,
+        };
+
+        let hit = intersect_visible(&sphere, &forward());
+        assert!(f64::abs(hit.unwrap().to_f64() - expected) < 0.01);
+    }
+
+    #[rstest]
+    // A -z normal points away from the ray's origin, so the front faces the ray.
+    #[case(-1, Sidedness::Front, true)]
+    #[case(-1, Sidedness::Back, false)]
+    #[case(1, Sidedness::Front, false)]
+    #[case(1, Sidedness::Back, true)]
+    #[case(1, Sidedness::Both, true)]
+    fn test_plane_sides(
+        #[case] normal_z: i16,
+        #[case] sidedness: Sidedness,
+        #[case] expect_hit: bool,
+    ) {
+        let plane = Plane::new(
+            vec3(0, 0, -5),
+            vec3(0, 0, normal_z),
+            Number::from(1),
+            Surface::Diffuse,
+            sidedness,
+        );
+
+        assert_eq!(intersect_visible(&plane, &forward()).is_some(), expect_hit);
+    }
+}
@@ -0,0 +1,327 @@
+use crate::Number;
+
+// Polynomial root finding for ray-surface intersections.
+
+lazy_static! {
+    // Stands in for infinity, e.g. where a ray that never leaves a half-space would come out, and
+    // roots further out than this are dropped rather than overflowing. Kept under half the range,
+    // since comparing Numbers subtracts them.
+    pub static ref FAR: Number = Number::from(16000);
+}
+
+// num / den, or None if that would be out of range.
+pub fn checked_div(num: &Number, den: &Number) -> Option<Number> {
+    if den.is_zero() {
+        return None;
+    }
+
+    let mut abs_den = *den;
+    abs_den.do_abs();
+
+    if abs_den.is_less_than(&Number::from(1)) {
+        let mut abs_num = *num;
+        abs_num.do_abs();
+        let mut limit = abs_den;
+        limit.do_mul(&FAR);
+
+        if !abs_num.is_less_than(&limit) {
+            return None;
+        }
+    }
+
+    let mut r = *num;
+    r.do_div(den);
+    Some(r)
+}
+
+// Number::do_sqrt overflows from 2048 up, so take bigger roots as 16 sqrt(x / 256).
+fn sqrt(x: &mut Number) {
+    if x.cmp(&Number::from(2048)) < 0 {
+        x.do_sqrt();
+    } else {
+        x.do_div(&Number::from(256));
+        x.do_sqrt();
+        x.do_mul(&Number::from(16));
+    }
+}
+
+// Real roots of a t^2 + 2 half_b t + c, smallest first. A single root comes back twice.
+//
+// The textbook (-b +- sqrt(disc)) / 2a loses nearly all its precision to cancellation when b^2
+// dwarfs 4ac, which 16.16 (and a 1% sqrt) can't afford. Instead, find the root where the signs
+// agree, and get the other from the product of the roots, c / a. This also copes with a = 0.
+pub fn solve_quadratic(a: &Number, half_b: &Number, c: &Number) -> Option<(Number, Number)> {
+    let mut discriminant = *half_b;
+    discriminant.do_mul(half_b);
+    let mut ac = *a;
+    ac.do_mul(c);
+    discriminant.do_sub(&ac);
+
+    if discriminant.is_negative() {
+        return None;
+    }
+    sqrt(&mut discriminant);
+
+    let mut q = *half_b;
+    if half_b.is_negative() {
+        q.do_sub(&discriminant);
+    } else {
+        q.do_add(&discriminant);
+    }
+    q.do_neg();
+
+    match (checked_div(&q, a), checked_div(c, &q)) {
+        (Some(t0), Some(t1)) => {
+            if t1.is_less_than(&t0) {
+                Some((t1, t0))
+            } else {
+                Some((t0, t1))
+            }
+        }
+        (Some(t), None) | (None, Some(t)) => Some((t, t)),
+        (None, None) => None,
+    }
+}
+
+// Polynomials are coefficient slices, lowest power first.
+
+pub fn evaluate(coefficients: &[Number], t: &Number) -> Number {
+    let mut r = Number::from(0);
+    for c in coefficients.iter().rev() {
+        r.do_mul(t);
+        r.do_add(c);
+    }
+    r
+}
+
+fn derivative(coefficients: &[Number]) -> Vec<Number> {
+    coefficients
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(power, c)| {
+            let mut d = *c;
+            d.do_mul(&Number::from(power as i16));
+            d
+        })
+        .collect()
+}
+
+pub fn multiply(a: &[Number], b: &[Number]) -> Vec<Number> {
+    if a.is_empty() || b.is_empty() {
+        return vec![];
+    }
+
+    let mut product = vec![Number::from(0); a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            let mut term = *x;
+            term.do_mul(y);
+            product[i + j].do_add(&term);
+        }
+    }
+    product
+}
+
+// The biggest of the coefficients, ignoring sign.
+pub fn largest(coefficients: &[Number]) -> Number {
+    coefficients
+        .iter()
+        .map(|c| {
+            let mut c = *c;
+            c.do_abs();
+            c
+        })
+        .fold(
+            Number::from(0),
+            |a, b| if a.is_less_than(&b) { b } else { a },
+        )
+}
+
+fn is_between(t: &Number, low: &Number, high: &Number) -> bool {
+    !t.is_less_than(low) && !high.is_less_than(t)
+}
+
+// Narrows in on the root in [low, high], where the polynomial changes sign once.
+fn bisect(coefficients: &[Number], low: &Number, high: &Number) -> Number {
+    let mut low = *low;
+    let mut high = *high;
+    let low_negative = evaluate(coefficients, &low).is_negative();
+
+    // Down to the last bit of 16.16 for anything under 64 wide.
+    for _ in 0..22 {
+        let mut middle = low;
+        middle.do_add(&high);
+        middle.do_div(&Number::from(2));
+
+        if evaluate(coefficients, &middle).is_negative() == low_negative {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+
+    low
+}
+
+// Real roots in [low, high], smallest first, for a polynomial of any degree.
+//
+// The roots of the derivative split the range into pieces where the polynomial only goes up or
+// only goes down, so each piece has at most one root, and bisection will find it if the ends
+// differ in sign. The derivative's roots come from the same function, one degree down. This
+// only ever evaluates the polynomial, which keeps it in range where Sturm sequences or Ferrari's
+// method would need divisions that 16.16 can't survive.
+pub fn roots_between(coefficients: &[Number], low: &Number, high: &Number) -> Vec<Number> {
+    match coefficients.len() {
+        0 | 1 => return vec![],
+        2 => {
+            let mut t = coefficients[0];
+            t.do_neg();
+            return match checked_div(&t, &coefficients[1]) {
+                Some(t) if is_between(&t, low, high) => vec![t],
+                _ => vec![],
+            };
+        }
+        3 => {
+            // Derivatives of a high degree polynomial come out with big coefficients, and solving
+            // squares them. Scaling the whole quadratic doesn't move its roots, but shrinking it
+            // further than it needs costs precision.
+            let mut q = [coefficients[0], coefficients[1], coefficients[2]];
+            let most = Number::from(128);
+            let mut biggest = largest(&q);
+            if most.is_less_than(&biggest) {
+                biggest.do_div(&most);
+                for c in q.iter_mut() {
+                    c.do_div(&biggest);
+                }
+            }
+
+            let mut half_b = q[1];
+            half_b.do_div(&Number::from(2));
+            return match solve_quadratic(&q[2], &half_b, &q[0]) {
+                Some((t0, t1)) => {
+                    let mut roots: Vec<Number> = vec![t0];
+                    if t0.cmp(&t1) != 0 {
+                        roots.push(t1);
+                    }
+                    roots.retain(|t| is_between(t, low, high));
+                    roots
+                }
+                None => vec![],
+            };
+        }
+        _ => {}
+    }
+
+    let mut ends = vec![*low];
+    ends.extend(roots_between(&derivative(coefficients), low, high));
+    ends.push(*high);
+
+    let mut roots = vec![];
+    if evaluate(coefficients, low).is_zero() {
+        roots.push(*low);
+    }
+
+    for pair in ends.windows(2) {
+        let start = evaluate(coefficients, &pair[0]);
+        let end = evaluate(coefficients, &pair[1]);
+
+        if end.is_zero() {
+            roots.push(pair[1]);
+        } else if !start.is_zero() && start.is_negative() != end.is_negative() {
+            roots.push(bisect(coefficients, &pair[0], &pair[1]));
+        }
+    }
+
+    roots
+}
+
+#[cfg(test)]
+mod test {
+    use super::{evaluate, multiply, roots_between, solve_quadratic};
+    use crate::Number;
+    use rstest::rstest;
+
+    #[rstest]
+    // (t - 2)(t - 3)
+    #[case(1, -5, 2, 6, Some((2.0, 3.0)))]
+    // (t + 1)^2
+    #[case(1, 1, 1, 1, Some((-1.0, -1.0)))]
+    #[case(1, 0, 1, 1, None)]
+    // Linear: t - 2
+    #[case(0, 1, 2, -2, Some((2.0, 2.0)))]
+    // t^2 - 100t + 1. The small root is all cancellation the textbook way.
+    #[case(1, -50, 1, 1, Some((0.0100, 99.99)))]
+    fn test_solve_quadratic(
+        #[case] a: i16,
+        #[case] half_b_num: i16,
+        #[case] half_b_den: i16,
+        #[case] c: i16,
+        #[case] expected: Option<(f64, f64)>,
+    ) {
+        let mut half_b = Number::from(half_b_num);
+        half_b.do_div(&Number::from(half_b_den));
+
+        let roots = solve_quadratic(&Number::from(a), &half_b, &Number::from(c));
+        match expected {
+            None => assert!(roots.is_none()),
+            Some((t0, t1)) => {
+                let (r0, r1) = roots.unwrap();
+                assert!(f64::abs(r0.to_f64() - t0) < 0.001 * f64::max(1.0, t0.abs()));
+                assert!(f64::abs(r1.to_f64() - t1) < 0.01 * f64::max(1.0, t1.abs()));
+            }
+        }
+    }
+
+    fn numbers(coefficients: &[i16]) -> Vec<Number> {
+        coefficients.iter().map(|c| Number::from(*c)).collect()
+    }
+
+    #[test]
+    fn test_evaluate() {
+        // 1 + 2t + 3t^2 at t = 2.
+        let p = numbers(&[1, 2, 3]);
+        assert_eq!(evaluate(&p, &Number::from(2)).to_f64(), 17.0);
+    }
+
+    #[test]
+    fn test_multiply() {
+        // (1 + t)(2 - t + t^2) = 2 + t + t^3
+        let p = multiply(&numbers(&[1, 1]), &numbers(&[2, -1, 1]));
+        let expected = [2.0, 1.0, 0.0, 1.0];
+        assert_eq!(p.len(), expected.len());
+        for (c, e) in p.iter().zip(expected.iter()) {
+            assert_eq!(c.to_f64(), *e);
+        }
+    }
+
+    #[rstest]
+    // (t - 1)(t - 2)(t - 3)(t - 4)
+    #[case(&[24, -50, 35, -10, 1], 0, 5, vec![1.0, 2.0, 3.0, 4.0])]
+    #[case(&[24, -50, 35, -10, 1], 0, 3, vec![1.0, 2.0, 3.0])]
+    // t^4 + 1
+    #[case(&[1, 0, 0, 0, 1], -5, 5, vec![])]
+    // t^3 - 2, the cube root of 2.
+    #[case(&[-2, 0, 0, 1], 0, 5, vec![1.259921])]
+    // (t^2 - 1)(t^2 - 9) with a zero leading coefficient tacked on.
+    #[case(&[9, 0, -10, 0, 1, 0], -5, 5, vec![-3.0, -1.0, 1.0, 3.0])]
+    // 300(t - 1)(t - 2), too big to square as it is.
+    #[case(&[600, -900, 300], 0, 5, vec![1.0, 2.0])]
+    fn test_roots_between(
+        #[case] coefficients: &[i16],
+        #[case] low: i16,
+        #[case] high: i16,
+        #[case] expected: Vec<f64>,
+    ) {
+        let roots = roots_between(
+            &numbers(coefficients),
+            &Number::from(low),
+            &Number::from(high),
+        );
+
+        assert_eq!(roots.len(), expected.len());
+        for (root, e) in roots.iter().zip(expected.iter()) {
+            assert!(f64::abs(root.to_f64() - e) < 0.001);
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use crate::background::lerp;
+use crate::Number;
+use crate::Vec3;
+
+// Colors a surface by where it was hit. `point` is in the space of the element the texture is on,
+// and `u` and `v` are across its surface, in whatever units the element uses.
+pub trait Texture: std::fmt::Debug {
+    fn color(&self, point: &Vec3, u: &Number, v: &Number) -> Number;
+}
+
+// The same color all over.
+#[derive(Debug)]
+pub struct SolidColor {
+    pub color: Number,
+}
+
+impl Texture for SolidColor {
+    fn color(&self, _point: &Vec3, _u: &Number, _v: &Number) -> Number {
+        self.color
+    }
+}
+
+// Squares across the surface, alternating between the two colors. `scale` is how many to a unit
+// of UV.
+#[derive(Debug)]
+pub struct Checker {
+    pub scale: Number,
+    pub colors: [Number; 2],
+}
+
+impl Texture for Checker {
+    fn color(&self, _point: &Vec3, u: &Number, v: &Number) -> Number {
+        pick(
+            &self.colors,
+            is_even_cell(u, &self.scale) == is_even_cell(v, &self.scale),
+        )
+    }
+}
+
+// Cubes through space rather than squares across the surface, so curved or cut surfaces show
+// where they pass through the solid.
+#[derive(Debug)]
+pub struct Checker3d {
+    pub scale: Number,
+    pub colors: [Number; 2],
+}
+
+impl Texture for Checker3d {
+    fn color(&self, point: &Vec3, _u: &Number, _v: &Number) -> Number {
+        let x = is_even_cell(&point.x, &self.scale);
+        let y = is_even_cell(&point.y, &self.scale);
+        let z = is_even_cell(&point.z, &self.scale);
+        pick(&self.colors, x ^ y ^ z)
+    }
+}
+
+// Bands along v, alternating across u.
+#[derive(Debug)]
+pub struct Stripes {
+    pub scale: Number,
+    pub colors: [Number; 2],
+}
+
+impl Texture for Stripes {
+    fn color(&self, _point: &Vec3, u: &Number, _v: &Number) -> Number {
+        pick(&self.colors, is_even_cell(u, &self.scale))
+    }
+}
+
+// Rings out from the y axis, like the grain through a log standing on end.
+#[derive(Debug)]
+pub struct Rings {
+    pub scale: Number,
+    pub colors: [Number; 2],
+}
+
+impl Texture for Rings {
+    fn color(&self, point: &Vec3, _u: &Number, _v: &Number) -> Number {
+        let radius = Vec3 {
+            y: Number::from(0),
+            ..*point
+        }
+        .length();
+        pick(&self.colors, is_even_cell(&radius, &self.scale))
+    }
+}
+
+// From the first color at v = 0 to the second at v = 1 / `scale`, and no further either way.
+#[derive(Debug)]
+pub struct Gradient {
+    pub scale: Number,
+    pub colors: [Number; 2],
+}
+
+impl Texture for Gradient {
+    fn color(&self, _point: &Vec3, _u: &Number, v: &Number) -> Number {
+        let mut t = *v;
+        t.do_mul(&self.scale);
+        if t.is_negative() {
+            t = Number::from(0);
+        } else if Number::from(1).is_less_than(&t) {
+            t = Number::from(1);
+        }
+        lerp(&self.colors[0], &self.colors[1], &t)
+    }
+}
+
+// Lines along whole units of u and v, with `fill` between them. `width` is how much of each cell
+// the lines take up, from 0 to 1.
+#[derive(Debug)]
+pub struct Grid {
+    pub scale: Number,
+    pub width: Number,
+    pub fill: Number,
+    pub line: Number,
+}
+
+impl Texture for Grid {
+    fn color(&self, _point: &Vec3, u: &Number, v: &Number) -> Number {
+        let on_line = |c: &Number| {
+            let mut within = *c;
+            within.do_mul(&self.scale);
+            let mut cell = within;
+            cell.do_floor();
+            within.do_sub(&cell);
+            within.is_less_than(&self.width)
+        };
+
+        if on_line(u) || on_line(v) {
+            self.line
+        } else {
+            self.fill
+        }
+    }
+}
+
+// Whether `c`, scaled, is in an even numbered cell. Cells below zero count down from -1, so the
+// pattern carries on across zero without doubling up.
+fn is_even_cell(c: &Number, scale: &Number) -> bool {
+    let mut cell = *c;
+    cell.do_mul(scale);
+    cell.do_floor();
+    cell.to_int32().is_even()
+}
+
+fn pick(colors: &[Number; 2], first: bool) -> Number {
+    if first {
+        colors[0]
+    } else {
+        colors[1]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Checker, Checker3d, Gradient, Grid, Rings, SolidColor, Stripes, Texture};
+    use crate::vector::Vec3;
+    use crate::Number;
+    use rstest::rstest;
+
+    fn origin() -> Vec3 {
+        Vec3 {
+            x: Number::from(0),
+            y: Number::from(0),
+            z: Number::from(0),
+        }
+    }
+
+    fn colors() -> [Number; 2] {
+        [Number::from(1), Number::from(0)]
+    }
+
+    fn tenths(n: i16) -> Number {
+        let mut c = Number::from(n);
+        c.do_div(&Number::from(10));
+        c
+    }
+
+    #[test]
+    fn test_solid_color() {
+        let texture = SolidColor { color: tenths(3) };
+        let color = texture.color(&origin(), &tenths(5), &tenths(-7));
+        assert_eq!(color.to_f64(), tenths(3).to_f64());
+    }
+
+    // Half units, so 2 squares to each unit of UV.
+    #[rstest]
+    #[case(1, 1, 1.0)]
+    #[case(6, 1, 0.0)]
+    #[case(6, 6, 1.0)]
+    #[case(-1, 1, 0.0)]
+    #[case(-1, -1, 1.0)]
+    #[case(-6, -1, 0.0)]
+    fn test_checker(#[case] u: i16, #[case] v: i16, #[case] expected: f64) {
+        let texture = Checker {
+            scale: Number::from(2),
+            colors: colors(),
+        };
+        let color = texture.color(&origin(), &tenths(u), &tenths(v));
+        assert_eq!(color.to_f64(), expected);
+    }
+
+    #[rstest]
+    #[case(Vec3 { x: tenths(5), y: tenths(5), z: tenths(5) }, 1.0)]
+    #[case(Vec3 { x: tenths(15), y: tenths(5), z: tenths(5) }, 0.0)]
+    #[case(Vec3 { x: tenths(15), y: tenths(15), z: tenths(5) }, 1.0)]
+    #[case(Vec3 { x: tenths(15), y: tenths(15), z: tenths(15) }, 0.0)]
+    #[case(Vec3 { x: tenths(-5), y: tenths(5), z: tenths(5) }, 0.0)]
+    fn test_checker_3d(#[case] point: Vec3, #[case] expected: f64) {
+        let texture = Checker3d {
+            scale: Number::from(1),
+            colors: colors(),
+        };
+
+        // Only the point matters, wherever it is on the surface.
+        let color = texture.color(&point, &tenths(5), &tenths(15));
+        assert_eq!(color.to_f64(), expected);
+    }
+
+    #[rstest]
+    #[case(5, 1.0)]
+    #[case(15, 0.0)]
+    #[case(25, 1.0)]
+    #[case(-5, 0.0)]
+    fn test_stripes(#[case] u: i16, #[case] expected: f64) {
+        let texture = Stripes {
+            scale: Number::from(1),
+            colors: colors(),
+        };
+        let color = texture.color(&origin(), &tenths(u), &tenths(15));
+        assert_eq!(color.to_f64(), expected);
+    }
+
+    #[rstest]
+    #[case(5, 0, 1.0)]
+    #[case(0, 15, 0.0)]
+    #[case(-15, 0, 0.0)]
+    // 1.8 out along the diagonal, so in the second ring even though neither is past 1.3.
+    #[case(13, 13, 0.0)]
+    #[case(18, 18, 1.0)]
+    fn test_rings(#[case] x: i16, #[case] z: i16, #[case] expected: f64) {
+        let texture = Rings {
+            scale: Number::from(1),
+            colors: colors(),
+        };
+        let point = Vec3 {
+            x: tenths(x),
+            y: Number::from(7),
+            z: tenths(z),
+        };
+        let color = texture.color(&point, &tenths(5), &tenths(5));
+        assert_eq!(color.to_f64(), expected);
+    }
+
+    #[rstest]
+    #[case(0, 1.0)]
+    #[case(10, 0.5)]
+    #[case(20, 0.0)]
+    #[case(-10, 1.0)]
+    #[case(50, 0.0)]
+    fn test_gradient(#[case] v: i16, #[case] expected: f64) {
+        let texture = Gradient {
+            scale: tenths(5),
+            colors: colors(),
+        };
+        let color = texture.color(&origin(), &tenths(5), &tenths(v));
+        assert!(f64::abs(color.to_f64() - expected) < 0.001);
+    }
+
+    #[rstest]
+    #[case(5, 5, 0.0)]
+    #[case(1, 5, 1.0)]
+    #[case(5, 21, 1.0)]
+    // Lines sit on the low side of each whole unit, below zero too.
+    #[case(-9, 5, 1.0)]
+    #[case(-5, 5, 0.0)]
+    fn test_grid(#[case] u: i16, #[case] v: i16, #[case] expected: f64) {
+        let texture = Grid {
+            scale: Number::from(1),
+            width: tenths(2),
+            fill: Number::from(0),
+            line: Number::from(1),
+        };
+        let color = texture.color(&origin(), &tenths(u), &tenths(v));
+        assert_eq!(color.to_f64(), expected);
+    }
+}
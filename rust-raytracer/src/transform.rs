@@ -0,0 +1,304 @@
+use crate::solver::checked_div;
+use crate::Number;
+use crate::Ray;
+use crate::Vec3;
+
+// A 4x4 matrix acting on column vectors, so the translation is down the last column. Only
+// affine ones come up here, with a bottom row of 0 0 0 1.
+#[derive(Debug, Clone, Copy)]
+pub struct Mat4 {
+    rows: [[Number; 4]; 4],
+}
+
+impl Mat4 {
+    pub fn identity() -> Mat4 {
+        let mut rows = [[Number::from(0); 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            row[i] = Number::from(1);
+        }
+        Mat4 { rows }
+    }
+
+    pub fn translation(offset: &Vec3) -> Mat4 {
+        let mut m = Mat4::identity();
+        m.rows[0][3] = offset.x;
+        m.rows[1][3] = offset.y;
+        m.rows[2][3] = offset.z;
+        m
+    }
+
+    pub fn scaling(factors: &Vec3) -> Mat4 {
+        let mut m = Mat4::identity();
+        m.rows[0][0] = factors.x;
+        m.rows[1][1] = factors.y;
+        m.rows[2][2] = factors.z;
+        m
+    }
+
+    // Rotations are by `angle` radians, anticlockwise looking back down the axis.
+    pub fn rotation_x(angle: &Number) -> Mat4 {
+        Mat4::rotation(angle, 1, 2)
+    }
+
+    pub fn rotation_y(angle: &Number) -> Mat4 {
+        Mat4::rotation(angle, 2, 0)
+    }
+
+    pub fn rotation_z(angle: &Number) -> Mat4 {
+        Mat4::rotation(angle, 0, 1)
+    }
+
+    // Turns axis `from` towards axis `to`.
+    fn rotation(angle: &Number, from: usize, to: usize) -> Mat4 {
+        let mut sin = *angle;
+        sin.do_sin();
+        let mut cos = *angle;
+        cos.do_cos();
+        let mut minus_sin = sin;
+        minus_sin.do_neg();
+
+        let mut m = Mat4::identity();
+        m.rows[from][from] = cos;
+        m.rows[from][to] = minus_sin;
+        m.rows[to][from] = sin;
+        m.rows[to][to] = cos;
+        m
+    }
+
+    // self = self * other, so `other` is applied first.
+    pub fn do_mul(&mut self, other: &Mat4) {
+        let mut rows = [[Number::from(0); 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, entry) in row.iter_mut().enumerate() {
+                for k in 0..4 {
+                    let mut product = self.rows[i][k];
+                    product.do_mul(&other.rows[k][j]);
+                    entry.do_add(&product);
+                }
+            }
+        }
+        self.rows = rows;
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut rows = self.rows;
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, entry) in row.iter_mut().enumerate() {
+                *entry = self.rows[j][i];
+            }
+        }
+        Mat4 { rows }
+    }
+
+    // The inverse of an affine matrix, from the 3x3 part's cofactors. None if it squashes space
+    // flat, or so nearly that the inverse is out of range.
+    pub fn inverse(&self) -> Option<Mat4> {
+        let a = |i: usize, j: usize| self.rows[i % 3][j % 3];
+        let cofactor = |i: usize, j: usize| {
+            let mut c = a(i + 1, j + 1);
+            c.do_mul(&a(i + 2, j + 2));
+            let mut d = a(i + 1, j + 2);
+            d.do_mul(&a(i + 2, j + 1));
+            c.do_sub(&d);
+            c
+        };
+
+        let mut det = Number::from(0);
+        for j in 0..3 {
+            let mut term = a(0, j);
+            term.do_mul(&cofactor(0, j));
+            det.do_add(&term);
+        }
+
+        let mut inverse = Mat4::identity();
+        for i in 0..3 {
+            for j in 0..3 {
+                inverse.rows[i][j] = checked_div(&cofactor(j, i), &det)?;
+            }
+        }
+
+        // Undo the translation after the rest.
+        let mut offset = inverse.transform_vector(&Vec3 {
+            x: self.rows[0][3],
+            y: self.rows[1][3],
+            z: self.rows[2][3],
+        });
+        offset.do_neg();
+        inverse.rows[0][3] = offset.x;
+        inverse.rows[1][3] = offset.y;
+        inverse.rows[2][3] = offset.z;
+
+        Some(inverse)
+    }
+
+    pub fn transform_point(&self, point: &Vec3) -> Vec3 {
+        let mut p = self.transform_vector(point);
+        p.x.do_add(&self.rows[0][3]);
+        p.y.do_add(&self.rows[1][3]);
+        p.z.do_add(&self.rows[2][3]);
+        p
+    }
+
+    // Leaves out the translation, for directions.
+    pub fn transform_vector(&self, vector: &Vec3) -> Vec3 {
+        let row = |i: usize| Vec3 {
+            x: self.rows[i][0],
+            y: self.rows[i][1],
+            z: self.rows[i][2],
+        };
+        Vec3 {
+            x: row(0).dot(vector),
+            y: row(1).dot(vector),
+            z: row(2).dot(vector),
+        }
+    }
+}
+
+// Where an element sits in the world, relative to its own object space. Keeps the inverse and
+// the normal matrix (the inverse's transpose) alongside, rather than working them out per ray.
+#[derive(Debug, Clone, Copy)]
+pub struct Transform {
+    to_world: Mat4,
+    to_object: Mat4,
+    normal: Mat4,
+}
+
+impl Transform {
+    // Panics if `to_world` can't be undone, like a scale of zero.
+    pub fn new(to_world: &Mat4) -> Transform {
+        let to_object = to_world
+            .inverse()
+            .unwrap_or_else(|| panic!("{:?} can't be inverted", to_world));
+        Transform {
+            to_world: *to_world,
+            to_object,
+            normal: to_object.transpose(),
+        }
+    }
+
+    // The ray in object space, with its direction normalized again, and how much further it goes
+    // there for every unit in the world. Divide distances found in object space by that.
+    pub fn ray_to_object(&self, ray: &Ray) -> (Ray, Number) {
+        let mut direction = self.to_object.transform_vector(&ray.direction);
+        let stretch = direction.length();
+        direction.do_normalize();
+        (
+            Ray {
+                origin: self.to_object.transform_point(&ray.origin),
+                direction,
+            },
+            stretch,
+        )
+    }
+
+    pub fn point_to_object(&self, point: &Vec3) -> Vec3 {
+        self.to_object.transform_point(point)
+    }
+
+    pub fn point_to_world(&self, point: &Vec3) -> Vec3 {
+        self.to_world.transform_point(point)
+    }
+
+    pub fn normal_to_world(&self, normal: &Vec3) -> Vec3 {
+        let mut n = self.normal.transform_vector(normal);
+        n.do_normalize();
+        n
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Mat4, Transform};
+    use crate::fixed::PI;
+    use crate::vector::{vec3, Vec3};
+    use crate::Number;
+    use crate::Ray;
+
+    fn assert_near(actual: &Vec3, expected: &Vec3) {
+        for (a, e) in [
+            (actual.x, expected.x),
+            (actual.y, expected.y),
+            (actual.z, expected.z),
+        ]
+        .iter()
+        {
+            assert!(
+                f64::abs(a.to_f64() - e.to_f64()) < 0.01,
+                "expected {} but got {}",
+                expected,
+                actual
+            );
+        }
+    }
+
+    fn quarter_turn() -> Number {
+        let mut angle = *PI;
+        angle.do_div(&Number::from(2));
+        angle
+    }
+
+    // Scale, then turn a quarter about y, then move.
+    fn placed() -> Mat4 {
+        let mut m = Mat4::translation(&vec3(1, 2, 3));
+        m.do_mul(&Mat4::rotation_y(&quarter_turn()));
+        m.do_mul(&Mat4::scaling(&vec3(2, 3, 4)));
+        m
+    }
+
+    #[test]
+    fn test_rotations() {
+        let turn = quarter_turn();
+        assert_near(
+            &Mat4::rotation_x(&turn).transform_vector(&vec3(0, 1, 0)),
+            &vec3(0, 0, 1),
+        );
+        assert_near(
+            &Mat4::rotation_y(&turn).transform_vector(&vec3(0, 0, 1)),
+            &vec3(1, 0, 0),
+        );
+        assert_near(
+            &Mat4::rotation_z(&turn).transform_vector(&vec3(1, 0, 0)),
+            &vec3(0, 1, 0),
+        );
+    }
+
+    #[test]
+    fn test_transform_point() {
+        // Scaled to (2, 0, 0), turned to (0, 0, -2), then moved.
+        assert_near(&placed().transform_point(&vec3(1, 0, 0)), &vec3(1, 2, 1));
+        // Directions aren't moved.
+        assert_near(&placed().transform_vector(&vec3(0, 1, 0)), &vec3(0, 3, 0));
+    }
+
+    #[test]
+    fn test_inverse() {
+        let m = placed();
+        let inverse = m.inverse().unwrap();
+        for p in [vec3(0, 0, 0), vec3(1, -2, 3), vec3(-4, 5, 1)].iter() {
+            assert_near(&inverse.transform_point(&m.transform_point(p)), p);
+        }
+
+        assert!(Mat4::scaling(&vec3(1, 0, 1)).inverse().is_none());
+    }
+
+    #[test]
+    fn test_transform_ray_and_normal() {
+        let transform = Transform::new(&Mat4::scaling(&vec3(2, 1, 1)));
+
+        // Twice as long in the world as in object space along x.
+        let (ray, stretch) = transform.ray_to_object(&Ray {
+            origin: vec3(4, 0, 0),
+            direction: vec3(1, 0, 0),
+        });
+        assert_near(&ray.origin, &vec3(2, 0, 0));
+        assert_near(&ray.direction, &vec3(1, 0, 0));
+        assert!(f64::abs(stretch.to_f64() - 0.5) < 0.01);
+
+        // Stretching a 45 degree slope along x makes it steeper.
+        let mut normal = vec3(1, 1, 0);
+        normal.do_normalize();
+        let mut expected = vec3(1, 2, 0);
+        expected.do_normalize();
+        assert_near(&transform.normal_to_world(&normal), &expected);
+    }
+}
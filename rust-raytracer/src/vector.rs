@@ -8,6 +8,15 @@ pub struct Vec3 {
     pub z: Number,
 }
 
+// Shorthand for a vector of whole numbers.
+pub fn vec3(x: i16, y: i16, z: i16) -> Vec3 {
+    Vec3 {
+        x: Number::from(x),
+        y: Number::from(y),
+        z: Number::from(z),
+    }
+}
+
 impl Vec3 {
     pub fn do_normalize(&mut self) {
         let mut x = self.x;
@@ -36,9 +45,15 @@ impl Vec3 {
     }
 
     pub fn do_scale(&mut self, s: &Number) {
-        self.x.do_mul(&s);
-        self.y.do_mul(&s);
-        self.z.do_mul(&s);
+        self.x.do_mul(s);
+        self.y.do_mul(s);
+        self.z.do_mul(s);
+    }
+
+    pub fn do_neg(&mut self) {
+        self.x.do_neg();
+        self.y.do_neg();
+        self.z.do_neg();
     }
 
     pub fn do_sub(&mut self, other: &Vec3) {
@@ -51,6 +66,45 @@ impl Vec3 {
         self.dot(self)
     }
 
+    // |self| <= radius, without squaring anything too big to square.
+    pub fn is_within(&self, radius: &Number) -> bool {
+        for component in [self.x, self.y, self.z].iter_mut() {
+            component.do_abs();
+            if component.cmp(radius) > 0 {
+                return false;
+            }
+        }
+
+        let mut radius_sq = *radius;
+        radius_sq.do_mul(radius);
+        self.dist_sq().cmp(&radius_sq) <= 0
+    }
+
+    // |self|, scaled down by its largest component first so squaring can't overflow.
+    pub fn length(&self) -> Number {
+        let mut largest = Number::from(0);
+        for component in [self.x, self.y, self.z].iter_mut() {
+            component.do_abs();
+            if largest.is_less_than(component) {
+                largest = *component;
+            }
+        }
+
+        if largest.is_zero() {
+            return largest;
+        }
+
+        let mut unit = *self;
+        unit.x.do_div(&largest);
+        unit.y.do_div(&largest);
+        unit.z.do_div(&largest);
+
+        let mut length = unit.dist_sq();
+        length.do_sqrt();
+        length.do_mul(&largest);
+        length
+    }
+
     pub fn dot(&self, other: &Vec3) -> Number {
         let mut xx = self.x;
         xx.do_mul(&other.x);
@@ -67,6 +121,37 @@ impl Vec3 {
         xx
     }
 
+    // Two unit vectors perpendicular to this (normalized) vector and to each other.
+    pub fn orthonormal_basis(&self) -> (Vec3, Vec3) {
+        let mut abs_x = self.x;
+        abs_x.do_abs();
+
+        let mut half = Number::from(1);
+        half.do_div(&Number::from(2));
+
+        // Cross with whichever axis is furthest from parallel.
+        let mut tangent = if abs_x.cmp(&half) > 0 {
+            Vec3 {
+                x: Number::from(0),
+                y: Number::from(1),
+                z: Number::from(0),
+            }
+        } else {
+            Vec3 {
+                x: Number::from(1),
+                y: Number::from(0),
+                z: Number::from(0),
+            }
+        };
+        tangent.do_cross(self);
+        tangent.do_normalize();
+
+        let mut bitangent = *self;
+        bitangent.do_cross(&tangent);
+
+        (tangent, bitangent)
+    }
+
     pub fn do_cross(&mut self, other: &Vec3) {
         let mut x1 = self.y;
         x1.do_mul(&other.z);